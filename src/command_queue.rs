@@ -0,0 +1,209 @@
+//! # Command Queue
+//!
+//! Non-blocking serial input: an interrupt handler pushes received bytes into a
+//! lock-free single-producer/single-consumer queue, and the main loop drains
+//! them into lines for the console.
+//!
+//! ```rust,ignore
+//! static mut QUEUE: CommandQueue = CommandQueue::new();
+//!
+//! // Split once at startup (the only access to QUEUE), hand the producer to
+//! // the UART interrupt and keep the consumer in the main loop.
+//! let (producer, mut consumer) = unsafe { (*core::ptr::addr_of_mut!(QUEUE)).split() };
+//!
+//! // ISR: producer.push(byte);
+//! // Main loop:
+//! if let Some(response) = consumer.poll(&mut controller) {
+//!     uart.write(response.as_bytes())?;
+//! }
+//! ```
+
+use crate::console::{LineBuffer, UndoHistory, handle_line_with_history};
+use crate::{Delay, PwmBackend, ServoController};
+use anyhow::{Result, anyhow};
+use core::sync::atomic::{AtomicBool, Ordering};
+use heapless::spsc::{Consumer, Producer, Queue};
+
+/// Slots in the byte queue (it holds one less than this)
+pub const QUEUE_SIZE: usize = 256;
+
+/// Byte queue shared between an interrupt handler and the main loop
+pub struct CommandQueue {
+    queue: Queue<u8, QUEUE_SIZE>,
+    /// Set by the producer when a byte was dropped because the queue was full
+    overrun: AtomicBool,
+}
+
+impl Default for CommandQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CommandQueue {
+    pub const fn new() -> Self {
+        Self {
+            queue: Queue::new(),
+            overrun: AtomicBool::new(false),
+        }
+    }
+
+    /// Split into the interrupt side and the main-loop side
+    pub fn split(&mut self) -> (ByteProducer<'_>, LineConsumer<'_>) {
+        let (producer, consumer) = self.queue.split();
+        let overrun = &self.overrun;
+        (
+            ByteProducer { producer, overrun },
+            LineConsumer {
+                consumer,
+                overrun,
+                buffer: LineBuffer::new(),
+                history: UndoHistory::new(),
+            },
+        )
+    }
+}
+
+/// Interrupt side of a `CommandQueue`
+pub struct ByteProducer<'a> {
+    producer: Producer<'a, u8, QUEUE_SIZE>,
+    overrun: &'a AtomicBool,
+}
+
+impl ByteProducer<'_> {
+    /// Queue a received byte without blocking or allocating
+    ///
+    /// Returns `false` if the queue was full and the byte was dropped.
+    pub fn push(&mut self, byte: u8) -> bool {
+        let queued = self.producer.enqueue(byte).is_ok();
+        if !queued {
+            self.overrun.store(true, Ordering::Release);
+        }
+        queued
+    }
+}
+
+/// Main-loop side of a `CommandQueue`
+pub struct LineConsumer<'a> {
+    consumer: Consumer<'a, u8, QUEUE_SIZE>,
+    overrun: &'a AtomicBool,
+    buffer: LineBuffer,
+    /// Movement history for `undo`, kept across polled lines
+    history: UndoHistory,
+}
+
+impl LineConsumer<'_> {
+    /// Drain queued bytes until a line is complete
+    ///
+    /// Returns `None` when the queue runs dry mid-line; the partial line is
+    /// kept for the next call. If bytes were dropped since the last line, the
+    /// line being completed is reported as an error instead, since it may be
+    /// missing input.
+    pub fn pop_line(&mut self) -> Option<Result<String>> {
+        while let Some(byte) = self.consumer.dequeue() {
+            if let Some(line) = self.buffer.push(byte) {
+                if self.overrun.swap(false, Ordering::Acquire) {
+                    return Some(Err(anyhow!("input overrun, line discarded")));
+                }
+                return Some(line);
+            }
+        }
+        None
+    }
+
+    /// Run the next complete line through `handle_line_with_history`
+    ///
+    /// Returns the response to print, or `None` if no line is complete yet.
+    pub fn poll<P: PwmBackend, D: Delay>(
+        &mut self,
+        controller: &mut ServoController<P, D>,
+    ) -> Option<String> {
+        Some(match self.pop_line()? {
+            Ok(line) => handle_line_with_history(controller, &mut self.history, &line),
+            Err(err) => format!("error: {}", err),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::console::MAX_LINE_LEN;
+    use crate::test_support::*;
+
+    fn push_all(producer: &mut ByteProducer<'_>, bytes: &[u8]) {
+        for &byte in bytes {
+            assert!(producer.push(byte));
+        }
+    }
+
+    /// Test that lines are framed on newlines and partial lines wait
+    #[test]
+    fn test_pop_line_framing() {
+        let mut queue = CommandQueue::new();
+        let (mut producer, mut consumer) = queue.split();
+
+        push_all(&mut producer, b"all 4");
+        assert!(consumer.pop_line().is_none());
+
+        push_all(&mut producer, b"5\r\nstatus\n");
+        assert_eq!(consumer.pop_line().unwrap().unwrap(), "all 45");
+        assert_eq!(consumer.pop_line().unwrap().unwrap(), "");
+        assert_eq!(consumer.pop_line().unwrap().unwrap(), "status");
+        assert!(consumer.pop_line().is_none());
+    }
+
+    /// Test that a full queue drops bytes and flags the affected line
+    #[test]
+    fn test_overrun_discards_line() {
+        let mut queue = CommandQueue::new();
+        let (mut producer, mut consumer) = queue.split();
+
+        let accepted = (0..QUEUE_SIZE).filter(|_| producer.push(b'x')).count();
+        assert_eq!(accepted, QUEUE_SIZE - 1);
+        assert!(consumer.pop_line().is_none());
+
+        push_all(&mut producer, b"\ncenter\n");
+        let err = consumer.pop_line().unwrap().unwrap_err();
+        assert_eq!(err.to_string(), "input overrun, line discarded");
+        assert_eq!(consumer.pop_line().unwrap().unwrap(), "center");
+    }
+
+    /// Test that an over-long line is reported and the next one is intact
+    #[test]
+    fn test_long_line() {
+        let mut queue = CommandQueue::new();
+        let (mut producer, mut consumer) = queue.split();
+
+        push_all(&mut producer, &[b'a'; MAX_LINE_LEN + 1]);
+        push_all(&mut producer, b"\nstatus\n");
+
+        let err = consumer.pop_line().unwrap().unwrap_err();
+        assert_eq!(err.to_string(), "line longer than 128 bytes");
+        assert_eq!(consumer.pop_line().unwrap().unwrap(), "status");
+    }
+
+    /// Test that polled lines drive the controller through the console
+    #[test]
+    fn test_poll_executes_commands() {
+        let (mut controller, _log) = mock_controller();
+        let mut queue = CommandQueue::new();
+        let (mut producer, mut consumer) = queue.split();
+
+        assert_eq!(consumer.poll(&mut controller), None);
+        push_all(&mut producer, b"all 45\nstatus\njump\nundo\n");
+
+        assert_eq!(consumer.poll(&mut controller).unwrap(), "ok");
+        assert_eq!(
+            consumer.poll(&mut controller).unwrap(),
+            "angles: 45 45 45 45"
+        );
+        assert_eq!(
+            consumer.poll(&mut controller).unwrap(),
+            "error: unknown command 'jump' (try 'help')"
+        );
+        assert_eq!(consumer.poll(&mut controller).unwrap(), "ok");
+        assert_eq!(controller.current_angles(), [90; 4]);
+        assert_eq!(consumer.poll(&mut controller), None);
+    }
+}
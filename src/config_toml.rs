@@ -0,0 +1,474 @@
+//! # TOML Configuration
+//!
+//! Human-editable export of the per-leg tuning in a `ControllerConfig`, for
+//! copying a calibration from one robot to another (`config-toml` feature).
+//!
+//! Settings shared by every leg go in a `[controller]` table. Each leg is a
+//! table named after the leg without `_leg`, holding its calibration, soft
+//! limits and forbidden zones:
+//!
+//! ```toml
+//! [controller]
+//! quantize = false
+//! max_gait_duration_ms = 60000
+//! gait_lead_in_ms = 0
+//! write_strategy = "immediate"
+//!
+//! [right_back]
+//! min_pulse_us = 500
+//! max_pulse_us = 2500
+//! trim_deg = -3
+//! inverted = false
+//! center_deg = 90
+//! soft_min_deg = 0
+//! soft_max_deg = 180
+//! forbidden_zones = [[60, 80]]
+//! ```
+//!
+//! Only this subset of TOML is read: tables, `key = value` pairs with integer,
+//! boolean or string values or, for the forbidden zones, a list of `[lo, hi]`
+//! pairs, and `#` comments. The write strategy is `"immediate"` or
+//! `"batched"`. Tables and keys left out keep their defaults, as
+//! do the settings not listed here. A `max_gait_duration_ms` of 0 turns the
+//! gait cutoff off.
+
+use crate::{ControllerConfig, LEGS, Leg, PERIOD_US, WriteStrategy};
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+/// Errors reading a configuration with `ControllerConfig::from_toml`
+///
+/// Line numbers start at 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigTomlError {
+    /// The line is neither a table header, a `key = value` pair nor a comment
+    Syntax { line: usize },
+    /// The table is neither `[controller]` nor a leg
+    UnknownTable { line: usize, name: String },
+    /// The key is outside any table or not a setting of its table
+    UnknownKey { line: usize, key: String },
+    /// The value is not of the key's type
+    InvalidValue { line: usize, key: String },
+    /// The value is outside the key's valid range
+    OutOfRange {
+        line: usize,
+        key: String,
+        value: i64,
+        min: i64,
+        max: i64,
+    },
+}
+
+impl std::fmt::Display for ConfigTomlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigTomlError::Syntax { line } => write!(f, "line {}: expected `key = value`", line),
+            ConfigTomlError::UnknownTable { line, name } => {
+                write!(f, "line {}: unknown leg [{}]", line, name)
+            }
+            ConfigTomlError::UnknownKey { line, key } => {
+                write!(f, "line {}: unknown key {}", line, key)
+            }
+            ConfigTomlError::InvalidValue { line, key } => {
+                write!(f, "line {}: invalid value for {}", line, key)
+            }
+            ConfigTomlError::OutOfRange {
+                line,
+                key,
+                value,
+                min,
+                max,
+            } => write!(
+                f,
+                "line {}: {} = {} is outside {}..={}",
+                line, key, value, min, max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigTomlError {}
+
+/// Valid range of the pulse widths: a pulse cannot outlast the 50 Hz period
+const PULSE_RANGE: RangeInclusive<i64> = 0..=PERIOD_US as i64;
+
+/// Valid range of the trim
+const TRIM_RANGE: RangeInclusive<i64> = -180..=180;
+
+/// Valid range of the center and soft limit angles
+const ANGLE_RANGE: RangeInclusive<i64> = 0..=180;
+
+/// Valid range of the durations
+const DURATION_RANGE: RangeInclusive<i64> = 0..=u32::MAX as i64;
+
+/// Name of the table holding the settings shared by every leg
+const CONTROLLER_TABLE: &str = "controller";
+
+/// Table a key is read into
+#[derive(Debug, Clone, Copy)]
+enum Table {
+    Controller,
+    Leg(Leg),
+}
+
+/// TOML name of a write strategy
+fn strategy_name(strategy: WriteStrategy) -> &'static str {
+    match strategy {
+        WriteStrategy::Immediate => "immediate",
+        WriteStrategy::Batched => "batched",
+    }
+}
+
+/// Read a quoted write strategy name
+fn parse_strategy(value: &str) -> Option<WriteStrategy> {
+    match value.strip_prefix('"')?.strip_suffix('"')? {
+        "immediate" => Some(WriteStrategy::Immediate),
+        "batched" => Some(WriteStrategy::Batched),
+        _ => None,
+    }
+}
+
+/// Table name of a leg: its name without `_leg`
+fn table_name(leg: Leg) -> &'static str {
+    leg.name().trim_end_matches("_leg")
+}
+
+/// Read a `[[lo, hi], ...]` list of integer pairs
+fn parse_pairs(value: &str) -> Option<Vec<(i64, i64)>> {
+    let inner = value.strip_prefix('[')?.strip_suffix(']')?.trim();
+    if inner.is_empty() {
+        return Some(Vec::new());
+    }
+    let mut pairs = Vec::new();
+    for (index, item) in inner.split(']').enumerate() {
+        let item = item.trim();
+        if item.is_empty() && index > 0 {
+            continue;
+        }
+        let item = if index == 0 {
+            item
+        } else {
+            item.strip_prefix(',')?.trim()
+        };
+        let (lo, hi) = item.strip_prefix('[')?.split_once(',')?;
+        pairs.push((lo.trim().parse().ok()?, hi.trim().parse().ok()?));
+    }
+    Some(pairs)
+}
+
+impl ControllerConfig {
+    /// Controller-wide settings, then per-leg calibration, trim, inversion,
+    /// center, soft limits and forbidden zones as TOML
+    pub fn to_toml(&self) -> String {
+        let mut toml = format!(
+            "# cobot-rs tuning\n\
+             \n[{}]\n\
+             quantize = {}\n\
+             max_gait_duration_ms = {}\n\
+             gait_lead_in_ms = {}\n\
+             write_strategy = \"{}\"\n",
+            CONTROLLER_TABLE,
+            self.quantize,
+            self.max_gait_duration
+                .map_or(0, |max| max.as_millis().min(u32::MAX.into())),
+            self.gait_lead_in_ms,
+            strategy_name(self.write_strategy)
+        );
+        for leg in LEGS {
+            let calibration = &self.calibration[leg.index()];
+            let (soft_min_deg, soft_max_deg) = self.soft_limits[leg.index()];
+            let zones: Vec<String> = self.forbidden_zones[leg.index()]
+                .iter()
+                .map(|(lo, hi)| format!("[{}, {}]", lo, hi))
+                .collect();
+            toml.push_str(&format!(
+                "\n[{}]\n\
+                 min_pulse_us = {}\n\
+                 max_pulse_us = {}\n\
+                 trim_deg = {}\n\
+                 inverted = {}\n\
+                 center_deg = {}\n\
+                 soft_min_deg = {}\n\
+                 soft_max_deg = {}\n\
+                 forbidden_zones = [{}]\n",
+                table_name(leg),
+                calibration.min_pulse_us,
+                calibration.max_pulse_us,
+                calibration.trim_deg,
+                calibration.inverted,
+                calibration.center_deg,
+                soft_min_deg,
+                soft_max_deg,
+                zones.join(", ")
+            ));
+        }
+        toml
+    }
+
+    /// Read a configuration written by `to_toml` (or edited by hand)
+    ///
+    /// Fails on the first line that cannot be read or holds a value outside
+    /// its range; nothing else is checked, so e.g. swapped soft limits or
+    /// zone bounds are accepted and sorted out by `apply_config`.
+    pub fn from_toml(text: &str) -> Result<Self, ConfigTomlError> {
+        let mut config = ControllerConfig::default();
+        let mut table = None;
+
+        for (index, raw) in text.lines().enumerate() {
+            let line = index + 1;
+            let content = raw.split('#').next().unwrap_or_default().trim();
+            if content.is_empty() {
+                continue;
+            }
+
+            if let Some(name) = content
+                .strip_prefix('[')
+                .and_then(|rest| rest.strip_suffix(']'))
+            {
+                let name = name.trim();
+                table = Some(if name == CONTROLLER_TABLE {
+                    Table::Controller
+                } else {
+                    Table::Leg(Leg::from_name(name).ok_or_else(|| {
+                        ConfigTomlError::UnknownTable {
+                            line,
+                            name: name.to_string(),
+                        }
+                    })?)
+                });
+                continue;
+            }
+
+            let (key, value) = content
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or(ConfigTomlError::Syntax { line })?;
+            let Some(table) = table else {
+                return Err(ConfigTomlError::UnknownKey {
+                    line,
+                    key: key.to_string(),
+                });
+            };
+            let key_path = match table {
+                Table::Controller => format!("{}.{}", CONTROLLER_TABLE, key),
+                Table::Leg(leg) => format!("{}.{}", table_name(leg), key),
+            };
+            let invalid = || ConfigTomlError::InvalidValue {
+                line,
+                key: key_path.clone(),
+            };
+            let in_range = |value: i64, range: RangeInclusive<i64>| {
+                if !range.contains(&value) {
+                    return Err(ConfigTomlError::OutOfRange {
+                        line,
+                        key: key_path.clone(),
+                        value,
+                        min: *range.start(),
+                        max: *range.end(),
+                    });
+                }
+                Ok(value)
+            };
+            let integer = |range: RangeInclusive<i64>| -> Result<i64, ConfigTomlError> {
+                in_range(value.parse().map_err(|_| invalid())?, range)
+            };
+
+            let leg = match table {
+                Table::Controller => {
+                    match key {
+                        "quantize" => config.quantize = value.parse().map_err(|_| invalid())?,
+                        "max_gait_duration_ms" => {
+                            config.max_gait_duration = match integer(DURATION_RANGE)? {
+                                0 => None,
+                                ms => Some(Duration::from_millis(ms as u64)),
+                            }
+                        }
+                        "gait_lead_in_ms" => {
+                            config.gait_lead_in_ms = integer(DURATION_RANGE)? as u32
+                        }
+                        "write_strategy" => {
+                            config.write_strategy = parse_strategy(value).ok_or_else(invalid)?
+                        }
+                        _ => {
+                            return Err(ConfigTomlError::UnknownKey {
+                                line,
+                                key: key_path,
+                            });
+                        }
+                    }
+                    continue;
+                }
+                Table::Leg(leg) => leg,
+            };
+            let calibration = &mut config.calibration[leg.index()];
+            let soft_limits = &mut config.soft_limits[leg.index()];
+            match key {
+                "min_pulse_us" => calibration.min_pulse_us = integer(PULSE_RANGE)? as u32,
+                "max_pulse_us" => calibration.max_pulse_us = integer(PULSE_RANGE)? as u32,
+                "trim_deg" => calibration.trim_deg = integer(TRIM_RANGE)? as i32,
+                "center_deg" => calibration.center_deg = integer(ANGLE_RANGE)? as u32,
+                "soft_min_deg" => soft_limits.0 = integer(ANGLE_RANGE)? as u32,
+                "soft_max_deg" => soft_limits.1 = integer(ANGLE_RANGE)? as u32,
+                "inverted" => calibration.inverted = value.parse().map_err(|_| invalid())?,
+                "forbidden_zones" => {
+                    let mut zones = Vec::new();
+                    for (lo, hi) in parse_pairs(value).ok_or_else(invalid)? {
+                        zones.push((
+                            in_range(lo, ANGLE_RANGE)? as u32,
+                            in_range(hi, ANGLE_RANGE)? as u32,
+                        ));
+                    }
+                    config.forbidden_zones[leg.index()] = zones;
+                }
+                _ => {
+                    return Err(ConfigTomlError::UnknownKey {
+                        line,
+                        key: key_path,
+                    });
+                }
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LegCalibration;
+
+    /// Config with different tuning on every leg
+    fn tuned_config() -> ControllerConfig {
+        let mut config = ControllerConfig::default();
+        config.calibration[0] = LegCalibration {
+            min_pulse_us: 600,
+            max_pulse_us: 2400,
+            trim_deg: -7,
+            inverted: false,
+            center_deg: 85,
+        };
+        config.calibration[1].inverted = true;
+        config.calibration[2].trim_deg = 12;
+        config.calibration[3].center_deg = 100;
+        config.soft_limits = [(0, 180), (20, 160), (45, 135), (0, 90)];
+        config.forbidden_zones[2] = vec![(60, 80), (100, 110)];
+        config.quantize = true;
+        config.max_gait_duration = Some(Duration::from_secs(90));
+        config.gait_lead_in_ms = 250;
+        config.write_strategy = WriteStrategy::Batched;
+        config
+    }
+
+    /// Test that the per-leg tuning survives a round trip and the rest of the
+    /// config keeps its defaults
+    #[test]
+    fn test_toml_round_trip() {
+        let config = tuned_config();
+        let toml = config.to_toml();
+        assert!(toml.contains("[controller]\nquantize = true\nmax_gait_duration_ms = 90000\n"));
+        assert!(toml.contains("gait_lead_in_ms = 250\nwrite_strategy = \"batched\"\n"));
+        assert!(toml.contains("[right_back]\nmin_pulse_us = 600\n"));
+        assert!(toml.contains("[left_back]"));
+        assert!(toml.contains("forbidden_zones = [[60, 80], [100, 110]]\n"));
+        assert_eq!(ControllerConfig::from_toml(&toml), Ok(config));
+        assert_eq!(
+            ControllerConfig::from_toml(&ControllerConfig::default().to_toml()),
+            Ok(ControllerConfig::default())
+        );
+    }
+
+    /// Test that hand edits with comments, spacing and omissions are read
+    #[test]
+    fn test_toml_hand_edited() {
+        let text = "# spare robot\n\
+                    [ left_front ]\n\
+                    trim_deg=-4   # tuned on the bench\n\
+                    \n\
+                    [rb]\n\
+                    inverted = true\n";
+
+        let config = ControllerConfig::from_toml(text).unwrap();
+
+        assert_eq!(config.calibration[Leg::LeftFront.index()].trim_deg, -4);
+        assert!(config.calibration[Leg::RightBack.index()].inverted);
+        assert_eq!(
+            config.calibration[Leg::RightFront.index()],
+            LegCalibration::default()
+        );
+
+        // A cutoff of 0 turns it off
+        let uncut = ControllerConfig::from_toml("[controller]\nmax_gait_duration_ms = 0").unwrap();
+        assert_eq!(uncut.max_gait_duration, None);
+        assert!(uncut.to_toml().contains("max_gait_duration_ms = 0\n"));
+    }
+
+    /// Test that out-of-range and malformed values are rejected clearly
+    #[test]
+    fn test_toml_rejects_invalid() {
+        let err = ControllerConfig::from_toml("[right_front]\ncenter_deg = 90\ntrim_deg = 400\n")
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ConfigTomlError::OutOfRange {
+                line: 3,
+                key: "right_front.trim_deg".to_string(),
+                value: 400,
+                min: -180,
+                max: 180,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "line 3: right_front.trim_deg = 400 is outside -180..=180"
+        );
+
+        assert!(matches!(
+            ControllerConfig::from_toml("[left_back]\nmax_pulse_us = 30000"),
+            Err(ConfigTomlError::OutOfRange { value: 30000, .. })
+        ));
+        assert_eq!(
+            ControllerConfig::from_toml("[left_back]\ninverted = yes"),
+            Err(ConfigTomlError::InvalidValue {
+                line: 2,
+                key: "left_back.inverted".to_string()
+            })
+        );
+        assert_eq!(
+            ControllerConfig::from_toml("trim_deg = 1"),
+            Err(ConfigTomlError::UnknownKey {
+                line: 1,
+                key: "trim_deg".to_string()
+            })
+        );
+        assert_eq!(
+            ControllerConfig::from_toml("[controller]\ntrim_deg = 1"),
+            Err(ConfigTomlError::UnknownKey {
+                line: 2,
+                key: "controller.trim_deg".to_string()
+            })
+        );
+        assert_eq!(
+            ControllerConfig::from_toml("[controller]\nwrite_strategy = batched"),
+            Err(ConfigTomlError::InvalidValue {
+                line: 2,
+                key: "controller.write_strategy".to_string()
+            })
+        );
+        assert!(matches!(
+            ControllerConfig::from_toml("[middle_leg]"),
+            Err(ConfigTomlError::UnknownTable { line: 1, .. })
+        ));
+        assert!(matches!(
+            ControllerConfig::from_toml("[left_back]\nforbidden_zones = [[60, 200]]"),
+            Err(ConfigTomlError::OutOfRange { value: 200, .. })
+        ));
+        assert!(matches!(
+            ControllerConfig::from_toml("[left_back]\nforbidden_zones = [60, 80]"),
+            Err(ConfigTomlError::InvalidValue { line: 2, .. })
+        ));
+        assert_eq!(
+            ControllerConfig::from_toml("[left_back]\ntrim_deg"),
+            Err(ConfigTomlError::Syntax { line: 2 })
+        );
+    }
+}
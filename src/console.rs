@@ -0,0 +1,550 @@
+//! # Console
+//!
+//! A line-based command shell for driving the robot interactively over the
+//! serial console.
+//!
+//! `parse_command` turns a line into a `ShellCommand`, `handle_line` parses and
+//! executes it and returns the text to print, `LineBuffer` frames raw bytes
+//! into lines, and `run_repl` (ESP32 only) wires that to a UART. Keeping the
+//! line handling free of I/O makes it testable off-device.
+//!
+//! Sessions keep an `UndoHistory` of the poses before each movement, so
+//! `undo` can step back; use `handle_line_with_history` to share one across
+//! lines.
+
+use crate::{Delay, Leg, Ms, PwmBackend, ServoController};
+#[cfg(feature = "esp32")]
+use crate::{LEGS, diagnostics::detect_wiring_remap};
+use anyhow::{Result, bail};
+#[cfg(feature = "esp32")]
+use esp_idf_hal::{delay::BLOCK, uart::UartDriver};
+use std::collections::VecDeque;
+
+/// Syntax of every command, printed by `help`
+pub const HELP: &str = "\
+commands:
+  help                               show this message
+  status                             print current angles (rb lb rf lf)
+  center                             center all legs
+  all <deg>                          set all legs to one angle
+  leg <leg> <deg>                    set one leg (rb, lb, rf, lf)
+  angles <rb> <lb> <rf> <lf>         set all four legs
+  define <name> <rb> <lb> <rf> <lf>  store a named pose
+  pose <name>                        move to a named pose
+  detach                             relax all legs
+  walk <ms>                          walk forward once
+  wave <ms>                          wave the front right leg
+  undo                               return to the pose before the last move
+  map <leg>                          print angle,pulse_us,duty for 0-180°
+  wiring                             check for swapped connectors (serial only)";
+
+/// A parsed console command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShellCommand {
+    Help,
+    Status,
+    Center,
+    All(u32),
+    Leg(Leg, u32),
+    Angles([u32; 4]),
+    Define(String, [u32; 4]),
+    Pose(String),
+    Detach,
+    Walk(u32),
+    Wave(u32),
+    Undo,
+    Map(Leg),
+    Wiring,
+}
+
+impl ShellCommand {
+    /// Whether the command moves legs, and so can be undone
+    fn is_movement(&self) -> bool {
+        matches!(
+            self,
+            ShellCommand::Center
+                | ShellCommand::All(_)
+                | ShellCommand::Leg(..)
+                | ShellCommand::Angles(_)
+                | ShellCommand::Pose(_)
+                | ShellCommand::Walk(_)
+                | ShellCommand::Wave(_)
+        )
+    }
+}
+
+/// Most poses an `UndoHistory` keeps; older ones are dropped
+pub const MAX_UNDO_DEPTH: usize = 16;
+
+/// Poses the legs held before recent movement commands, newest last
+#[derive(Debug, Default)]
+pub struct UndoHistory {
+    poses: VecDeque<[u32; 4]>,
+}
+
+impl UndoHistory {
+    pub const fn new() -> Self {
+        Self {
+            poses: VecDeque::new(),
+        }
+    }
+
+    /// Remember a pose, dropping the oldest beyond `MAX_UNDO_DEPTH`
+    pub fn record(&mut self, angles: [u32; 4]) {
+        if self.poses.len() == MAX_UNDO_DEPTH {
+            self.poses.pop_front();
+        }
+        self.poses.push_back(angles);
+    }
+
+    /// Take the most recent pose
+    pub fn pop(&mut self) -> Option<[u32; 4]> {
+        self.poses.pop_back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.poses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.poses.is_empty()
+    }
+}
+
+/// Parse one console line (case-insensitive, whitespace-separated)
+pub fn parse_command(line: &str) -> Result<ShellCommand> {
+    let line = line.to_ascii_lowercase();
+    let mut words = line.split_whitespace();
+    let Some(name) = words.next() else {
+        bail!("empty command");
+    };
+    let args: Vec<&str> = words.collect();
+
+    let command = match (name, args.as_slice()) {
+        ("help" | "?", []) => ShellCommand::Help,
+        ("status", []) => ShellCommand::Status,
+        ("center", []) => ShellCommand::Center,
+        ("all", [deg]) => ShellCommand::All(parse_number(deg)?),
+        ("leg", [leg, deg]) => ShellCommand::Leg(parse_leg(leg)?, parse_number(deg)?),
+        ("angles", [rb, lb, rf, lf]) => ShellCommand::Angles([
+            parse_number(rb)?,
+            parse_number(lb)?,
+            parse_number(rf)?,
+            parse_number(lf)?,
+        ]),
+        ("define", [name, rb, lb, rf, lf]) => ShellCommand::Define(
+            name.to_string(),
+            [
+                parse_number(rb)?,
+                parse_number(lb)?,
+                parse_number(rf)?,
+                parse_number(lf)?,
+            ],
+        ),
+        ("pose", [name]) => ShellCommand::Pose(name.to_string()),
+        ("detach", []) => ShellCommand::Detach,
+        ("walk", [ms]) => ShellCommand::Walk(parse_number(ms)?),
+        ("wave", [ms]) => ShellCommand::Wave(parse_number(ms)?),
+        ("undo", []) => ShellCommand::Undo,
+        ("map", [leg]) => ShellCommand::Map(parse_leg(leg)?),
+        ("wiring", []) => ShellCommand::Wiring,
+        (
+            "help" | "?" | "status" | "center" | "all" | "leg" | "angles" | "define" | "pose"
+            | "detach" | "walk" | "wave" | "undo" | "map" | "wiring",
+            _,
+        ) => bail!("wrong arguments for '{}' (try 'help')", name),
+        _ => bail!("unknown command '{}' (try 'help')", name),
+    };
+    Ok(command)
+}
+
+fn parse_number(word: &str) -> Result<u32> {
+    word.parse()
+        .map_err(|_| anyhow::anyhow!("'{}' is not a number", word))
+}
+
+fn parse_leg(word: &str) -> Result<Leg> {
+    Leg::from_name(word).ok_or_else(|| anyhow::anyhow!("unknown leg '{}'", word))
+}
+
+/// Execute a parsed command and return the text to print
+///
+/// Without a session history there is nothing for `undo` to return to; see
+/// `execute_with_history`.
+pub fn execute<P: PwmBackend, D: Delay>(
+    controller: &mut ServoController<P, D>,
+    command: ShellCommand,
+) -> Result<String> {
+    execute_with_history(controller, &mut UndoHistory::new(), command)
+}
+
+/// Execute a parsed command, recording movements in `history` for `undo`
+///
+/// A movement is recorded if it succeeds or moved any leg before failing.
+/// `undo` re-commands the most recent recorded pose and is not itself
+/// recorded, so repeated undos keep stepping back.
+pub fn execute_with_history<P: PwmBackend, D: Delay>(
+    controller: &mut ServoController<P, D>,
+    history: &mut UndoHistory,
+    command: ShellCommand,
+) -> Result<String> {
+    if command == ShellCommand::Undo
+        && let Some([right_back, left_back, right_front, left_front]) = history.pop()
+    {
+        controller.set_servo_angles(right_back, left_back, right_front, left_front)?;
+        return Ok("ok".to_string());
+    }
+    if command.is_movement() {
+        let before = controller.current_angles();
+        let result = run(controller, command);
+        if result.is_ok() || controller.current_angles() != before {
+            history.record(before);
+        }
+        return result;
+    }
+    run(controller, command)
+}
+
+fn run<P: PwmBackend, D: Delay>(
+    controller: &mut ServoController<P, D>,
+    command: ShellCommand,
+) -> Result<String> {
+    match command {
+        ShellCommand::Help => return Ok(HELP.to_string()),
+        ShellCommand::Status => {
+            let angles = controller.current_angles().map(|a| a.to_string());
+            return Ok(format!("angles: {}", angles.join(" ")));
+        }
+        ShellCommand::Center => controller.center_all_servos()?,
+        ShellCommand::All(angle) => controller.set_all_servos_angle(angle)?,
+        ShellCommand::Leg(leg, angle) => controller.set_leg_angle(leg, angle)?,
+        ShellCommand::Angles(angles) => controller.set_from_slice(&angles)?,
+        ShellCommand::Define(name, angles) => controller.define_pose(&name, angles)?,
+        ShellCommand::Pose(name) => controller.goto_pose(&name)?,
+        ShellCommand::Detach => controller.detach_all()?,
+        ShellCommand::Walk(ms) => controller.walk_forward(Ms(ms))?,
+        ShellCommand::Wave(ms) => controller.wave(Ms(ms))?,
+        ShellCommand::Undo => bail!("nothing to undo"),
+        ShellCommand::Map(leg) => return controller.dump_mapping(leg),
+        ShellCommand::Wiring => bail!("'wiring' is interactive; run it from the serial console"),
+    }
+    Ok("ok".to_string())
+}
+
+/// Parse and execute one console line, returning the response text
+///
+/// Errors are reported in the response (prefixed with `error: `) rather than
+/// returned, so a bad line never ends the session. Blank lines get an empty
+/// response.
+pub fn handle_line<P: PwmBackend, D: Delay>(
+    controller: &mut ServoController<P, D>,
+    line: &str,
+) -> String {
+    handle_line_with_history(controller, &mut UndoHistory::new(), line)
+}
+
+/// `handle_line` for a session, recording movements in `history` for `undo`
+pub fn handle_line_with_history<P: PwmBackend, D: Delay>(
+    controller: &mut ServoController<P, D>,
+    history: &mut UndoHistory,
+    line: &str,
+) -> String {
+    if line.trim().is_empty() {
+        return String::new();
+    }
+
+    match parse_command(line).and_then(|command| execute_with_history(controller, history, command))
+    {
+        Ok(response) => response,
+        Err(err) => format!("error: {}", err),
+    }
+}
+
+/// Longest accepted console line; longer input is discarded up to the newline
+pub const MAX_LINE_LEN: usize = 128;
+
+/// Assembles console bytes into lines
+///
+/// `\r` and `\n` both end a line, so `\r\n` also yields an empty line.
+#[derive(Debug, Default)]
+pub struct LineBuffer {
+    line: Vec<u8>,
+    overflow: bool,
+}
+
+impl LineBuffer {
+    pub const fn new() -> Self {
+        Self {
+            line: Vec::new(),
+            overflow: false,
+        }
+    }
+
+    /// Add one byte, returning the finished line when it is a line ending
+    ///
+    /// A line longer than `MAX_LINE_LEN` is returned as an error.
+    pub fn push(&mut self, byte: u8) -> Option<Result<String>> {
+        match byte {
+            b'\r' | b'\n' => {
+                let line = if self.overflow {
+                    Err(anyhow::anyhow!("line longer than {} bytes", MAX_LINE_LEN))
+                } else {
+                    Ok(String::from_utf8_lossy(&self.line).into_owned())
+                };
+                self.clear();
+                Some(line)
+            }
+            _ if self.line.len() == MAX_LINE_LEN => {
+                self.overflow = true;
+                None
+            }
+            b => {
+                self.line.push(b);
+                None
+            }
+        }
+    }
+
+    /// Discard the partial line
+    pub fn clear(&mut self) {
+        self.line.clear();
+        self.overflow = false;
+    }
+}
+
+/// Run an interactive shell on `uart` forever
+///
+/// Reads bytes until a newline, runs the line through `handle_line` and writes
+/// the response back followed by a prompt. Returns only on a UART error.
+#[cfg(feature = "esp32")]
+pub fn run_repl<P: PwmBackend, D: Delay>(
+    controller: &mut ServoController<P, D>,
+    uart: &UartDriver<'_>,
+) -> Result<()> {
+    let mut buffer = LineBuffer::new();
+    let mut history = UndoHistory::new();
+    let mut byte = [0u8; 1];
+
+    uart.write(b"cobot ready, type 'help'\r\n> ")?;
+    loop {
+        if uart.read(&mut byte, BLOCK)? == 0 {
+            continue;
+        }
+
+        if let Some(line) = buffer.push(byte[0]) {
+            let response = match line {
+                Ok(line) if matches!(parse_command(&line), Ok(ShellCommand::Wiring)) => {
+                    match run_wiring_check(controller, uart) {
+                        Ok(()) => "ok".to_string(),
+                        Err(err) => format!("error: {}", err),
+                    }
+                }
+                Ok(line) => handle_line_with_history(controller, &mut history, &line),
+                Err(err) => format!("error: {}", err),
+            };
+            if !response.is_empty() {
+                uart.write(response.replace('\n', "\r\n").as_bytes())?;
+                uart.write(b"\r\n")?;
+            }
+            uart.write(b"> ")?;
+        }
+    }
+}
+
+/// Read the next non-blank line from `uart`, reporting over-long lines
+#[cfg(feature = "esp32")]
+fn read_line(uart: &UartDriver<'_>, buffer: &mut LineBuffer) -> Result<String> {
+    let mut byte = [0u8; 1];
+    loop {
+        if uart.read(&mut byte, BLOCK)? == 0 {
+            continue;
+        }
+        match buffer.push(byte[0]) {
+            Some(Ok(line)) if !line.trim().is_empty() => return Ok(line),
+            Some(Err(err)) => {
+                uart.write(format!("error: {}\r\n", err).as_bytes())?;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Guided check for swapped servo connectors on `uart`
+///
+/// Probes each leg in turn (`ServoController::probe_wiring`), asks which leg
+/// actually moved, and if the answers show a swap offers to apply the
+/// corrected wiring. Save `controller.config()` to keep it.
+#[cfg(feature = "esp32")]
+pub fn run_wiring_check<P: PwmBackend, D: Delay>(
+    controller: &mut ServoController<P, D>,
+    uart: &UartDriver<'_>,
+) -> Result<()> {
+    let mut buffer = LineBuffer::new();
+    let mut observed = LEGS;
+
+    for leg in LEGS {
+        controller.probe_wiring(leg)?;
+        observed[leg.index()] = loop {
+            uart.write(b"which leg moved? (rb, lb, rf, lf) ")?;
+            let line = read_line(uart, &mut buffer)?.to_ascii_lowercase();
+            match parse_leg(line.trim()) {
+                Ok(moved) => break moved,
+                Err(err) => {
+                    uart.write(format!("error: {}\r\n", err).as_bytes())?;
+                }
+            }
+        };
+    }
+    controller.center_all_servos()?;
+
+    let Some(wiring) = detect_wiring_remap(controller.wiring(), observed)? else {
+        uart.write(b"wiring ok\r\n")?;
+        return Ok(());
+    };
+    for (commanded, moved) in LEGS.into_iter().zip(observed) {
+        if commanded != moved {
+            let report = format!("{} moved {}\r\n", commanded.name(), moved.name());
+            uart.write(report.as_bytes())?;
+        }
+    }
+    uart.write(b"apply corrected wiring? (y/n) ")?;
+    if read_line(uart, &mut buffer)?
+        .trim()
+        .eq_ignore_ascii_case("y")
+    {
+        controller.set_wiring(wiring)?;
+        uart.write(b"wiring updated\r\n")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LEGS;
+    use crate::test_support::*;
+
+    /// Test that a script of lines produces the expected responses
+    #[test]
+    fn test_handle_line_script() {
+        let (mut controller, _log) = mock_controller();
+
+        let script = [
+            ("status", "angles: 90 90 90 90"),
+            ("all 45", "ok"),
+            ("leg rf 180", "ok"),
+            ("  ANGLES 10 20 30 40  ", "ok"),
+            ("status", "angles: 10 20 30 40"),
+            ("define sit 0 0 90 90", "ok"),
+            ("center", "ok"),
+            ("status", "angles: 90 90 90 90"),
+            ("pose sit", "ok"),
+            ("status", "angles: 0 0 90 90"),
+            ("", ""),
+        ];
+        for (line, expected) in script {
+            assert_eq!(
+                handle_line(&mut controller, line),
+                expected,
+                "line: {line:?}"
+            );
+        }
+    }
+
+    /// Test that help lists the command syntax
+    #[test]
+    fn test_help() {
+        let (mut controller, log) = mock_controller();
+
+        let response = handle_line(&mut controller, "help");
+
+        assert_eq!(response, HELP);
+        for command in ["status", "all <deg>", "leg <leg> <deg>", "angles"] {
+            assert!(response.contains(command), "help is missing {command}");
+        }
+        assert!(log.commands().is_empty());
+    }
+
+    /// Test that bad lines are reported without moving anything
+    #[test]
+    fn test_handle_line_errors() {
+        let (mut controller, log) = mock_controller();
+
+        let script = [
+            ("jump", "error: unknown command 'jump' (try 'help')"),
+            ("all", "error: wrong arguments for 'all' (try 'help')"),
+            ("all high", "error: 'high' is not a number"),
+            ("leg tail 10", "error: unknown leg 'tail'"),
+            ("pose dance", "error: pose 'dance' is not defined"),
+            (
+                "angles 1 2 200 4",
+                "error: right_front_leg angle 200° is outside 0-180°",
+            ),
+        ];
+        for (line, expected) in script {
+            assert_eq!(
+                handle_line(&mut controller, line),
+                expected,
+                "line: {line:?}"
+            );
+        }
+        assert!(log.duties().is_empty());
+    }
+
+    /// Test parsing of every command form
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse_command("?").unwrap(), ShellCommand::Help);
+        assert_eq!(
+            parse_command("leg left_back_leg 5").unwrap(),
+            ShellCommand::Leg(Leg::LeftBack, 5)
+        );
+        assert_eq!(parse_command("walk 300").unwrap(), ShellCommand::Walk(300));
+        assert_eq!(parse_command("detach").unwrap(), ShellCommand::Detach);
+        assert_eq!(parse_command("wiring").unwrap(), ShellCommand::Wiring);
+        assert_eq!(parse_command("UNDO").unwrap(), ShellCommand::Undo);
+        assert_eq!(
+            parse_command("map lf").unwrap(),
+            ShellCommand::Map(Leg::LeftFront)
+        );
+        assert!(parse_command("   ").is_err());
+        assert_eq!(LEGS.map(|leg| Leg::from_name(leg.name())), LEGS.map(Some));
+    }
+
+    /// Test that undo steps back through the poses before each move
+    #[test]
+    fn test_undo() {
+        let (mut controller, _log) = mock_controller();
+        let mut history = UndoHistory::new();
+        let mut run = |line: &str| handle_line_with_history(&mut controller, &mut history, line);
+
+        assert_eq!(run("undo"), "error: nothing to undo");
+        assert_eq!(run("angles 10 20 30 40"), "ok");
+        assert_eq!(run("leg rf 180"), "ok");
+        // Neither a failed move nor a non-movement is recorded
+        assert_eq!(run("pose dance"), "error: pose 'dance' is not defined");
+        assert_eq!(run("define sit 0 0 90 90"), "ok");
+        assert_eq!(run("status"), "angles: 10 20 180 40");
+
+        assert_eq!(run("undo"), "ok");
+        assert_eq!(run("status"), "angles: 10 20 30 40");
+        assert_eq!(run("undo"), "ok");
+        assert_eq!(run("status"), "angles: 90 90 90 90");
+        assert_eq!(run("undo"), "error: nothing to undo");
+    }
+
+    /// Test that the history keeps only the most recent poses
+    #[test]
+    fn test_undo_history_bounded() {
+        let mut history = UndoHistory::new();
+        for angle in 0..MAX_UNDO_DEPTH as u32 + 5 {
+            history.record([angle; 4]);
+        }
+
+        assert_eq!(history.len(), MAX_UNDO_DEPTH);
+        assert_eq!(history.pop(), Some([MAX_UNDO_DEPTH as u32 + 4; 4]));
+        let oldest = std::iter::from_fn(|| history.pop()).last();
+        assert_eq!(oldest, Some([5; 4]));
+        assert!(history.is_empty());
+    }
+}
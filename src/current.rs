@@ -0,0 +1,117 @@
+//! # Current Estimate
+//!
+//! A rough supply-current budget without a current sensor.
+//!
+//! This is a heuristic, not a measurement. A servo draws a small holding
+//! current while driven and a burst while it moves, roughly in proportion to
+//! how far it has to go. So every attached leg counts `hold_ma`, and a leg
+//! that moved recently adds `move_ma_per_deg` per degree of its latest move,
+//! fading linearly to nothing over `move_window_ms`. Only the latest move of
+//! each leg counts. Tune the coefficients for your servos and supply; use the
+//! estimate to hold back big moves while it is near the budget.
+
+use crate::{Delay, LEGS, PwmBackend, ServoController};
+
+/// Coefficients of `ServoController::estimated_current_ma`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CurrentModel {
+    /// Draw of a leg holding its position
+    pub hold_ma: u32,
+    /// Extra draw per degree of a leg's latest move, right after the move
+    pub move_ma_per_deg: u32,
+    /// Time over which a move's extra draw fades out
+    pub move_window_ms: u32,
+}
+
+impl Default for CurrentModel {
+    /// Ballpark figures for SG90-class micro servos
+    fn default() -> Self {
+        Self {
+            hold_ma: 10,
+            move_ma_per_deg: 5,
+            move_window_ms: 300,
+        }
+    }
+}
+
+impl<P: PwmBackend, D: Delay> ServoController<P, D> {
+    /// Set the coefficients of `estimated_current_ma`
+    pub fn set_current_model(&mut self, model: CurrentModel) {
+        self.current_model = model;
+    }
+
+    /// Coefficients of `estimated_current_ma`
+    pub fn current_model(&self) -> CurrentModel {
+        self.current_model
+    }
+
+    /// Estimated supply current of all servos right now, in mA
+    ///
+    /// Sums `CurrentModel` contributions of the legs being driven: disabled
+    /// legs, detached legs and legs never written draw nothing.
+    pub fn estimated_current_ma(&self) -> u32 {
+        let model = self.current_model;
+        let now = self.clock.now();
+        LEGS.into_iter()
+            .filter(|leg| {
+                let i = leg.index();
+                self.enabled[i] && !self.detached[i] && self.duties[i].is_some()
+            })
+            .map(|leg| {
+                let moving = self.last_moves[leg.index()].map_or(0, |(deg, at)| {
+                    let window = u64::from(model.move_window_ms);
+                    let elapsed = now.saturating_duration_since(at).as_millis() as u64;
+                    let peak = u64::from(deg) * u64::from(model.move_ma_per_deg);
+                    peak * window.saturating_sub(elapsed) / window.max(1)
+                });
+                u64::from(model.hold_ma) + moving
+            })
+            .sum::<u64>()
+            .min(u64::from(u32::MAX)) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Leg;
+    use crate::test_support::*;
+
+    /// Test that more and larger moves raise the estimate, which then fades
+    /// back to the holding current
+    #[test]
+    fn test_estimated_current() {
+        let (controller, _log) = mock_controller();
+        let clock = MockClock::new();
+        let mut controller = controller.with_clock(clock.clone());
+        assert_eq!(controller.estimated_current_ma(), 0, "nothing driven yet");
+
+        // From 90°: one leg moving 10° (10 + 50 mA) and three holding
+        controller.set_all_servos_angle(90).unwrap();
+        clock.advance(1000);
+        controller.set_leg_angle(Leg::RightBack, 100).unwrap();
+        let one_small = controller.estimated_current_ma();
+        assert_eq!(one_small, 4 * 10 + 50);
+
+        controller.set_leg_angle(Leg::RightBack, 150).unwrap();
+        let one_large = controller.estimated_current_ma();
+        assert!(one_large > one_small);
+
+        controller.set_servo_angles(100, 140, 140, 140).unwrap();
+        let four = controller.estimated_current_ma();
+        assert!(four > one_large);
+
+        clock.advance(150);
+        assert_eq!(controller.estimated_current_ma(), 40 + (4 * 50 * 5) / 2);
+        clock.advance(150);
+        assert_eq!(controller.estimated_current_ma(), 40);
+
+        // Relaxed legs draw nothing, and the coefficients are tunable
+        controller.detach_leg(Leg::LeftFront).unwrap();
+        controller.set_current_model(CurrentModel {
+            hold_ma: 25,
+            ..Default::default()
+        });
+        assert_eq!(controller.estimated_current_ma(), 3 * 25);
+    }
+}
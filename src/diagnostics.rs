@@ -0,0 +1,741 @@
+//! # Diagnostics
+//!
+//! Bring-up and health checks for the servo hardware.
+
+use crate::{
+    ControllerConfig, Delay, Easing, LEGS, Leg, LegCalibration, MoveInterpolator, PwmBackend,
+    ServoController, ServoError, is_valid_wiring, period_us,
+};
+use anyhow::Result;
+
+/// Angle commanded by the startup health check
+pub const HEALTH_CHECK_ANGLE: u32 = 90;
+
+/// Angle a leg is moved to by `probe_wiring`
+pub const WIRING_PROBE_ANGLE: u32 = 45;
+
+/// Source of measured servo positions (e.g. feedback servos or encoders)
+pub trait PositionFeedback {
+    /// Measured angle of a leg, or `None` if no reading is available
+    fn read_angle(&mut self, leg: Leg) -> Option<u32>;
+}
+
+/// A leg that failed the health check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HealthFailure {
+    pub leg: Leg,
+    /// Measured angle, `None` if the leg gave no reading
+    pub measured: Option<u32>,
+}
+
+/// Result of `ServoController::health_check`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HealthReport {
+    /// Whether positions were verified with feedback (otherwise timed only)
+    pub verified: bool,
+    /// Legs that did not reach the target within tolerance
+    pub failures: Vec<HealthFailure>,
+}
+
+/// One step of a planned move, from `ServoController::profile_move`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MoveSample {
+    /// Time since the start of the move
+    pub t_ms: u32,
+    /// Physical angle commanded at this step
+    pub angle: u32,
+    /// Speed since the previous step in degrees per second (signed)
+    pub velocity_dps: f32,
+    /// Change in speed since the previous step in degrees per second squared
+    pub accel_dps2: f32,
+}
+
+/// Build and tuning identification, from `ServoController::firmware_info`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FirmwareInfo {
+    /// Version of this crate the firmware was built with
+    pub version: &'static str,
+    /// `config_hash` of the settings in use
+    pub config_hash: u32,
+}
+
+impl std::fmt::Display for FirmwareInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "cobot-rs {} (config {:08x})",
+            self.version, self.config_hash
+        )
+    }
+}
+
+/// Short fingerprint of a configuration, for telling tunings apart
+///
+/// FNV-1a over every setting, so any change to the calibration, limits or
+/// other tuning gives a different hash (barring a rare collision). Stable for
+/// a given build; not meant to be compared across crate versions.
+pub fn config_hash(config: &ControllerConfig) -> u32 {
+    format!("{:?}", config)
+        .bytes()
+        .fold(0x811c_9dc5, |hash, byte| {
+            (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+        })
+}
+
+impl HealthReport {
+    /// Build a report from per-leg readings (in `set_servo_angles` order)
+    pub fn from_readings(readings: [Option<u32>; 4], target: u32, tolerance_deg: u32) -> Self {
+        let failures = LEGS
+            .into_iter()
+            .zip(readings)
+            .filter(|&(_, measured)| match measured {
+                Some(angle) => angle.abs_diff(target) > tolerance_deg,
+                None => true,
+            })
+            .map(|(leg, measured)| HealthFailure { leg, measured })
+            .collect();
+
+        Self {
+            verified: true,
+            failures,
+        }
+    }
+
+    /// Whether every leg passed
+    pub fn is_healthy(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Wiring that corrects swapped connectors, from wiring probe results
+///
+/// `observed[i]` is the leg that actually moved when `LEGS[i]` was probed
+/// under the `current` wiring. Returns the wiring that makes every leg move
+/// as commanded, or `None` if it already does. Observations where a leg moved
+/// twice or never point to a fault rather than a swap and are rejected with
+/// `ServoError::InvalidWiring`.
+pub fn detect_wiring_remap(current: [Leg; 4], observed: [Leg; 4]) -> Result<Option<[Leg; 4]>> {
+    if !is_valid_wiring(observed) {
+        return Err(ServoError::InvalidWiring.into());
+    }
+    if observed == LEGS {
+        return Ok(None);
+    }
+
+    let mut wiring = current;
+    for (commanded, moved) in LEGS.into_iter().zip(observed) {
+        wiring[moved.index()] = current[commanded.index()];
+    }
+    Ok(Some(wiring))
+}
+
+/// Trim that moves a centered leg onto its true center, from a measurement
+///
+/// `measured` is the physical angle feedback reported after commanding
+/// `calibration.center_deg`. The error from where an untrimmed servo would
+/// sit (center, mirrored if inverted) is taken off the current trim, so a
+/// leg reading 5° high gets 5° less trim. Limited to ±180°.
+pub fn trim_from_feedback(calibration: &LegCalibration, measured: u32) -> i32 {
+    let untrimmed = LegCalibration {
+        trim_deg: 0,
+        ..*calibration
+    };
+    let error = measured as i32 - untrimmed.physical_angle(calibration.center_deg) as i32;
+    (calibration.trim_deg - error).clamp(-180, 180)
+}
+
+impl<P: PwmBackend, D: Delay> ServoController<P, D> {
+    /// Center all legs, wait `settle_ms`, and verify they reached center
+    ///
+    /// With `feedback`, each leg's measured angle must be within
+    /// `tolerance_deg` of center. Without it the check is timed only and always
+    /// reports success. Write errors are returned as errors, not failures.
+    pub fn health_check(
+        &mut self,
+        settle_ms: u32,
+        tolerance_deg: u32,
+        feedback: Option<&mut dyn PositionFeedback>,
+    ) -> Result<HealthReport> {
+        self.set_all_servos_angle(HEALTH_CHECK_ANGLE)?;
+        self.delay_ms(settle_ms);
+
+        let Some(feedback) = feedback else {
+            log_info!(
+                target: self.log_target(),
+                "Health check: timed center complete (no feedback)"
+            );
+            return Ok(HealthReport::default());
+        };
+
+        let readings = LEGS.map(|leg| feedback.read_angle(leg));
+        let report = HealthReport::from_readings(readings, HEALTH_CHECK_ANGLE, tolerance_deg);
+
+        for failure in &report.failures {
+            log_warn!(
+                target: self.log_target(),
+                "Health check: {} failed (measured: {:?})",
+                failure.leg.name(),
+                failure.measured
+            );
+        }
+        Ok(report)
+    }
+
+    /// Calibrate a leg's trim from where its servo actually centers
+    ///
+    /// Commands the leg to its center, waits `settle_ms`, reads its position
+    /// and stores the trim from `trim_from_feedback`, which takes effect on
+    /// the next write. Returns the new trim. A leg without a reading fails
+    /// with `ServoError::NoFeedback` and keeps its trim.
+    pub fn auto_trim(
+        &mut self,
+        leg: Leg,
+        settle_ms: u32,
+        feedback: &mut dyn PositionFeedback,
+    ) -> Result<i32> {
+        let mut calibration = self.calibration(leg);
+        self.set_leg_angle(leg, calibration.center_deg)?;
+        self.delay_ms(settle_ms);
+
+        let Some(measured) = feedback.read_angle(leg) else {
+            return Err(ServoError::NoFeedback { leg }.into());
+        };
+        let trim_deg = trim_from_feedback(&calibration, measured);
+        log_info!(
+            target: self.log_target(),
+            "Auto trim: {} centered at {}°, trim {}° -> {}°",
+            leg.name(),
+            measured,
+            calibration.trim_deg,
+            trim_deg
+        );
+        calibration.trim_deg = trim_deg;
+        self.set_calibration(leg, calibration);
+        Ok(trim_deg)
+    }
+
+    /// Drive every leg together to 0°, then 180°, then center
+    ///
+    /// A bring-up check that all servos reach both extremes without binding.
+    /// Pauses `delay_ms` at each extreme.
+    pub fn sweep_range_test(&mut self, delay_ms: u32) -> Result<()> {
+        log_info!(
+            target: self.log_target(),
+            "Range test: sweeping all legs to 0° and 180°"
+        );
+        self.set_all_servos_angle(0)?;
+        self.delay_ms(delay_ms);
+        self.set_all_servos_angle(180)?;
+        self.delay_ms(delay_ms);
+        self.center_all_servos()
+    }
+
+    /// Center all legs, then move only `leg` to `WIRING_PROBE_ANGLE`
+    ///
+    /// The asymmetric pose makes it obvious which physical leg is wired to
+    /// `leg`'s channel.
+    pub fn probe_wiring(&mut self, leg: Leg) -> Result<()> {
+        self.center_all_servos()?;
+        self.set_leg_angle(leg, WIRING_PROBE_ANGLE)
+    }
+
+    /// Plan an eased move of `leg` and return its implied motion profile
+    ///
+    /// The move from `from` to `to` (clamped to 0-180) is split into `steps`
+    /// equal intervals over `duration_ms`. Each sample holds the physical angle
+    /// the leg would be commanded to (rounded, with its calibration applied)
+    /// and the velocity and acceleration implied by consecutive samples. The
+    /// first sample is the start of the move, with zero velocity and
+    /// acceleration. Nothing is written to the hardware.
+    pub fn profile_move(
+        &self,
+        leg: Leg,
+        from: u32,
+        to: u32,
+        duration_ms: u32,
+        steps: u32,
+        easing: Easing,
+    ) -> Vec<MoveSample> {
+        let steps = steps.max(1);
+        let duration_ms = duration_ms.max(1);
+        let step_s = duration_ms as f32 / steps as f32 / 1000.0;
+        let interpolator = MoveInterpolator::new(from, to, duration_ms, easing);
+        let calibration = self.calibration(leg);
+
+        let mut samples: Vec<MoveSample> = Vec::with_capacity(steps as usize + 1);
+        for i in 0..=steps {
+            let logical = interpolator.at(i as f32 / steps as f32);
+            let angle = calibration.physical_angle(logical);
+
+            let (velocity_dps, accel_dps2) = match samples.last() {
+                Some(prev) => {
+                    let velocity = (angle as f32 - prev.angle as f32) / step_s;
+                    (velocity, (velocity - prev.velocity_dps) / step_s)
+                }
+                None => (0.0, 0.0),
+            };
+            samples.push(MoveSample {
+                t_ms: (u64::from(i) * u64::from(duration_ms) / u64::from(steps)) as u32,
+                angle,
+                velocity_dps,
+                accel_dps2,
+            });
+        }
+        samples
+    }
+
+    /// Crate version and a hash of the live configuration
+    ///
+    /// Report this from a field unit to confirm which build and tuning it is
+    /// running.
+    pub fn firmware_info(&self) -> FirmwareInfo {
+        FirmwareInfo {
+            version: env!("CARGO_PKG_VERSION"),
+            config_hash: config_hash(&self.config()),
+        }
+    }
+
+    /// Smallest angle change, in degrees, that changes a leg's duty
+    ///
+    /// One duty step is `period / max_duty` of pulse width, spread over the
+    /// leg's calibrated pulse range. Above 1.0 some whole-degree commands
+    /// drive the same duty, and a finer timer resolution would help: e.g. a
+    /// 500-2500 µs servo at 50 Hz resolves about 1.76° at 10 bits but 0.11° at
+    /// 14 bits. Correction curves are not taken into account. A channel with
+    /// a max duty of 0 or an empty pulse range gives infinity.
+    pub fn angle_resolution(&self, leg: Leg) -> f32 {
+        let calibration = self.calibration(leg);
+        let span_us = calibration.min_pulse_us.abs_diff(calibration.max_pulse_us);
+        let max_duty = self.legs[self.channel_index(leg)].get_max_duty();
+        if span_us == 0 || max_duty == 0 {
+            return f32::INFINITY;
+        }
+
+        let step_us = period_us(self.actual_frequency_hz) as f32 / max_duty as f32;
+        step_us * 180.0 / span_us as f32
+    }
+
+    /// Pulse width and duty driven for every angle 0-180° on a leg, as CSV
+    ///
+    /// One `angle,pulse_us,duty` row per degree after that header, computed
+    /// exactly as a move would be: the leg's soft limits, calibration, trim
+    /// and correction curve all apply. Fails if the leg's channel reports a
+    /// max duty of 0.
+    pub fn dump_mapping(&self, leg: Leg) -> Result<String> {
+        let calibration = self.calibration(leg);
+        let mut csv = String::from("angle,pulse_us,duty\n");
+        for angle in 0..=180 {
+            let physical = self.physical_angle(leg, angle)?;
+            csv.push_str(&format!(
+                "{},{},{}\n",
+                angle,
+                calibration.pulse_us(physical),
+                self.duty_for(leg, angle)?
+            ));
+        }
+        Ok(csv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::*;
+    use std::time::Duration;
+
+    /// Feedback returning fixed readings
+    struct StubFeedback([Option<u32>; 4]);
+
+    impl PositionFeedback for StubFeedback {
+        fn read_angle(&mut self, leg: Leg) -> Option<u32> {
+            self.0[leg.index()]
+        }
+    }
+
+    /// Test that legs within tolerance pass
+    #[test]
+    fn test_health_check_passes() {
+        let (mut controller, log) = mock_controller();
+        let mut feedback = StubFeedback([Some(90), Some(88), Some(93), Some(90)]);
+
+        let report = controller
+            .health_check(500, 3, Some(&mut feedback))
+            .unwrap();
+
+        assert!(report.verified);
+        assert!(report.is_healthy());
+        assert_eq!(log.duties().len(), 4);
+        assert!(log.commands().contains(&Command::Delay(500)));
+    }
+
+    /// Test that off-target and silent legs are reported
+    #[test]
+    fn test_health_check_reports_failures() {
+        let (mut controller, _log) = mock_controller();
+        let mut feedback = StubFeedback([Some(90), None, Some(120), Some(86)]);
+
+        let report = controller
+            .health_check(500, 3, Some(&mut feedback))
+            .unwrap();
+
+        assert!(!report.is_healthy());
+        assert_eq!(
+            report.failures,
+            vec![
+                HealthFailure {
+                    leg: Leg::LeftBack,
+                    measured: None
+                },
+                HealthFailure {
+                    leg: Leg::RightFront,
+                    measured: Some(120)
+                },
+                HealthFailure {
+                    leg: Leg::LeftFront,
+                    measured: Some(86)
+                },
+            ]
+        );
+    }
+
+    /// Test the trim computed from a centered leg's measured position
+    #[test]
+    fn test_trim_from_feedback() {
+        let calibration = LegCalibration::default();
+        assert_eq!(trim_from_feedback(&calibration, 95), -5);
+        assert_eq!(trim_from_feedback(&calibration, 90), 0);
+        assert_eq!(trim_from_feedback(&calibration, 84), 6);
+
+        // The error is taken off an existing trim
+        let trimmed = LegCalibration {
+            trim_deg: 2,
+            ..calibration
+        };
+        assert_eq!(trim_from_feedback(&trimmed, 95), -3);
+
+        // An inverted leg centered at 60° should sit at 120°
+        let inverted = LegCalibration {
+            inverted: true,
+            center_deg: 60,
+            ..calibration
+        };
+        assert_eq!(trim_from_feedback(&inverted, 125), -5);
+    }
+
+    /// Test that auto trim centers, reads and stores the correction
+    #[test]
+    fn test_auto_trim() {
+        let (mut controller, log) = mock_controller();
+        let mut feedback = StubFeedback([None, Some(95), None, None]);
+
+        let trim = controller
+            .auto_trim(Leg::LeftBack, 300, &mut feedback)
+            .unwrap();
+
+        assert_eq!(trim, -5);
+        assert_eq!(controller.calibration(Leg::LeftBack).trim_deg, -5);
+        log.assert_commands(&[
+            Command::Duty(Leg::LeftBack, crate::angle_to_duty(90, 1024)),
+            Command::Delay(300),
+        ]);
+
+        let err = controller
+            .auto_trim(Leg::RightBack, 300, &mut feedback)
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ServoError>(),
+            Some(&ServoError::NoFeedback {
+                leg: Leg::RightBack
+            })
+        );
+        assert_eq!(controller.calibration(Leg::RightBack).trim_deg, 0);
+    }
+
+    /// Test that the range test moves all legs together through both extremes
+    #[test]
+    fn test_sweep_range_test() {
+        let (mut controller, log) = mock_controller();
+
+        controller.sweep_range_test(400).unwrap();
+
+        let all = |angle| LEGS.map(|leg| Command::Duty(leg, crate::angle_to_duty(angle, 1024)));
+        let mut expected = all(0).to_vec();
+        expected.push(Command::Delay(400));
+        expected.extend(all(180));
+        expected.push(Command::Delay(400));
+        expected.extend(all(90));
+        log.assert_commands(&expected);
+    }
+
+    /// Test that a front left/right swap is detected and corrected
+    #[test]
+    fn test_detect_front_swap() {
+        // Probing right front moved left front and vice versa
+        let observed = [
+            Leg::RightBack,
+            Leg::LeftBack,
+            Leg::LeftFront,
+            Leg::RightFront,
+        ];
+
+        let wiring = detect_wiring_remap(LEGS, observed).unwrap().unwrap();
+        assert_eq!(
+            wiring,
+            [
+                Leg::RightBack,
+                Leg::LeftBack,
+                Leg::LeftFront,
+                Leg::RightFront
+            ]
+        );
+
+        // With the fix applied each leg drives its own servo
+        let (mut controller, log) = mock_controller();
+        controller.set_wiring(wiring).unwrap();
+        controller.probe_wiring(Leg::RightFront).unwrap();
+        assert_eq!(
+            log.duties().last(),
+            Some(&(
+                Leg::LeftFront,
+                crate::angle_to_duty(WIRING_PROBE_ANGLE, 1024)
+            ))
+        );
+        assert_eq!(controller.config().wiring, wiring);
+    }
+
+    /// Test detection relative to an already remapped wiring
+    #[test]
+    fn test_detect_swap_with_remapped_wiring() {
+        let current = [
+            Leg::LeftBack,
+            Leg::RightBack,
+            Leg::RightFront,
+            Leg::LeftFront,
+        ];
+
+        // Correct wiring: nothing to do
+        assert_eq!(detect_wiring_remap(current, LEGS).unwrap(), None);
+
+        // The back remap was wrong after all: probing each back leg moved the other
+        let observed = [
+            Leg::LeftBack,
+            Leg::RightBack,
+            Leg::RightFront,
+            Leg::LeftFront,
+        ];
+        assert_eq!(detect_wiring_remap(current, observed).unwrap(), Some(LEGS));
+    }
+
+    /// Test that observations that aren't a swap are rejected
+    #[test]
+    fn test_detect_wiring_fault() {
+        let observed = [
+            Leg::RightBack,
+            Leg::RightBack,
+            Leg::RightFront,
+            Leg::LeftFront,
+        ];
+
+        let err = detect_wiring_remap(LEGS, observed).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ServoError>(),
+            Some(&ServoError::InvalidWiring)
+        );
+
+        let (mut controller, _log) = mock_controller();
+        assert!(controller.set_wiring(observed).is_err());
+        assert_eq!(controller.wiring(), LEGS);
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 0.01, "{actual:?} != {expected:?}");
+        }
+    }
+
+    /// Test the derivatives of a linear move: constant speed, one jerk at start
+    #[test]
+    fn test_profile_move_linear() {
+        let (controller, log) = mock_controller();
+
+        let samples = controller.profile_move(Leg::RightBack, 0, 100, 1000, 10, Easing::Linear);
+
+        assert_eq!(
+            samples.iter().map(|s| s.t_ms).collect::<Vec<_>>(),
+            (0..=1000).step_by(100).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            samples.iter().map(|s| s.angle).collect::<Vec<_>>(),
+            (0..=100).step_by(10).collect::<Vec<_>>()
+        );
+        let velocities: Vec<f32> = samples.iter().map(|s| s.velocity_dps).collect();
+        let accels: Vec<f32> = samples.iter().map(|s| s.accel_dps2).collect();
+        let mut expected_velocities = vec![100.0; 11];
+        expected_velocities[0] = 0.0;
+        let mut expected_accels = vec![0.0; 11];
+        expected_accels[1] = 1000.0;
+        assert_close(&velocities, &expected_velocities);
+        assert_close(&accels, &expected_accels);
+        assert!(log.commands().is_empty());
+    }
+
+    /// Test the derivatives of an eased move: speed peaks mid-move, accel flips
+    #[test]
+    fn test_profile_move_eased() {
+        let (mut controller, _log) = mock_controller();
+
+        // Smoothstep 0→180 in 10 steps of 100 ms rounds to these angles
+        let samples = controller.profile_move(Leg::LeftBack, 0, 180, 1000, 10, Easing::EaseInOut);
+        let angles: Vec<u32> = samples.iter().map(|s| s.angle).collect();
+        assert_eq!(angles, [0, 5, 19, 39, 63, 90, 117, 141, 161, 175, 180]);
+        assert_close(
+            &samples.iter().map(|s| s.velocity_dps).collect::<Vec<_>>(),
+            &[
+                0.0, 50.0, 140.0, 200.0, 240.0, 270.0, 270.0, 240.0, 200.0, 140.0, 50.0,
+            ],
+        );
+        assert_close(
+            &samples.iter().map(|s| s.accel_dps2).collect::<Vec<_>>(),
+            &[
+                0.0, 500.0, 900.0, 600.0, 400.0, 300.0, 0.0, -300.0, -400.0, -600.0, -900.0,
+            ],
+        );
+
+        // The leg's calibration is applied: inverted, the same move runs backwards
+        controller.set_calibration(
+            Leg::LeftBack,
+            crate::LegCalibration {
+                inverted: true,
+                ..Default::default()
+            },
+        );
+        let inverted = controller.profile_move(Leg::LeftBack, 0, 180, 1000, 10, Easing::EaseInOut);
+        for (sample, normal) in inverted.iter().zip(&samples) {
+            assert_eq!(sample.angle, 180 - normal.angle);
+            assert_eq!(sample.velocity_dps, -normal.velocity_dps);
+        }
+    }
+
+    /// Test that without feedback the check is timed only
+    #[test]
+    fn test_health_check_without_feedback() {
+        let (mut controller, log) = mock_controller();
+
+        let report = controller.health_check(250, 3, None).unwrap();
+
+        assert!(!report.verified);
+        assert!(report.is_healthy());
+        assert_eq!(log.now_ms(), 250);
+    }
+
+    /// Test the resolvable angle step at 10-bit and 14-bit resolution
+    #[test]
+    fn test_angle_resolution() {
+        for (max_duty, expected) in [(1024, 1.7578), (16_384, 0.1099)] {
+            let (controller, _log) = mock_controller_with_max_duty(max_duty);
+            let resolution = controller.angle_resolution(Leg::RightBack);
+            assert!(
+                (resolution - expected).abs() < 1e-3,
+                "{max_duty}: {resolution}"
+            );
+
+            // Whole degrees map onto about 180 / resolution distinct duties
+            let mut duties: Vec<u32> = (0..=180)
+                .map(|angle| controller.duty_for(Leg::RightBack, angle).unwrap())
+                .collect();
+            duties.dedup();
+            let distinct = (180.0 / resolution).min(180.0) as usize + 1;
+            assert!(
+                duties.len().abs_diff(distinct) <= 1,
+                "{max_duty}: {}",
+                duties.len()
+            );
+        }
+    }
+
+    /// Test that calibration narrows resolution and dead channels report it
+    #[test]
+    fn test_angle_resolution_calibration() {
+        let (mut controller, _log) = mock_controller();
+        controller.set_calibration(
+            Leg::LeftFront,
+            crate::LegCalibration {
+                min_pulse_us: 1000,
+                max_pulse_us: 2000,
+                ..Default::default()
+            },
+        );
+        let full = controller.angle_resolution(Leg::RightBack);
+        assert!((controller.angle_resolution(Leg::LeftFront) - 2.0 * full).abs() < 1e-4);
+
+        let (controller, _log) = mock_controller_with_max_duty(0);
+        assert_eq!(controller.angle_resolution(Leg::LeftBack), f32::INFINITY);
+    }
+
+    /// Test that the mapping dump follows a leg's calibration and trim
+    #[test]
+    fn test_dump_mapping() {
+        let (mut controller, _log) = mock_controller();
+        let calibration = LegCalibration {
+            min_pulse_us: 600,
+            max_pulse_us: 2400,
+            trim_deg: 10,
+            ..Default::default()
+        };
+        controller.set_calibration(Leg::LeftBack, calibration);
+
+        let dump = controller.dump_mapping(Leg::LeftBack).unwrap();
+        let rows: Vec<&str> = dump.lines().collect();
+        assert_eq!(rows.len(), 182);
+        assert_eq!(rows[0], "angle,pulse_us,duty");
+        let duty = |physical| calibration.duty(physical, MOCK_MAX_DUTY, crate::PERIOD_US);
+        assert_eq!(rows[1], format!("0,700,{}", duty(10)));
+        assert_eq!(rows[81], format!("80,1500,{}", duty(90)));
+        // Trim pushes the top angles against the end stop
+        assert_eq!(rows[171], format!("170,2400,{}", duty(180)));
+        assert_eq!(rows[181], rows[171].replacen("170", "180", 1));
+
+        // Each row drives what a move to that angle would
+        controller.set_leg_angle(Leg::LeftBack, 45).unwrap();
+        let driven = controller.read_duty(Leg::LeftBack);
+        assert!(rows[46].ends_with(&format!(",{}", driven)));
+
+        let (controller, _log) = mock_controller_with_max_duty(0);
+        assert!(controller.dump_mapping(Leg::RightBack).is_err());
+    }
+
+    /// Test that the version is the crate's and the hash follows the tuning
+    #[test]
+    fn test_firmware_info() {
+        let (mut controller, _log) = mock_controller();
+        let info = controller.firmware_info();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(controller.firmware_info(), info);
+        assert_eq!(
+            info.to_string(),
+            format!(
+                "cobot-rs {} (config {:08x})",
+                info.version, info.config_hash
+            )
+        );
+
+        let mut calibration = controller.calibration(Leg::RightFront);
+        calibration.trim_deg += 1;
+        controller.set_calibration(Leg::RightFront, calibration);
+        let trimmed = controller.firmware_info();
+        assert_ne!(trimmed.config_hash, info.config_hash);
+
+        // Restoring the tuning restores the hash
+        calibration.trim_deg -= 1;
+        controller.set_calibration(Leg::RightFront, calibration);
+        assert_eq!(controller.firmware_info(), info);
+
+        // Settings outside the calibration count too
+        controller.set_max_command_rate(25);
+        assert_ne!(controller.firmware_info(), info);
+        controller.set_max_command_rate(0);
+        controller.auto_center_after(Some(Duration::from_secs(5)));
+        assert_ne!(controller.firmware_info(), info);
+    }
+}
@@ -0,0 +1,1629 @@
+//! # Gaits
+//!
+//! Frame tables describing a movement pattern, and a non-blocking runner that
+//! plays them from the main loop.
+//!
+//! A `Gait` is an ordered list of `Pose`s. Each pose holds the target angle of
+//! every leg (in `set_servo_angles` order), how long to dwell there before
+//! moving to the next frame, and whether the servos keep holding it.
+//!
+//! Gaits can be stored compactly with `Gait::to_bytes`: a version byte, a
+//! little-endian `u16` frame count, then per frame one byte per leg angle, a
+//! little-endian `u16` dwell and a hold byte (1 to hold, 0 to relax).
+
+use crate::{Delay, LEGS, Leg, PwmBackend, SMOOTH_STEP_MS, ServoController, ServoError, Timing};
+use anyhow::Result;
+use std::time::{Duration, Instant};
+
+/// Current version of the `Gait::to_bytes` encoding
+pub const GAIT_FORMAT_VERSION: u8 = 2;
+
+/// Shortest dwell `Gait::scale_time` produces (one 50 Hz PWM period)
+pub const MIN_SCALED_DWELL_MS: u32 = 20;
+
+/// Encoded size of the version byte and frame count
+const HEADER_LEN: usize = 3;
+
+/// Encoded size of one frame: four angles, a `u16` dwell and the hold byte
+const FRAME_LEN: usize = 7;
+
+/// Errors decoding a gait from bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GaitDecodeError {
+    /// The version byte is not `GAIT_FORMAT_VERSION`
+    UnsupportedVersion(u8),
+    /// The input is not the length implied by its header
+    InvalidLength { expected: usize, actual: usize },
+    /// A frame holds an angle outside 0-180°
+    InvalidAngle { frame: usize, degrees: u8 },
+    /// A frame's hold byte is neither 0 nor 1
+    InvalidHold { frame: usize, value: u8 },
+}
+
+impl std::fmt::Display for GaitDecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GaitDecodeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported gait format version {}", version)
+            }
+            GaitDecodeError::InvalidLength { expected, actual } => {
+                write!(f, "expected {} gait bytes, got {}", expected, actual)
+            }
+            GaitDecodeError::InvalidAngle { frame, degrees } => {
+                write!(f, "frame {} angle {}° is outside 0-180°", frame, degrees)
+            }
+            GaitDecodeError::InvalidHold { frame, value } => {
+                write!(f, "frame {} hold byte {} is neither 0 nor 1", frame, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GaitDecodeError {}
+
+/// Thresholds used by `Gait::validate_with`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GaitThresholds {
+    /// Largest allowed change of one leg between consecutive frames
+    pub max_jump_deg: u32,
+    /// Shortest allowed dwell
+    pub min_dwell_ms: u32,
+}
+
+impl Default for GaitThresholds {
+    fn default() -> Self {
+        Self {
+            max_jump_deg: 90,
+            min_dwell_ms: 1,
+        }
+    }
+}
+
+/// A likely authoring mistake found by `Gait::validate`, or a safety problem
+/// found by `ServoController::validate_choreography`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GaitWarning {
+    /// A leg moves more than `max_jump_deg` from the previous frame
+    LargeJump {
+        frame: usize,
+        leg: Leg,
+        from: u32,
+        to: u32,
+    },
+    /// An angle is outside 0-180° (it would be clamped)
+    OutOfRange {
+        frame: usize,
+        leg: Leg,
+        degrees: u32,
+    },
+    /// A frame dwells for less than `min_dwell_ms`
+    ShortDwell { frame: usize, dwell_ms: u32 },
+    /// An angle is outside the leg's soft limits (it would be clamped)
+    OutsideSoftLimits {
+        frame: usize,
+        leg: Leg,
+        degrees: u32,
+        min: u32,
+        max: u32,
+    },
+    /// A leg moves, but by no more than the deadband, so it would not move
+    WithinDeadband {
+        frame: usize,
+        leg: Leg,
+        from: u32,
+        to: u32,
+    },
+    /// The whole gait takes longer than allowed
+    TooLong { duration_ms: u32, max_ms: u32 },
+}
+
+impl GaitWarning {
+    /// Frame the problem is in, `None` for problems of the whole gait
+    pub fn frame(&self) -> Option<usize> {
+        match *self {
+            GaitWarning::LargeJump { frame, .. }
+            | GaitWarning::OutOfRange { frame, .. }
+            | GaitWarning::ShortDwell { frame, .. }
+            | GaitWarning::OutsideSoftLimits { frame, .. }
+            | GaitWarning::WithinDeadband { frame, .. } => Some(frame),
+            GaitWarning::TooLong { .. } => None,
+        }
+    }
+}
+
+/// Largest asymmetry `Gait::symmetry_report` accepts as symmetric
+pub const SYMMETRY_TOLERANCE_DEG: u32 = 5;
+
+/// Result of `Gait::symmetry_report`
+///
+/// One side of the robot is symmetric with the other if it repeats the other
+/// side's motion, possibly some frames later (e.g. half a cycle later in a
+/// trot). Each axis reports the frame shift that matches best and the largest
+/// angle difference left at that shift.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SymmetryReport {
+    /// Frames the left legs lag their right counterparts by
+    pub left_right_shift: usize,
+    /// Largest difference between a right leg and its shifted left leg
+    pub left_right_deg: u32,
+    /// Frames the back legs lag their front counterparts by
+    pub front_back_shift: usize,
+    /// Largest difference between a front leg and its shifted back leg
+    pub front_back_deg: u32,
+    /// Asymmetry accepted as symmetric
+    pub tolerance_deg: u32,
+}
+
+impl SymmetryReport {
+    /// Whether the left side mirrors the right within the tolerance
+    pub fn is_left_right_symmetric(&self) -> bool {
+        self.left_right_deg <= self.tolerance_deg
+    }
+
+    /// Whether the back mirrors the front within the tolerance
+    pub fn is_front_back_symmetric(&self) -> bool {
+        self.front_back_deg <= self.tolerance_deg
+    }
+
+    /// Whether both axes are symmetric
+    pub fn is_symmetric(&self) -> bool {
+        self.is_left_right_symmetric() && self.is_front_back_symmetric()
+    }
+
+    /// Largest asymmetry on either axis
+    pub fn max_asymmetry_deg(&self) -> u32 {
+        self.left_right_deg.max(self.front_back_deg)
+    }
+}
+
+/// One gait frame: target angles for all legs and how long to hold them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pose {
+    /// Angles in `set_servo_angles` order
+    pub angles: [u32; 4],
+    /// Time to hold this pose before the next frame
+    pub dwell_ms: u32,
+    /// Keep driving the servos after the dwell, to resist a load; otherwise
+    /// `play_sequence` detaches every leg once the dwell is over
+    pub hold: bool,
+}
+
+impl Pose {
+    /// Create a held pose from angles and a dwell time
+    pub const fn new(angles: [u32; 4], dwell_ms: u32) -> Self {
+        Self {
+            angles,
+            dwell_ms,
+            hold: true,
+        }
+    }
+
+    /// Create a pose the servos relax from after its dwell, saving power where
+    /// nothing needs to be resisted
+    pub const fn relaxed(angles: [u32; 4], dwell_ms: u32) -> Self {
+        Self {
+            angles,
+            dwell_ms,
+            hold: false,
+        }
+    }
+}
+
+/// Build a `Gait` from a frame table, one `[angles] @ dwell_ms;` per frame
+///
+/// Angles are in `set_servo_angles` order. A frame without exactly four
+/// angles does not compile; angle ranges are left to `Gait::validate`.
+///
+/// ```
+/// let gait = cobot_rs::gait! {
+///     [90, 90, 90, 90] @ 300;
+///     [45, 90, 45, 90] @ 300;
+/// };
+/// assert_eq!(gait.len(), 2);
+/// assert_eq!(gait.duration_ms(), 600);
+/// ```
+///
+/// ```compile_fail
+/// let gait = cobot_rs::gait! { [90, 90, 90] @ 300; };
+/// ```
+#[macro_export]
+macro_rules! gait {
+    ($([$a:expr, $b:expr, $c:expr, $d:expr $(,)?] @ $dwell_ms:expr);* $(;)?) => {
+        $crate::Gait::new(vec![$($crate::Pose::new([$a, $b, $c, $d], $dwell_ms)),*])
+    };
+}
+
+/// Length of the repeating cycle in a recorded trajectory, in samples
+///
+/// `samples` are angles in `set_servo_angles` order taken at a fixed rate,
+/// e.g. `current_angles` logged during teleoperation. A simple
+/// autocorrelation: for each candidate period up to half the recording, the
+/// recording is compared with itself shifted by that period, and the shortest
+/// period whose mean difference per leg is at most `tolerance_deg` wins. The
+/// shortest is taken because every multiple of the period matches as well.
+/// `None` if no period repeats at least twice within the tolerance.
+pub fn detect_cycle(samples: &[[u32; 4]], tolerance_deg: u32) -> Option<usize> {
+    (1..=samples.len() / 2).find(|&period| {
+        let pairs = samples.iter().zip(&samples[period..]);
+        let count = (samples.len() - period) * LEGS.len();
+        let total: u64 = pairs
+            .flat_map(|(a, b)| LEGS.map(|leg| u64::from(a[leg.index()].abs_diff(b[leg.index()]))))
+            .sum();
+        total <= u64::from(tolerance_deg) * count as u64
+    })
+}
+
+/// An ordered sequence of poses
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Gait {
+    frames: Vec<Pose>,
+}
+
+impl Gait {
+    /// Create a gait from its frames
+    pub fn new(frames: Vec<Pose>) -> Self {
+        Self { frames }
+    }
+
+    /// Loop a recorded trajectory into a gait of one cycle
+    ///
+    /// The cycle is found with `detect_cycle`. Its frames average every full
+    /// repetition in the recording (rounded), which smooths out the jitter of
+    /// a hand-driven recording, and dwell `sample_ms` each. The recording may
+    /// start anywhere in the cycle; so does the gait. `None` if no cycle is
+    /// found.
+    pub fn from_recording(
+        samples: &[[u32; 4]],
+        sample_ms: u32,
+        tolerance_deg: u32,
+    ) -> Option<Self> {
+        let period = detect_cycle(samples, tolerance_deg)?;
+        let repetitions = (samples.len() / period) as u32;
+        let frames = (0..period)
+            .map(|frame| {
+                let angles = LEGS.map(|leg| {
+                    let sum: u32 = samples[frame..]
+                        .iter()
+                        .step_by(period)
+                        .take(repetitions as usize)
+                        .map(|sample| sample[leg.index()])
+                        .sum();
+                    (sum + repetitions / 2) / repetitions
+                });
+                Pose::new(angles, sample_ms)
+            })
+            .collect();
+        Some(Self::new(frames))
+    }
+
+    /// Frames in playback order
+    pub fn frames(&self) -> &[Pose] {
+        &self.frames
+    }
+
+    /// Number of frames
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Whether the gait has no frames
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Total time of one cycle (sum of all dwells)
+    pub fn duration_ms(&self) -> u32 {
+        self.frames.iter().map(|f| f.dwell_ms).sum()
+    }
+
+    /// Copy of the gait with every dwell multiplied by `factor`
+    ///
+    /// A factor of 0.5 plays twice as fast, 2.0 half as fast. Scaled dwells are
+    /// rounded and never drop below `MIN_SCALED_DWELL_MS`. `factor` must be
+    /// positive and finite.
+    pub fn scale_time(&self, factor: f32) -> Result<Gait> {
+        if !(factor > 0.0 && factor.is_finite()) {
+            return Err(ServoError::InvalidTimeScale { factor }.into());
+        }
+
+        let frames = self
+            .frames
+            .iter()
+            .map(|frame| {
+                let dwell_ms = (frame.dwell_ms as f32 * factor).round() as u32;
+                Pose {
+                    dwell_ms: dwell_ms.max(MIN_SCALED_DWELL_MS),
+                    ..*frame
+                }
+            })
+            .collect();
+        Ok(Gait::new(frames))
+    }
+
+    /// Copy of the gait scaled so no leg strays more than `max_deg` from 90°
+    ///
+    /// Every excursion from center is scaled by the same factor, chosen so the
+    /// largest one lands on `max_deg`, which keeps the gait's shape: legs
+    /// still move in the same directions and in the same proportions, just
+    /// less far. Angles are clamped to 180° first and rounded after scaling.
+    /// A gait already within the limit is returned unchanged.
+    pub fn limit_excursion(&self, max_deg: u32) -> Gait {
+        let excursion = |angle: u32| angle.min(180).abs_diff(90);
+        let peak = self
+            .frames
+            .iter()
+            .flat_map(|frame| frame.angles.map(excursion))
+            .max()
+            .unwrap_or(0);
+        if peak <= max_deg {
+            return self.clone();
+        }
+
+        let scale = |angle: u32| {
+            let scaled = (2 * excursion(angle) * max_deg + peak) / (2 * peak);
+            if angle >= 90 {
+                90 + scaled
+            } else {
+                90 - scaled
+            }
+        };
+        let frames = self
+            .frames
+            .iter()
+            .map(|frame| Pose {
+                angles: frame.angles.map(scale),
+                ..*frame
+            })
+            .collect();
+        Gait::new(frames)
+    }
+
+    /// Copy of the gait with the angles of `legs` taken from `other`
+    ///
+    /// For e.g. waving the right front leg while the others walk. The result
+    /// keeps this gait's cycle and timing; `other` is time-aligned to it,
+    /// looping if it is shorter and cut off if it is longer. A frame starts
+    /// wherever either gait changes frame, so neither loses a frame even when
+    /// their frame counts and dwells differ (only zero-dwell frames, which
+    /// are never held, drop out). Each frame keeps this gait's `hold`. If
+    /// either gait has no duration the frames are matched by position
+    /// instead, resampling `other` to this gait's frame count.
+    pub fn overlay(&self, other: &Gait, legs: &[Leg]) -> Gait {
+        if self.is_empty() || other.is_empty() {
+            return self.clone();
+        }
+        let merge = |own: &Pose, theirs: &Pose, dwell_ms| {
+            let mut angles = own.angles;
+            for leg in legs {
+                angles[leg.index()] = theirs.angles[leg.index()];
+            }
+            Pose {
+                angles,
+                dwell_ms,
+                ..*own
+            }
+        };
+
+        let (own_ms, other_ms) = (
+            u64::from(self.duration_ms()),
+            u64::from(other.duration_ms()),
+        );
+        if own_ms == 0 || other_ms == 0 {
+            let frames = self
+                .frames
+                .iter()
+                .enumerate()
+                .map(|(i, own)| {
+                    merge(
+                        own,
+                        &other.frames[i * other.len() / self.len()],
+                        own.dwell_ms,
+                    )
+                })
+                .collect();
+            return Gait::new(frames);
+        }
+
+        let mut cuts = self.frame_starts();
+        for repeat in (0..own_ms).step_by(other_ms as usize) {
+            cuts.extend(other.frame_starts().into_iter().map(|start| repeat + start));
+        }
+        cuts.retain(|&t| t < own_ms);
+        cuts.sort_unstable();
+        cuts.dedup();
+
+        let frames = cuts
+            .iter()
+            .enumerate()
+            .map(|(i, &t)| {
+                let end = cuts.get(i + 1).copied().unwrap_or(own_ms);
+                merge(self.frame_at(t), other.frame_at(t), (end - t) as u32)
+            })
+            .collect();
+        Gait::new(frames)
+    }
+
+    /// Time each frame starts at within one cycle
+    fn frame_starts(&self) -> Vec<u64> {
+        self.frames
+            .iter()
+            .scan(0u64, |start, frame| {
+                let at = *start;
+                *start += u64::from(frame.dwell_ms);
+                Some(at)
+            })
+            .collect()
+    }
+
+    /// Frame held at `t_ms` into playback, looping; the gait must not be empty
+    /// or have zero duration
+    fn frame_at(&self, t_ms: u64) -> &Pose {
+        let mut offset = t_ms % u64::from(self.duration_ms());
+        for frame in &self.frames[..self.len() - 1] {
+            if offset < u64::from(frame.dwell_ms) {
+                return frame;
+            }
+            offset -= u64::from(frame.dwell_ms);
+        }
+        &self.frames[self.len() - 1]
+    }
+
+    /// Copy of the gait with its frames in reverse order
+    ///
+    /// With `mirror`, every angle is also reflected around center
+    /// (`180 - angle`), swapping fore and aft so a forward gait walks
+    /// backward. Each frame keeps its own dwell.
+    pub fn reversed(&self, mirror: bool) -> Gait {
+        let frames = self
+            .frames
+            .iter()
+            .rev()
+            .map(|frame| {
+                let angles = if mirror {
+                    frame.angles.map(|angle| 180 - angle.min(180))
+                } else {
+                    frame.angles
+                };
+                Pose { angles, ..*frame }
+            })
+            .collect();
+        Gait::new(frames)
+    }
+
+    /// Check the gait against the default `GaitThresholds`
+    pub fn validate(&self) -> Result<(), Vec<GaitWarning>> {
+        self.validate_with(GaitThresholds::default())
+    }
+
+    /// Check for large per-frame jumps, out-of-range angles and short dwells
+    ///
+    /// Jumps are measured between consecutive frames, including from the last
+    /// frame back to the first since gaits are played in a loop.
+    pub fn validate_with(&self, thresholds: GaitThresholds) -> Result<(), Vec<GaitWarning>> {
+        let mut warnings = Vec::new();
+
+        for (frame, pose) in self.frames.iter().enumerate() {
+            let previous = &self.frames[(frame + self.len() - 1) % self.len()];
+            for leg in LEGS {
+                let (from, to) = (previous.angles[leg.index()], pose.angles[leg.index()]);
+                if to > 180 {
+                    warnings.push(GaitWarning::OutOfRange {
+                        frame,
+                        leg,
+                        degrees: to,
+                    });
+                }
+                if from.min(180).abs_diff(to.min(180)) > thresholds.max_jump_deg {
+                    warnings.push(GaitWarning::LargeJump {
+                        frame,
+                        leg,
+                        from,
+                        to,
+                    });
+                }
+            }
+            if pose.dwell_ms < thresholds.min_dwell_ms {
+                warnings.push(GaitWarning::ShortDwell {
+                    frame,
+                    dwell_ms: pose.dwell_ms,
+                });
+            }
+        }
+
+        if warnings.is_empty() {
+            Ok(())
+        } else {
+            Err(warnings)
+        }
+    }
+
+    /// Check left/right and front/back symmetry within `SYMMETRY_TOLERANCE_DEG`
+    pub fn symmetry_report(&self) -> SymmetryReport {
+        self.symmetry_report_with(SYMMETRY_TOLERANCE_DEG)
+    }
+
+    /// Check left/right and front/back symmetry within `tolerance_deg`
+    ///
+    /// Frames are compared by index, looping like playback, so dwell times are
+    /// not considered. An empty gait is symmetric.
+    pub fn symmetry_report_with(&self, tolerance_deg: u32) -> SymmetryReport {
+        let (left_right_shift, left_right_deg) = self.best_shift(
+            [Leg::RightBack, Leg::RightFront],
+            [Leg::LeftBack, Leg::LeftFront],
+        );
+        let (front_back_shift, front_back_deg) = self.best_shift(
+            [Leg::RightFront, Leg::LeftFront],
+            [Leg::RightBack, Leg::LeftBack],
+        );
+        SymmetryReport {
+            left_right_shift,
+            left_right_deg,
+            front_back_shift,
+            front_back_deg,
+            tolerance_deg,
+        }
+    }
+
+    /// Frame shift at which `followers` best repeat `leaders`, and the largest
+    /// angle difference left at that shift (the smallest shift wins ties)
+    fn best_shift(&self, leaders: [Leg; 2], followers: [Leg; 2]) -> (usize, u32) {
+        let len = self.len();
+        let angle = |frame: usize, leg: Leg| self.frames[frame].angles[leg.index()].min(180);
+        (0..len)
+            .map(|shift| {
+                let deviation = (0..len)
+                    .flat_map(|frame| {
+                        let led = (frame + len - shift) % len;
+                        leaders
+                            .iter()
+                            .zip(&followers)
+                            .map(move |(&leader, &follower)| {
+                                angle(led, leader).abs_diff(angle(frame, follower))
+                            })
+                    })
+                    .max()
+                    .unwrap_or(0);
+                (shift, deviation)
+            })
+            .min_by_key(|&(_, deviation)| deviation)
+            .unwrap_or((0, 0))
+    }
+
+    /// Timeline of one cycle as CSV, for plotting
+    ///
+    /// A `time_ms,right_back,left_back,right_front,left_front` header, then one
+    /// row per frame at the time it starts; the legs hold each row's angles
+    /// until the next. An empty gait gives just the header.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("time_ms,right_back,left_back,right_front,left_front\n");
+        let mut time_ms = 0u64;
+        for frame in &self.frames {
+            let [right_back, left_back, right_front, left_front] = frame.angles;
+            csv.push_str(&format!(
+                "{time_ms},{right_back},{left_back},{right_front},{left_front}\n"
+            ));
+            time_ms += u64::from(frame.dwell_ms);
+        }
+        csv
+    }
+
+    /// Encode the gait in the compact binary format
+    ///
+    /// Angles are clamped to 180° and dwells saturate at `u16::MAX` ms; frames
+    /// beyond `u16::MAX` are dropped.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let frames = &self.frames[..self.frames.len().min(usize::from(u16::MAX))];
+        let mut bytes = Vec::with_capacity(HEADER_LEN + frames.len() * FRAME_LEN);
+        bytes.push(GAIT_FORMAT_VERSION);
+        bytes.extend_from_slice(&(frames.len() as u16).to_le_bytes());
+        for frame in frames {
+            bytes.extend(frame.angles.map(|angle| angle.min(180) as u8));
+            let dwell = u16::try_from(frame.dwell_ms).unwrap_or(u16::MAX);
+            bytes.extend_from_slice(&dwell.to_le_bytes());
+            bytes.push(u8::from(frame.hold));
+        }
+        bytes
+    }
+
+    /// Decode a gait produced by `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, GaitDecodeError> {
+        let Some(&version) = bytes.first() else {
+            return Err(GaitDecodeError::InvalidLength {
+                expected: HEADER_LEN,
+                actual: 0,
+            });
+        };
+        if version != GAIT_FORMAT_VERSION {
+            return Err(GaitDecodeError::UnsupportedVersion(version));
+        }
+        if bytes.len() < HEADER_LEN {
+            return Err(GaitDecodeError::InvalidLength {
+                expected: HEADER_LEN,
+                actual: bytes.len(),
+            });
+        }
+
+        let count = usize::from(u16::from_le_bytes([bytes[1], bytes[2]]));
+        let expected = HEADER_LEN + count * FRAME_LEN;
+        if bytes.len() != expected {
+            return Err(GaitDecodeError::InvalidLength {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+
+        let frames = bytes[HEADER_LEN..]
+            .chunks_exact(FRAME_LEN)
+            .enumerate()
+            .map(|(frame, chunk)| {
+                if let Some(&degrees) = chunk[..4].iter().find(|&&a| a > 180) {
+                    return Err(GaitDecodeError::InvalidAngle { frame, degrees });
+                }
+                let angles = [chunk[0], chunk[1], chunk[2], chunk[3]].map(u32::from);
+                let dwell_ms = u32::from(u16::from_le_bytes([chunk[4], chunk[5]]));
+                match chunk[6] {
+                    0 => Ok(Pose::relaxed(angles, dwell_ms)),
+                    1 => Ok(Pose::new(angles, dwell_ms)),
+                    value => Err(GaitDecodeError::InvalidHold { frame, value }),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self::new(frames))
+    }
+}
+
+impl<P: PwmBackend, D: Delay> ServoController<P, D> {
+    /// Play every frame of a gait once, blocking for each frame's dwell
+    ///
+    /// After the dwell of a pose without `hold`, every leg is detached; the
+    /// next frame re-attaches them. With a lead-in set (`set_gait_lead_in_ms`),
+    /// the legs first blend from their tracked angles into the first frame.
+    pub fn play_sequence(&mut self, gait: &Gait) -> Result<()> {
+        if let Some(first) = gait.frames().first() {
+            self.lead_in(first.angles)?;
+        }
+        for frame in gait.frames() {
+            let [right_back, left_back, right_front, left_front] = frame.angles;
+            self.set_servo_angles(right_back, left_back, right_front, left_front)?;
+            self.delay_ms(frame.dwell_ms);
+            if !frame.hold {
+                self.detach_all()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check an untrusted gait against every safety limit before playing it
+    ///
+    /// Runs `Gait::validate_with(thresholds)` and also flags angles outside
+    /// the soft limits, moves the deadband would swallow (looping back to the
+    /// first frame like playback) and a total duration over `max_duration_ms`.
+    /// Problems are sorted by frame, whole-gait problems last.
+    pub fn validate_choreography(
+        &self,
+        gait: &Gait,
+        thresholds: GaitThresholds,
+        max_duration_ms: u32,
+    ) -> Result<(), Vec<GaitWarning>> {
+        let mut problems = gait.validate_with(thresholds).err().unwrap_or_default();
+
+        let frames = gait.frames();
+        for (frame, pose) in frames.iter().enumerate() {
+            let previous = &frames[(frame + frames.len() - 1) % frames.len()];
+            for leg in LEGS {
+                let (min, max) = self.soft_limits(leg);
+                let (from, to) = (previous.angles[leg.index()], pose.angles[leg.index()]);
+                if !(min..=max).contains(&to) {
+                    problems.push(GaitWarning::OutsideSoftLimits {
+                        frame,
+                        leg,
+                        degrees: to,
+                        min,
+                        max,
+                    });
+                }
+                let change = self
+                    .limit_angle(leg, from)
+                    .abs_diff(self.limit_angle(leg, to));
+                if change > 0 && change <= self.deadband() {
+                    problems.push(GaitWarning::WithinDeadband {
+                        frame,
+                        leg,
+                        from,
+                        to,
+                    });
+                }
+            }
+        }
+        if gait.duration_ms() > max_duration_ms {
+            problems.push(GaitWarning::TooLong {
+                duration_ms: gait.duration_ms(),
+                max_ms: max_duration_ms,
+            });
+        }
+
+        problems.sort_by_key(|problem| problem.frame().unwrap_or(usize::MAX));
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// Ease every gait start instead of snapping to its first frame
+    ///
+    /// `play_sequence` spends `ms` blending linearly from the tracked angles
+    /// to the first frame, one blended pose every `SMOOTH_STEP_MS`, before
+    /// playing the frames as usual; the last blended pose is the first frame
+    /// itself. Nothing is blended when the legs already hold the first
+    /// frame. Gait methods that loop call `play_sequence` once per cycle, so
+    /// each later cycle also blends in from the previous cycle's last frame.
+    /// The default of 0 turns the lead-in off.
+    pub fn set_gait_lead_in_ms(&mut self, ms: u32) {
+        self.gait_lead_in_ms = ms;
+    }
+
+    /// Lead-in set with `set_gait_lead_in_ms`
+    pub fn gait_lead_in_ms(&self) -> u32 {
+        self.gait_lead_in_ms
+    }
+
+    /// Blend from the tracked angles to `to` over the gait lead-in
+    fn lead_in(&mut self, to: [u32; 4]) -> Result<()> {
+        let from = self.current_angles();
+        if self.gait_lead_in_ms == 0 || from == to {
+            return Ok(());
+        }
+        let steps = (self.gait_lead_in_ms / SMOOTH_STEP_MS).max(1);
+        for step in 1..=steps {
+            self.set_blended_pose(from, to, step as f32 / steps as f32)?;
+            self.delay_ms(self.gait_lead_in_ms / steps);
+        }
+        Ok(())
+    }
+
+    /// `play_sequence` with `timing` overriding the configured settle time and
+    /// lead-in for this playback only
+    pub fn play_sequence_with(&mut self, gait: &Gait, timing: Timing) -> Result<()> {
+        self.with_timing(timing, |controller| controller.play_sequence(gait))
+    }
+}
+
+/// A frame whose writes took longer than its dwell, from `GaitRunner`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameOverrun {
+    /// Index of the frame within the gait
+    pub frame: usize,
+    /// Time taken to write the frame, settle time included
+    pub work: Duration,
+    /// Dwell the frame was given
+    pub dwell_ms: u32,
+}
+
+/// Non-blocking gait player driven from the main loop
+///
+/// Frame transitions are scheduled against absolute time: frame `n` of a cycle
+/// fires at `start + sum(dwell[0..n])`, where `start` is the first `poll`. A
+/// late poll therefore never shifts later frames. If a poll is so late that
+/// several frames are due, only the most recent one is written, keeping the
+/// legs where the schedule says they should be.
+///
+/// Each poll also times the frame it writes, with the controller's `Clock`.
+/// A frame whose writes take longer
+/// than its dwell pushes every later frame late, a sign that the gait is too
+/// fast for the hardware: it is logged as a warning and kept for
+/// `last_overrun`.
+#[derive(Debug, Clone)]
+pub struct GaitRunner {
+    gait: Gait,
+    /// Cycles to play, `None` for forever
+    cycles: Option<u32>,
+    /// Absolute time at which the first frame fired
+    start: Option<Instant>,
+    /// Global index (across cycles) of the last frame written
+    last_frame: Option<u64>,
+    /// Most recent frame that outlasted its dwell
+    last_overrun: Option<FrameOverrun>,
+    finished: bool,
+}
+
+impl GaitRunner {
+    /// Play `gait` in a loop until stopped
+    pub fn new(gait: Gait) -> Self {
+        Self {
+            gait,
+            cycles: None,
+            start: None,
+            last_frame: None,
+            last_overrun: None,
+            finished: false,
+        }
+    }
+
+    /// Play `gait` for a fixed number of cycles
+    pub fn with_cycles(gait: Gait, cycles: u32) -> Self {
+        Self {
+            cycles: Some(cycles),
+            ..Self::new(gait)
+        }
+    }
+
+    /// Whether all cycles have been played (never true for looping runners)
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Most recent frame whose writes took longer than its dwell, if any
+    pub fn last_overrun(&self) -> Option<FrameOverrun> {
+        self.last_overrun
+    }
+
+    /// Stop playback; later polls do nothing
+    pub fn stop(&mut self) {
+        self.finished = true;
+    }
+
+    /// Absolute time at which the given global frame index is due
+    fn frame_due(&self, start: Instant, frame: u64) -> Instant {
+        let len = self.gait.len() as u64;
+        let full_cycles = frame / len;
+        let within: u64 = self.gait.frames()[..(frame % len) as usize]
+            .iter()
+            .map(|f| u64::from(f.dwell_ms))
+            .sum();
+        let elapsed = full_cycles * u64::from(self.gait.duration_ms()) + within;
+        start + Duration::from_millis(elapsed)
+    }
+
+    /// Global index of the latest frame due at `now`
+    ///
+    /// `next` is the first frame not yet written and is known to be due.
+    fn due_frame(&self, start: Instant, now: Instant, next: u64) -> u64 {
+        let cycle_ms = u64::from(self.gait.duration_ms());
+        if cycle_ms == 0 {
+            // Zero-dwell gaits have every frame due at once: advance one per poll
+            return next;
+        }
+
+        let elapsed = now.duration_since(start).as_millis() as u64;
+        let mut offset = elapsed % cycle_ms;
+        let mut index = 0;
+        for frame in &self.gait.frames()[..self.gait.len() - 1] {
+            if offset < u64::from(frame.dwell_ms) {
+                break;
+            }
+            offset -= u64::from(frame.dwell_ms);
+            index += 1;
+        }
+        (elapsed / cycle_ms) * self.gait.len() as u64 + index
+    }
+
+    /// Advance playback to `now`
+    ///
+    /// Writes the frame scheduled for `now` if it hasn't been written yet and
+    /// returns its index within the gait. Returns `None` when nothing was due.
+    pub fn poll<P: PwmBackend, D: Delay>(
+        &mut self,
+        controller: &mut ServoController<P, D>,
+        now: Instant,
+    ) -> Result<Option<usize>> {
+        let clock = controller.clock();
+        self.poll_timed(controller, now, || clock.now())
+    }
+
+    /// `poll` with the frame's writes timed by `clock`
+    fn poll_timed<P: PwmBackend, D: Delay>(
+        &mut self,
+        controller: &mut ServoController<P, D>,
+        now: Instant,
+        mut clock: impl FnMut() -> Instant,
+    ) -> Result<Option<usize>> {
+        if self.finished || self.gait.is_empty() || self.cycles == Some(0) {
+            self.finished = true;
+            return Ok(None);
+        }
+
+        let start = *self.start.get_or_insert(now);
+        let next = self.last_frame.map_or(0, |f| f + 1);
+        if self.frame_due(start, next) > now {
+            return Ok(None);
+        }
+
+        let len = self.gait.len() as u64;
+        let mut frame = self.due_frame(start, now, next);
+
+        // Cap at the final frame of the final cycle; it is held until its
+        // dwell expires, then the runner finishes
+        if let Some(cycles) = self.cycles {
+            let total = u64::from(cycles) * len;
+            if frame >= total {
+                if self.last_frame == Some(total - 1) {
+                    self.finished = true;
+                    return Ok(None);
+                }
+                frame = total - 1;
+            }
+        }
+
+        let index = (frame % len) as usize;
+        let pose = self.gait.frames()[index];
+        let [right_back, left_back, right_front, left_front] = pose.angles;
+        let work_start = clock();
+        controller.set_servo_angles(right_back, left_back, right_front, left_front)?;
+        let work = clock().saturating_duration_since(work_start);
+        self.last_frame = Some(frame);
+
+        if work > Duration::from_millis(u64::from(pose.dwell_ms)) {
+            log_warn!(
+                target: controller.log_target(),
+                "Gait frame {} took {} ms, over its {} ms dwell",
+                index,
+                work.as_millis(),
+                pose.dwell_ms
+            );
+            self.last_overrun = Some(FrameOverrun {
+                frame: index,
+                work,
+                dwell_ms: pose.dwell_ms,
+            });
+        }
+        Ok(Some(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::*;
+    use crate::{Ms, angle_to_duty};
+
+    fn three_frame_gait() -> Gait {
+        Gait::new(vec![
+            Pose::new([0; 4], 100),
+            Pose::new([90; 4], 200),
+            Pose::new([180; 4], 100),
+        ])
+    }
+
+    fn ms(start: Instant, ms: u64) -> Instant {
+        start + Duration::from_millis(ms)
+    }
+
+    /// Test that the gait macro builds the same frames as `Gait::new`
+    #[test]
+    fn test_gait_macro() {
+        let step = 45;
+        let gait = crate::gait! {
+            [90, 90, 90, 90] @ 300;
+            [90 - step, 90, 90 - step, 90] @ 150;
+            [180, 0, 180, 0,] @ 2 * 100
+        };
+        assert_eq!(
+            gait,
+            Gait::new(vec![
+                Pose::new([90, 90, 90, 90], 300),
+                Pose::new([45, 90, 45, 90], 150),
+                Pose::new([180, 0, 180, 0], 200),
+            ])
+        );
+        assert_eq!(crate::gait! {}, Gait::default());
+    }
+
+    /// Test that gaits survive a byte round trip
+    #[test]
+    fn test_gait_bytes_round_trip() {
+        let gait = three_frame_gait();
+        let bytes = gait.to_bytes();
+
+        assert_eq!(bytes.len(), 3 + 3 * 7);
+        assert_eq!(&bytes[..3], &[GAIT_FORMAT_VERSION, 3, 0]);
+        assert_eq!(Gait::from_bytes(&bytes), Ok(gait));
+        assert_eq!(
+            Gait::from_bytes(&Gait::default().to_bytes()),
+            Ok(Gait::default())
+        );
+
+        // Relaxed frames stay relaxed
+        let relaxed = Gait::new(vec![
+            Pose::new([90; 4], 100),
+            Pose::relaxed([45, 135, 45, 135], 250),
+        ]);
+        let bytes = relaxed.to_bytes();
+        assert_eq!(&bytes[3 + 6..3 + 7], &[1]);
+        assert_eq!(&bytes[3 + 7 + 6..], &[0]);
+        assert_eq!(Gait::from_bytes(&bytes), Ok(relaxed));
+    }
+
+    /// Test the CSV timeline of a known gait
+    #[test]
+    fn test_to_csv() {
+        let csv = crate::gaits::walk(45, Ms(300)).to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(
+            lines[0],
+            "time_ms,right_back,left_back,right_front,left_front"
+        );
+        assert_eq!(lines[1], "0,45,90,45,90");
+        assert_eq!(lines[2], "300,135,90,135,90");
+        assert_eq!(lines[5], "1200,90,90,90,90");
+        assert_eq!(lines.len(), 6);
+
+        assert_eq!(Gait::default().to_csv().lines().count(), 1);
+    }
+
+    /// Test that truncated, padded and corrupt input is rejected
+    #[test]
+    fn test_gait_from_malformed_bytes() {
+        let bytes = three_frame_gait().to_bytes();
+
+        assert_eq!(
+            Gait::from_bytes(&[]),
+            Err(GaitDecodeError::InvalidLength {
+                expected: 3,
+                actual: 0
+            })
+        );
+        assert_eq!(
+            Gait::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(GaitDecodeError::InvalidLength {
+                expected: 24,
+                actual: 23
+            })
+        );
+
+        let mut padded = bytes.clone();
+        padded.push(0);
+        assert!(matches!(
+            Gait::from_bytes(&padded),
+            Err(GaitDecodeError::InvalidLength { .. })
+        ));
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[0] = 9;
+        assert_eq!(
+            Gait::from_bytes(&wrong_version),
+            Err(GaitDecodeError::UnsupportedVersion(9))
+        );
+
+        let mut bad_hold = bytes.clone();
+        bad_hold[3 + 7 + 6] = 2;
+        assert_eq!(
+            Gait::from_bytes(&bad_hold),
+            Err(GaitDecodeError::InvalidHold { frame: 1, value: 2 })
+        );
+
+        let mut bad_angle = bytes;
+        bad_angle[3 + 7 + 2] = 200;
+        assert_eq!(
+            Gait::from_bytes(&bad_angle),
+            Err(GaitDecodeError::InvalidAngle {
+                frame: 1,
+                degrees: 200
+            })
+        );
+    }
+
+    /// Test that scaling by 0.5 halves each dwell
+    #[test]
+    fn test_scale_time_halves() {
+        let fast = three_frame_gait().scale_time(0.5).unwrap();
+
+        let dwells: Vec<u32> = fast.frames().iter().map(|f| f.dwell_ms).collect();
+        assert_eq!(dwells, vec![50, 100, 50]);
+        assert_eq!(fast.frames()[1].angles, [90; 4]);
+        assert_eq!(
+            three_frame_gait().scale_time(2.0).unwrap().duration_ms(),
+            800
+        );
+    }
+
+    /// Test that scaled dwells respect the floor and bad factors are rejected
+    #[test]
+    fn test_scale_time_floor_and_errors() {
+        let fastest = three_frame_gait().scale_time(0.01).unwrap();
+        assert!(
+            fastest
+                .frames()
+                .iter()
+                .all(|f| f.dwell_ms == MIN_SCALED_DWELL_MS)
+        );
+
+        for factor in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            let err = three_frame_gait().scale_time(factor).unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<ServoError>(),
+                Some(ServoError::InvalidTimeScale { .. })
+            ));
+        }
+    }
+
+    /// Test that reversing flips the frame order and optionally mirrors angles
+    #[test]
+    fn test_reversed() {
+        let gait = Gait::new(vec![
+            Pose::new([0, 45, 90, 135], 100),
+            Pose::new([180, 90, 60, 30], 200),
+        ]);
+
+        assert_eq!(
+            gait.reversed(false).frames(),
+            &[
+                Pose::new([180, 90, 60, 30], 200),
+                Pose::new([0, 45, 90, 135], 100)
+            ]
+        );
+        assert_eq!(
+            gait.reversed(true).frames(),
+            &[
+                Pose::new([0, 90, 120, 150], 200),
+                Pose::new([180, 135, 90, 45], 100)
+            ]
+        );
+        assert_eq!(gait.reversed(true).reversed(true), gait);
+    }
+
+    /// Test that a 170° swing is scaled to the limit and the rest in
+    /// proportion
+    #[test]
+    fn test_limit_excursion() {
+        let gait = Gait::new(vec![
+            Pose::new([170, 90, 50, 90], 100),
+            Pose::relaxed([10, 100, 130, 90], 200),
+        ]);
+
+        let limited = gait.limit_excursion(40);
+
+        assert_eq!(
+            limited.frames(),
+            &[
+                Pose::new([130, 90, 70, 90], 100),
+                Pose::relaxed([50, 95, 110, 90], 200)
+            ]
+        );
+        assert_eq!(gait.limit_excursion(80), gait);
+        assert_eq!(gait.limit_excursion(0).frames()[0].angles, [90; 4]);
+        let clamped = Gait::new(vec![Pose::new([250, 0, 90, 90], 100)]);
+        assert_eq!(
+            clamped.limit_excursion(45).frames()[0].angles,
+            [135, 45, 90, 90]
+        );
+    }
+
+    /// Test that overlaid legs follow `other` in time and the rest `self`
+    #[test]
+    fn test_overlay() {
+        let walk = crate::gaits::walk(45, Ms(100));
+        let wave = crate::gaits::wave(30, Ms(100));
+
+        let waving = walk.overlay(&wave, &[Leg::RightFront]);
+        assert_eq!(waving.len(), walk.len());
+        assert_eq!(waving.duration_ms(), walk.duration_ms());
+        for (i, frame) in waving.frames().iter().enumerate() {
+            let (own, theirs) = (walk.frames()[i].angles, wave.frames()[i % 4].angles);
+            assert_eq!(frame.angles, [own[0], own[1], theirs[2], own[3]]);
+        }
+
+        // A slower two-frame overlay splits the frames it changes within
+        let slow = Gait::new(vec![Pose::new([0; 4], 250), Pose::new([180; 4], 250)]);
+        let overlaid = walk.overlay(&slow, &[Leg::LeftBack, Leg::LeftFront]);
+        let timeline: Vec<([u32; 4], u32)> = overlaid
+            .frames()
+            .iter()
+            .map(|f| (f.angles, f.dwell_ms))
+            .collect();
+        assert_eq!(
+            timeline,
+            vec![
+                ([45, 0, 45, 0], 100),
+                ([135, 0, 135, 0], 100),
+                ([90, 0, 90, 0], 50),
+                ([90, 180, 90, 180], 50),
+                ([90, 180, 90, 180], 100),
+                ([90, 180, 90, 180], 100),
+            ]
+        );
+
+        // Without a timeline, frames are matched by position
+        let instant = Gait::new(vec![Pose::new([1; 4], 0), Pose::new([2; 4], 0)]);
+        let zero = Gait::new(vec![Pose::new([90; 4], 0); 4]);
+        let matched: Vec<u32> = zero
+            .overlay(&instant, &Leg::ALL)
+            .frames()
+            .iter()
+            .map(|f| f.angles[0])
+            .collect();
+        assert_eq!(matched, vec![1, 1, 2, 2]);
+        assert_eq!(walk.overlay(&Gait::default(), &[Leg::RightBack]), walk);
+        assert_eq!(walk.overlay(&wave, &[]), walk);
+    }
+
+    /// Test that a smooth gait passes validation
+    #[test]
+    fn test_validate_smooth_gait() {
+        let gait = Gait::new(vec![
+            Pose::new([60, 90, 120, 90], 100),
+            Pose::new([90, 60, 90, 120], 100),
+            Pose::new([120, 90, 60, 90], 100),
+        ]);
+
+        assert_eq!(gait.validate(), Ok(()));
+        assert_eq!(Gait::default().validate(), Ok(()));
+    }
+
+    /// Test that jumps, out-of-range angles and zero dwells are flagged
+    #[test]
+    fn test_validate_flags_mistakes() {
+        let gait = Gait::new(vec![
+            Pose::new([5, 90, 90, 90], 100),
+            Pose::new([175, 90, 90, 200], 0),
+        ]);
+
+        let warnings = gait.validate().unwrap_err();
+
+        assert!(warnings.contains(&GaitWarning::LargeJump {
+            frame: 1,
+            leg: Leg::RightBack,
+            from: 5,
+            to: 175
+        }));
+        // The loop back to the first frame jumps too
+        assert!(warnings.contains(&GaitWarning::LargeJump {
+            frame: 0,
+            leg: Leg::RightBack,
+            from: 175,
+            to: 5
+        }));
+        assert!(warnings.contains(&GaitWarning::OutOfRange {
+            frame: 1,
+            leg: Leg::LeftFront,
+            degrees: 200
+        }));
+        assert!(warnings.contains(&GaitWarning::ShortDwell {
+            frame: 1,
+            dwell_ms: 0
+        }));
+
+        // Looser thresholds only leave the range error
+        let loose = GaitThresholds {
+            max_jump_deg: 180,
+            min_dwell_ms: 0,
+        };
+        assert_eq!(
+            gait.validate_with(loose),
+            Err(vec![GaitWarning::OutOfRange {
+                frame: 1,
+                leg: Leg::LeftFront,
+                degrees: 200
+            }])
+        );
+    }
+
+    /// Test that library gaits built from mirrored halves are symmetric
+    #[test]
+    fn test_symmetry_report_symmetric() {
+        let trot = crate::gaits::trot(45, Ms(100)).symmetry_report();
+        assert!(trot.is_symmetric(), "{trot:?}");
+        assert_eq!((trot.left_right_shift, trot.left_right_deg), (2, 0));
+        assert_eq!((trot.front_back_shift, trot.front_back_deg), (2, 0));
+
+        let walk = crate::gaits::walk(45, Ms(100)).symmetry_report();
+        assert!(walk.is_symmetric(), "{walk:?}");
+        assert_eq!(walk.left_right_shift, 2);
+        assert_eq!(walk.front_back_shift, 0);
+
+        assert!(Gait::default().symmetry_report().is_symmetric());
+    }
+
+    /// Test that a broken gait is flagged, and small errors are tolerated
+    #[test]
+    fn test_symmetry_report_flags_broken() {
+        let mut frames = crate::gaits::trot(45, Ms(100)).frames().to_vec();
+
+        // Within tolerance
+        frames[1].angles[3] += 3;
+        let report = Gait::new(frames.clone()).symmetry_report();
+        assert!(report.is_symmetric(), "{report:?}");
+        assert_eq!(report.max_asymmetry_deg(), 3);
+
+        // Left front overshoots on one step only
+        frames[1].angles[3] += 17;
+        let report = Gait::new(frames).symmetry_report();
+        assert!(!report.is_left_right_symmetric());
+        assert!(!report.is_front_back_symmetric());
+        assert_eq!(report.max_asymmetry_deg(), 20);
+
+        // Only one leg moves: nothing else repeats it
+        let wave = crate::gaits::wave(45, Ms(100)).symmetry_report();
+        assert!(!wave.is_left_right_symmetric());
+        assert!(!wave.is_front_back_symmetric());
+        assert_eq!(wave.max_asymmetry_deg(), 45);
+    }
+
+    /// Test that play_sequence writes each frame then waits its dwell
+    #[test]
+    fn test_play_sequence() {
+        let (mut controller, log) = mock_controller();
+
+        controller.play_sequence(&three_frame_gait()).unwrap();
+
+        let delays: Vec<Command> = log
+            .commands()
+            .into_iter()
+            .filter(|c| matches!(c, Command::Delay(_)))
+            .collect();
+        assert_eq!(
+            delays,
+            vec![
+                Command::Delay(100),
+                Command::Delay(200),
+                Command::Delay(100)
+            ]
+        );
+        assert_eq!(controller.current_angles(), [180; 4]);
+    }
+
+    /// Test that a noisy recording of a 4-frame cycle loops into that cycle
+    #[test]
+    fn test_gait_from_recording() {
+        let cycle = [
+            [45, 90, 45, 90],
+            [135, 90, 135, 90],
+            [90, 45, 90, 45],
+            [90, 135, 90, 135],
+        ];
+        let jitter = [0, 1, 2, 1, 1, 2, 0, 1, 2, 0, 1, 1];
+        let samples: Vec<[u32; 4]> = (0..12)
+            .map(|i| cycle[i % 4].map(|angle| angle + jitter[i]))
+            .collect();
+
+        assert_eq!(detect_cycle(&samples, 3), Some(4));
+        let gait = Gait::from_recording(&samples, 300, 3).unwrap();
+        assert_eq!(gait.len(), 4);
+        let angles: Vec<[u32; 4]> = gait.frames().iter().map(|f| f.angles).collect();
+        assert_eq!(angles, cycle.map(|angles| angles.map(|angle| angle + 1)));
+        assert_eq!(gait.duration_ms(), 4 * 300);
+
+        // Starting mid-cycle gives the same cycle, rotated
+        let gait = Gait::from_recording(&samples[2..], 300, 3).unwrap();
+        assert_eq!(gait.frames()[0].angles, [91, 46, 91, 46]);
+
+        // A sweep never repeats, and one cycle is not enough to tell
+        let sweep: Vec<[u32; 4]> = (0..12).map(|i| [i * 10; 4]).collect();
+        assert_eq!(detect_cycle(&sweep, 3), None);
+        assert_eq!(Gait::from_recording(&samples[..4], 300, 3), None);
+    }
+
+    /// Test that a choreography breaking the soft limits in frame 3 is
+    /// pinpointed, along with its other problems
+    #[test]
+    fn test_validate_choreography() {
+        let (mut controller, _log) = mock_controller();
+        controller.set_soft_limits(Leg::RightFront, 30, 150);
+        controller.set_deadband(2);
+        let thresholds = GaitThresholds::default();
+        let gait = Gait::new(vec![
+            Pose::new([90; 4], 100),
+            Pose::new([60, 90, 120, 90], 100),
+            Pose::new([90, 91, 90, 90], 100),
+            Pose::new([90, 95, 160, 90], 100),
+        ]);
+
+        assert_eq!(
+            controller.validate_choreography(&gait, thresholds, 400),
+            Err(vec![
+                GaitWarning::WithinDeadband {
+                    frame: 2,
+                    leg: Leg::LeftBack,
+                    from: 90,
+                    to: 91
+                },
+                GaitWarning::OutsideSoftLimits {
+                    frame: 3,
+                    leg: Leg::RightFront,
+                    degrees: 160,
+                    min: 30,
+                    max: 150
+                },
+            ])
+        );
+
+        // The base validators and the duration limit report too
+        let problems = controller
+            .validate_choreography(
+                &gait,
+                GaitThresholds {
+                    max_jump_deg: 20,
+                    ..thresholds
+                },
+                300,
+            )
+            .unwrap_err();
+        assert_eq!(problems.first().and_then(GaitWarning::frame), Some(0));
+        assert!(problems.contains(&GaitWarning::LargeJump {
+            frame: 1,
+            leg: Leg::RightBack,
+            from: 90,
+            to: 60
+        }));
+        assert_eq!(
+            problems.last(),
+            Some(&GaitWarning::TooLong {
+                duration_ms: 400,
+                max_ms: 300
+            })
+        );
+
+        let safe = crate::gaits::walk(45, Ms(100));
+        assert_eq!(
+            controller.validate_choreography(&safe, thresholds, 500),
+            Ok(())
+        );
+    }
+
+    /// Test that only poses without hold relax after their dwell
+    #[test]
+    fn test_play_sequence_hold() {
+        let (mut controller, log) = mock_controller();
+        let gait = Gait::new(vec![
+            Pose::new([45; 4], 100),
+            Pose::relaxed([90; 4], 200),
+            Pose::new([135; 4], 100),
+        ]);
+        assert!(!gait.scale_time(2.0).unwrap().frames()[1].hold);
+        assert!(!gait.reversed(true).frames()[1].hold);
+
+        controller.play_sequence(&gait).unwrap();
+
+        let commands = log.commands();
+        let disables: Vec<usize> = commands
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| matches!(c, Command::Disable(_)))
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(disables.len(), 4, "one relax of every leg");
+        assert_eq!(commands[disables[0] - 1], Command::Delay(200));
+        assert!(LEGS.iter().all(|&leg| !controller.is_detached(leg)));
+        assert_eq!(commands.last(), Some(&Command::Delay(100)));
+    }
+
+    /// Test that the lead-in blends from the tracked angles into the first
+    /// frame before the gait proper plays
+    #[test]
+    fn test_play_sequence_lead_in() {
+        let (mut controller, log) = mock_controller();
+        controller.set_all_servos_angle(90).unwrap();
+        let gait = Gait::new(vec![
+            Pose::new([50, 130, 90, 90], 100),
+            Pose::new([90; 4], 100),
+        ]);
+        controller.set_gait_lead_in_ms(100);
+        assert_eq!(controller.gait_lead_in_ms(), 100);
+
+        log.clear();
+        let start = log.now_ms();
+        controller.play_sequence(&gait).unwrap();
+
+        let duty = |angle| angle_to_duty(angle, MOCK_MAX_DUTY);
+        let blended = [82, 74, 66, 58, 50];
+        let mut right_back: Vec<u32> = blended.iter().map(|&a| duty(a)).collect();
+        right_back.extend([duty(50), duty(90)]);
+        assert_eq!(log.duties_for(Leg::RightBack), right_back);
+        let left_back: Vec<u32> = blended.iter().map(|&a| duty(180 - a)).collect();
+        assert_eq!(log.duties_for(Leg::LeftBack)[..5], left_back[..]);
+        assert_eq!(log.duties_for(Leg::RightFront), vec![duty(90); 7]);
+        assert_eq!(log.now_ms() - start, 100 + 2 * 100);
+
+        // Already at the first frame, or overridden per call: no lead-in
+        controller.set_servo_angles(50, 130, 90, 90).unwrap();
+        log.clear();
+        controller.play_sequence(&gait).unwrap();
+        assert_eq!(log.duties_for(Leg::RightBack).len(), 2);
+        log.clear();
+        controller
+            .play_sequence_with(
+                &gait,
+                Timing {
+                    lead_in_ms: Some(0),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(log.duties_for(Leg::RightBack).len(), 2);
+        assert_eq!(controller.gait_lead_in_ms(), 100);
+    }
+
+    /// Test that frames fire at absolute times despite irregular polling
+    #[test]
+    fn test_runner_irregular_polls() {
+        let (mut controller, _log) = mock_controller();
+        let mut runner = GaitRunner::new(three_frame_gait());
+        let t0 = Instant::now();
+
+        // Schedule: frame 0 @0, frame 1 @100, frame 2 @300, frame 0 @400
+        assert_eq!(runner.poll(&mut controller, t0).unwrap(), Some(0));
+        assert_eq!(runner.poll(&mut controller, ms(t0, 37)).unwrap(), None);
+        assert_eq!(runner.poll(&mut controller, ms(t0, 99)).unwrap(), None);
+        // A late poll fires frame 1 but does not shift frame 2
+        assert_eq!(runner.poll(&mut controller, ms(t0, 180)).unwrap(), Some(1));
+        assert_eq!(runner.poll(&mut controller, ms(t0, 299)).unwrap(), None);
+        assert_eq!(runner.poll(&mut controller, ms(t0, 300)).unwrap(), Some(2));
+        assert_eq!(runner.poll(&mut controller, ms(t0, 401)).unwrap(), Some(0));
+    }
+
+    /// Test that a very late poll skips straight to the frame now due
+    #[test]
+    fn test_runner_skips_missed_frames() {
+        let (mut controller, log) = mock_controller();
+        let mut runner = GaitRunner::new(three_frame_gait());
+        let t0 = Instant::now();
+
+        runner.poll(&mut controller, t0).unwrap();
+        log.clear();
+
+        // At 350 ms frame 1 (100) is stale and frame 2 (300) is current
+        assert_eq!(runner.poll(&mut controller, ms(t0, 350)).unwrap(), Some(2));
+        assert_eq!(
+            log.duties_for(crate::Leg::RightBack),
+            vec![angle_to_duty(180, 1024)]
+        );
+        // Next cycle still starts on schedule at 400
+        assert_eq!(runner.poll(&mut controller, ms(t0, 399)).unwrap(), None);
+        assert_eq!(runner.poll(&mut controller, ms(t0, 400)).unwrap(), Some(0));
+    }
+
+    /// Test that a frame whose writes outlast its dwell is reported
+    #[test]
+    fn test_runner_reports_overrun() {
+        let (mut controller, log) = mock_controller();
+        let mut runner = GaitRunner::new(three_frame_gait());
+        let t0 = Instant::now();
+        // The mock clock advances only with delays, such as the settle time
+        let clock = || ms(t0, log.now_ms());
+
+        runner.poll_timed(&mut controller, t0, clock).unwrap();
+        assert_eq!(runner.last_overrun(), None);
+
+        // Settling for 150 ms fits frame 1's 200 ms dwell but not frame 2's 100
+        controller.set_settle_time_ms(150);
+        runner
+            .poll_timed(&mut controller, ms(t0, 100), clock)
+            .unwrap();
+        assert_eq!(runner.last_overrun(), None);
+        runner
+            .poll_timed(&mut controller, ms(t0, 300), clock)
+            .unwrap();
+        assert_eq!(
+            runner.last_overrun(),
+            Some(FrameOverrun {
+                frame: 2,
+                work: Duration::from_millis(150),
+                dwell_ms: 100,
+            })
+        );
+
+        // The overrun is kept until a newer one replaces it
+        controller.set_settle_time_ms(0);
+        runner
+            .poll_timed(&mut controller, ms(t0, 400), clock)
+            .unwrap();
+        assert_eq!(runner.last_overrun().unwrap().frame, 2);
+    }
+
+    /// Test that a fixed-cycle runner finishes after the last dwell
+    #[test]
+    fn test_runner_finishes_after_cycles() {
+        let (mut controller, _log) = mock_controller();
+        let mut runner = GaitRunner::with_cycles(three_frame_gait(), 1);
+        let t0 = Instant::now();
+
+        assert_eq!(runner.poll(&mut controller, t0).unwrap(), Some(0));
+        // Far past the end: the final frame is still written once
+        assert_eq!(runner.poll(&mut controller, ms(t0, 5000)).unwrap(), Some(2));
+        assert!(!runner.is_finished());
+        assert_eq!(runner.poll(&mut controller, ms(t0, 5001)).unwrap(), None);
+        assert!(runner.is_finished());
+    }
+}
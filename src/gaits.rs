@@ -0,0 +1,375 @@
+//! # Gait Library
+//!
+//! Ready-made locomotion patterns as `Gait` frame tables.
+//!
+//! Every gait is built from a step height (`step_deg`, how far a leg swings
+//! either side of center) and a per-frame `dwell` such as `Ms(300)`. Angles
+//! are clamped so any step height produces valid frames. Play one with
+//! `ServoController::play_sequence` or `GaitRunner`, or use its frames as a
+//! starting point for a custom gait.
+//!
+//! Frame angles are in `set_servo_angles` order: right back, left back, right
+//! front, left front.
+
+use crate::{Gait, Leg, Ms, Pose};
+
+/// Step height used by the controller's built-in movements
+pub const DEFAULT_STEP_DEG: u32 = 45;
+
+/// Lifted/back position for a step height
+const fn lift(step_deg: u32) -> u32 {
+    90 - if step_deg > 90 { 90 } else { step_deg }
+}
+
+/// Forward position for a step height
+const fn reach(step_deg: u32) -> u32 {
+    90 + if step_deg > 90 { 90 } else { step_deg }
+}
+
+/// Step height for cycle `cycle` (from 0) of a gait warming up to `step_deg`
+///
+/// The first `warmup_cycles` cycles ramp up linearly: cycle `n` gets
+/// `(n + 1) / (warmup_cycles + 1)` of the full step height, so even the first
+/// cycle moves a little and the last warm-up cycle is close to full. Later
+/// cycles get the full height.
+pub fn warmup_step(step_deg: u32, cycle: u32, warmup_cycles: u32) -> u32 {
+    if cycle >= warmup_cycles {
+        return step_deg;
+    }
+    (u64::from(step_deg) * (u64::from(cycle) + 1) / (u64::from(warmup_cycles) + 1)) as u32
+}
+
+fn gait(frames: &[[u32; 4]], dwell: Ms) -> Gait {
+    Gait::new(
+        frames
+            .iter()
+            .map(|&angles| Pose::new(angles, dwell.0))
+            .collect(),
+    )
+}
+
+/// Walk: the right pair lifts and reaches, then the left pair, then center
+pub fn walk(step_deg: u32, dwell: impl Into<Ms>) -> Gait {
+    walk_sides(step_deg, step_deg, dwell.into())
+}
+
+/// Walk with a separate step height for the right and left legs
+fn walk_sides(right_step_deg: u32, left_step_deg: u32, dwell: Ms) -> Gait {
+    let (right_up, right_fwd) = (lift(right_step_deg), reach(right_step_deg));
+    let (left_up, left_fwd) = (lift(left_step_deg), reach(left_step_deg));
+    gait(
+        &[
+            [right_up, 90, right_up, 90],
+            [right_fwd, 90, right_fwd, 90],
+            [90, left_up, 90, left_up],
+            [90, left_fwd, 90, left_fwd],
+            [90; 4],
+        ],
+        dwell,
+    )
+}
+
+/// Step heights of the right and left legs for an arc with `turn_bias`
+///
+/// `turn_bias` is clamped to -100..=100. A positive bias shortens the right
+/// steps by that percentage, curving the path to the right; a negative bias
+/// shortens the left steps. The other side keeps the full `step_deg`, and 0
+/// walks straight.
+pub fn arc_steps(step_deg: u32, turn_bias: i32) -> (u32, u32) {
+    let bias = turn_bias.clamp(-100, 100);
+    let shorten = |percent: i32| (u64::from(step_deg) * (100 - percent) as u64 / 100) as u32;
+    if bias >= 0 {
+        (shorten(bias), step_deg)
+    } else {
+        (step_deg, shorten(-bias))
+    }
+}
+
+/// Arc walk: a `walk` whose sides take different step lengths (see
+/// `arc_steps`), for turning gradually while moving forward
+pub fn walk_arc(step_deg: u32, turn_bias: i32, dwell: impl Into<Ms>) -> Gait {
+    let (right_step_deg, left_step_deg) = arc_steps(step_deg, turn_bias);
+    walk_sides(right_step_deg, left_step_deg, dwell.into())
+}
+
+/// Level walk: a walk that keeps the body height constant
+///
+/// With one hip joint per leg, a hip sits at a height proportional to the
+/// cosine of its leg's deviation from 90°, whichever way the leg points. The
+/// classic `walk` parks one side at 90° while the other swings to
+/// `90 ± step_deg`, so the body bobs and rolls toward the swinging side.
+/// Here every leg is at `step_deg` from center in every frame: as the swing
+/// pair reaches forward, the stance pair shifts back by the same amount (which
+/// is also the stroke that pushes the body forward), and vice versa. The gait
+/// never returns to center, which would raise the body; only the servos'
+/// passage through 90° between frames remains.
+///
+/// The two frames end the right pair's swing and then the left pair's.
+pub fn walk_level(step_deg: u32, dwell: impl Into<Ms>) -> Gait {
+    let (up, fwd) = (lift(step_deg), reach(step_deg));
+    gait(&[[fwd, up, fwd, up], [up, fwd, up, fwd]], dwell.into())
+}
+
+/// Trot: diagonal pairs (right back + left front, left back + right front)
+/// alternate half a cycle apart
+pub fn trot(step_deg: u32, dwell: impl Into<Ms>) -> Gait {
+    let (up, fwd) = (lift(step_deg), reach(step_deg));
+    gait(
+        &[
+            [up, 90, 90, up],
+            [fwd, 90, 90, fwd],
+            [90, up, up, 90],
+            [90, fwd, fwd, 90],
+        ],
+        dwell.into(),
+    )
+}
+
+/// Bound: the front pair lifts and plants, then the back pair
+pub fn bound(step_deg: u32, dwell: impl Into<Ms>) -> Gait {
+    let up = lift(step_deg);
+    gait(
+        &[[90, 90, up, up], [90; 4], [up, up, 90, 90], [90; 4]],
+        dwell.into(),
+    )
+}
+
+/// Crab: the right and left sides alternate pushing in opposite directions to
+/// shuffle sideways
+pub fn crab(step_deg: u32, dwell: impl Into<Ms>) -> Gait {
+    let (up, fwd) = (lift(step_deg), reach(step_deg));
+    gait(
+        &[
+            [up, 90, up, 90],
+            [90, fwd, 90, fwd],
+            [fwd, 90, fwd, 90],
+            [90, up, 90, up],
+        ],
+        dwell.into(),
+    )
+}
+
+/// Wave: the front right leg swings back and forth while the others hold
+pub fn wave(step_deg: u32, dwell: impl Into<Ms>) -> Gait {
+    let (up, fwd) = (lift(step_deg), reach(step_deg));
+    gait(
+        &[
+            [90, 90, up, 90],
+            [90, 90, fwd, 90],
+            [90, 90, up, 90],
+            [90; 4],
+        ],
+        dwell.into(),
+    )
+}
+
+/// Order in which `creep` moves the legs: right front, left back, left front,
+/// right back
+pub const CREEP_ORDER: [Leg; 4] = [
+    Leg::RightFront,
+    Leg::LeftBack,
+    Leg::LeftFront,
+    Leg::RightBack,
+];
+
+/// Creep: a static crawl moving one leg at a time, in `CREEP_ORDER`
+///
+/// Each leg lifts, reaches forward and plants while the other three stay
+/// planted at 90°, so the body always rests on a tripod. This is the slowest
+/// gait (three frames per leg, twelve per cycle) and the most stable one, for
+/// carrying loads. The order steps one diagonal pair (right front, left back)
+/// and then the other, the usual sequence for a quadruped crawl.
+pub fn creep(step_deg: u32, dwell: impl Into<Ms>) -> Gait {
+    let (up, fwd) = (lift(step_deg), reach(step_deg));
+    let frames: Vec<[u32; 4]> = CREEP_ORDER
+        .iter()
+        .flat_map(|leg| {
+            [up, fwd, 90].map(|angle| {
+                let mut frame = [90; 4];
+                frame[leg.index()] = angle;
+                frame
+            })
+        })
+        .collect();
+    gait(&frames, dwell.into())
+}
+
+/// Order in which `march_in_place` lifts the legs: around the body
+pub const MARCH_ORDER: [Leg; 4] = [
+    Leg::RightFront,
+    Leg::RightBack,
+    Leg::LeftBack,
+    Leg::LeftFront,
+];
+
+/// March in place: each leg lifts and lowers in turn, in `MARCH_ORDER`
+///
+/// The other three legs stay planted at 90° and no leg reaches forward, so
+/// the body does not travel; use it to warm up the servos before a run.
+/// Going around the body means each lift is next to the previous one. Two
+/// frames per leg, eight per cycle.
+pub fn march_in_place(step_deg: u32, dwell: impl Into<Ms>) -> Gait {
+    let up = lift(step_deg);
+    let frames: Vec<[u32; 4]> = MARCH_ORDER
+        .iter()
+        .flat_map(|leg| {
+            [up, 90].map(|angle| {
+                let mut frame = [90; 4];
+                frame[leg.index()] = angle;
+                frame
+            })
+        })
+        .collect();
+    gait(&frames, dwell.into())
+}
+
+/// Every library gait with its name
+pub fn all(step_deg: u32, dwell: impl Into<Ms>) -> [(&'static str, Gait); 8] {
+    let dwell = dwell.into();
+    [
+        ("walk", walk(step_deg, dwell)),
+        ("walk_level", walk_level(step_deg, dwell)),
+        ("trot", trot(step_deg, dwell)),
+        ("bound", bound(step_deg, dwell)),
+        ("crab", crab(step_deg, dwell)),
+        ("wave", wave(step_deg, dwell)),
+        ("creep", creep(step_deg, dwell)),
+        ("march_in_place", march_in_place(step_deg, dwell)),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SupportInfo;
+
+    /// Test that every library gait stays in range for any step height
+    #[test]
+    fn test_library_gaits_in_range() {
+        for step_deg in [0, 10, DEFAULT_STEP_DEG, 90, 91, u32::MAX] {
+            for (name, gait) in all(step_deg, Ms(100)) {
+                assert!(!gait.is_empty(), "{name} has no frames");
+                for pose in gait.frames() {
+                    assert!(
+                        pose.angles.iter().all(|&angle| angle <= 180),
+                        "{name} (step {step_deg}) out of range: {:?}",
+                        pose.angles
+                    );
+                    assert_eq!(pose.dwell_ms, 100);
+                }
+            }
+        }
+    }
+
+    /// Test that the stance pair mirrors the swing pair in every frame
+    #[test]
+    fn test_walk_level_compensates() {
+        for step_deg in [10, DEFAULT_STEP_DEG, 80] {
+            let gait = walk_level(step_deg, Ms(100));
+            let frames: Vec<[u32; 4]> = gait.frames().iter().map(|f| f.angles).collect();
+
+            // Right swing ends forward while the left stance shifts back, then
+            // the left swing ends forward while the right stance shifts back
+            let (up, fwd) = (90 - step_deg, 90 + step_deg);
+            assert_eq!(frames, vec![[fwd, up, fwd, up], [up, fwd, up, fwd]]);
+
+            for [right_back, left_back, right_front, left_front] in frames {
+                assert_eq!(left_back, 180 - right_back);
+                assert_eq!(left_front, 180 - right_front);
+                // Every leg is equally far from center, so hips stay level
+                for angle in [right_back, left_back, right_front, left_front] {
+                    assert_eq!(angle.abs_diff(90), step_deg);
+                }
+            }
+        }
+    }
+
+    /// Test that the creep lifts at most one leg per frame, in order, and is
+    /// always supported
+    #[test]
+    fn test_creep_single_support_transfer() {
+        for step_deg in [DEFAULT_STEP_DEG, 30, 90] {
+            let gait = creep(step_deg, Ms(100));
+            assert_eq!(gait.len(), 12);
+
+            let mut moved = Vec::new();
+            for pose in gait.frames() {
+                let support = SupportInfo::from_angles(pose.angles);
+                let lifted: Vec<Leg> = crate::LEGS
+                    .into_iter()
+                    .filter(|&leg| !support.is_planted(leg))
+                    .collect();
+                assert!(lifted.len() <= 1, "{:?} lifts {lifted:?}", pose.angles);
+                assert!(support.stable, "{:?} is unsupported", pose.angles);
+                if let Some(&leg) = lifted.first()
+                    && moved.last() != Some(&leg)
+                {
+                    moved.push(leg);
+                }
+            }
+            assert_eq!(moved, CREEP_ORDER);
+            assert_eq!(gait.frames().last().unwrap().angles, [90; 4]);
+        }
+    }
+
+    /// Test that a positive bias shortens the right steps, a negative one the
+    /// left, and out-of-range biases are clamped
+    #[test]
+    fn test_walk_arc_steps() {
+        assert_eq!(arc_steps(40, 0), (40, 40));
+        assert_eq!(arc_steps(40, 25), (30, 40));
+        assert_eq!(arc_steps(40, -50), (40, 20));
+        assert_eq!(arc_steps(40, 1000), (0, 40));
+        assert_eq!(arc_steps(40, i32::MIN), (40, 0));
+        assert_eq!(walk_arc(45, 0, Ms(300)), walk(45, Ms(300)));
+
+        let frames: Vec<[u32; 4]> = walk_arc(40, 50, Ms(300))
+            .frames()
+            .iter()
+            .map(|f| f.angles)
+            .collect();
+        assert_eq!(
+            frames,
+            vec![
+                [70, 90, 70, 90],
+                [110, 90, 110, 90],
+                [90, 50, 90, 50],
+                [90, 130, 90, 130],
+                [90; 4],
+            ]
+        );
+    }
+
+    /// Test that the warm-up step height ramps up and then holds
+    #[test]
+    fn test_warmup_step() {
+        let steps: Vec<u32> = (0..6).map(|cycle| warmup_step(45, cycle, 3)).collect();
+        assert_eq!(steps, vec![11, 22, 33, 45, 45, 45]);
+        assert_eq!(warmup_step(45, 0, 0), 45);
+        assert_eq!(warmup_step(u32::MAX, 0, 1), u32::MAX / 2);
+        // The longest warm-up neither overflows nor reaches the full step early
+        assert_eq!(warmup_step(45, 0, u32::MAX), 0);
+        assert_eq!(warmup_step(45, u32::MAX - 1, u32::MAX), 44);
+        assert_eq!(warmup_step(45, u32::MAX, u32::MAX), 45);
+    }
+
+    /// Test that the walk gait matches the classic walk_forward pattern
+    #[test]
+    fn test_walk_frames() {
+        let angles: Vec<[u32; 4]> = walk(45, Ms(300))
+            .frames()
+            .iter()
+            .map(|f| f.angles)
+            .collect();
+        assert_eq!(
+            angles,
+            vec![
+                [45, 90, 45, 90],
+                [135, 90, 135, 90],
+                [90, 45, 90, 45],
+                [90, 135, 90, 135],
+                [90, 90, 90, 90],
+            ]
+        );
+    }
+}
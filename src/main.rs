@@ -5,9 +5,7 @@
 use anyhow::Result;
 use esp_idf_hal::peripherals::Peripherals;
 
-mod servo_controller;
-
-use servo_controller::{demo_servo_movements, setup_servos};
+use cobot_rs::setup_servos;
 
 fn main() -> Result<()> {
     // Initialize ESP-IDF
@@ -37,16 +35,17 @@ fn main() -> Result<()> {
 
     // Test walking pattern
     // log::info!("Testing walking pattern...");
-    // servo_controller.walk_forward(300)?;
+    // servo_controller.walk_forward(cobot_rs::Ms(300))?;
 
     // // Test wave gesture
     // log::info!("Testing wave gesture...");
-    // servo_controller.wave(50)?;
+    // servo_controller.wave(cobot_rs::Ms(50))?;
 
     // log::info!("Cycle complete, repeating...");
     // esp_idf_hal::delay::FreeRtos::delay_ms(3000);
-    
+
     loop {
-        // Do nothing in loop
+        // Idle without starving the FreeRTOS idle task
+        esp_idf_hal::delay::FreeRtos::delay_ms(1000);
     }
 }
@@ -0,0 +1,433 @@
+//! # Presets
+//!
+//! Named poses kept in flash across reboots, e.g. "alert", "rest" and "play",
+//! so they can be recalled by name over serial after a power cycle.
+//!
+//! Unlike `ServoController::define_pose`, which keeps poses in RAM, presets
+//! live in a `PresetStore` (`EspPresetStore` on the ESP32, a single NVS blob).
+//! Every change rewrites the whole `PresetTable`; the table is small enough
+//! that this is cheaper than tracking entries one by one.
+//!
+//! ## Format
+//!
+//! `PresetTable::to_bytes` writes a version byte (`PRESET_FORMAT_VERSION`) and
+//! a count byte, then per preset the name length, the UTF-8 name and one byte
+//! per leg angle in `set_servo_angles` order.
+
+use crate::{Delay, PwmBackend, ServoController};
+use anyhow::Result;
+use std::collections::HashMap;
+
+#[cfg(feature = "esp32")]
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+
+/// Current version of the `PresetTable::to_bytes` encoding
+pub const PRESET_FORMAT_VERSION: u8 = 1;
+
+/// Most presets a table holds
+pub const MAX_PRESETS: usize = 8;
+
+/// Longest preset name, in bytes
+pub const MAX_PRESET_NAME_LEN: usize = 15;
+
+/// Version and count bytes
+const HEADER_LEN: usize = 2;
+
+/// Largest encoded table: the header plus `MAX_PRESETS` full-length entries
+pub const MAX_PRESET_TABLE_LEN: usize = HEADER_LEN + MAX_PRESETS * (1 + MAX_PRESET_NAME_LEN + 4);
+
+/// Errors storing, recalling or decoding presets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PresetError {
+    /// The name is longer than `MAX_PRESET_NAME_LEN` bytes
+    NameTooLong { max: usize },
+    /// `MAX_PRESETS` presets are already stored under other names
+    TableFull { max: usize },
+    /// No preset with this name is stored
+    UnknownPreset(String),
+    /// The version byte is not `PRESET_FORMAT_VERSION`
+    UnsupportedVersion(u8),
+    /// The input ends before its last preset, or has bytes after it
+    InvalidLength,
+    /// A preset name is too long or not UTF-8
+    InvalidName { preset: usize },
+    /// A preset holds an angle outside 0-180°
+    InvalidAngle { preset: usize, degrees: u8 },
+}
+
+impl std::fmt::Display for PresetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PresetError::NameTooLong { max } => {
+                write!(f, "preset names are limited to {} bytes", max)
+            }
+            PresetError::TableFull { max } => write!(f, "cannot store more than {} presets", max),
+            PresetError::UnknownPreset(name) => write!(f, "preset '{}' is not stored", name),
+            PresetError::UnsupportedVersion(version) => {
+                write!(f, "unsupported preset format version {}", version)
+            }
+            PresetError::InvalidLength => write!(f, "preset table has an invalid length"),
+            PresetError::InvalidName { preset } => {
+                write!(f, "preset {} has an invalid name", preset)
+            }
+            PresetError::InvalidAngle { preset, degrees } => {
+                write!(f, "preset {} angle {}° is outside 0-180°", preset, degrees)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PresetError {}
+
+/// Named poses in the order they were first saved
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PresetTable {
+    presets: Vec<(String, [u32; 4])>,
+}
+
+impl PresetTable {
+    /// Empty table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store `angles` (in `set_servo_angles` order, clamped to 180°) under
+    /// `name`
+    ///
+    /// Saving an existing name replaces its angles, even in a full table.
+    pub fn insert(&mut self, name: &str, angles: [u32; 4]) -> Result<(), PresetError> {
+        if name.len() > MAX_PRESET_NAME_LEN {
+            return Err(PresetError::NameTooLong {
+                max: MAX_PRESET_NAME_LEN,
+            });
+        }
+        let angles = angles.map(|angle| angle.min(180));
+        if let Some((_, stored)) = self.presets.iter_mut().find(|(n, _)| n == name) {
+            *stored = angles;
+            return Ok(());
+        }
+        if self.presets.len() == MAX_PRESETS {
+            return Err(PresetError::TableFull { max: MAX_PRESETS });
+        }
+
+        self.presets.push((name.to_string(), angles));
+        Ok(())
+    }
+
+    /// Angles stored under `name`, if any
+    pub fn get(&self, name: &str) -> Option<[u32; 4]> {
+        self.presets
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|&(_, angles)| angles)
+    }
+
+    /// Number of stored presets
+    pub fn len(&self) -> usize {
+        self.presets.len()
+    }
+
+    /// Whether no preset is stored
+    pub fn is_empty(&self) -> bool {
+        self.presets.is_empty()
+    }
+
+    /// Presets as a map from name to angles
+    pub fn to_map(&self) -> HashMap<String, [u32; 4]> {
+        self.presets.iter().cloned().collect()
+    }
+
+    /// Encode the table for storage; at most `MAX_PRESET_TABLE_LEN` bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![PRESET_FORMAT_VERSION, self.presets.len() as u8];
+        for (name, angles) in &self.presets {
+            bytes.push(name.len() as u8);
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend(angles.map(|angle| angle as u8));
+        }
+        bytes
+    }
+
+    /// Decode a table produced by `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PresetError> {
+        let Some(&version) = bytes.first() else {
+            return Err(PresetError::InvalidLength);
+        };
+        if version != PRESET_FORMAT_VERSION {
+            return Err(PresetError::UnsupportedVersion(version));
+        }
+        let Some(&count) = bytes.get(1) else {
+            return Err(PresetError::InvalidLength);
+        };
+        if usize::from(count) > MAX_PRESETS {
+            return Err(PresetError::InvalidLength);
+        }
+
+        let mut table = Self::new();
+        let mut rest = &bytes[HEADER_LEN..];
+        for preset in 0..usize::from(count) {
+            let (&name_len, tail) = rest.split_first().ok_or(PresetError::InvalidLength)?;
+            let name_len = usize::from(name_len);
+            if name_len > MAX_PRESET_NAME_LEN {
+                return Err(PresetError::InvalidName { preset });
+            }
+            if tail.len() < name_len + 4 {
+                return Err(PresetError::InvalidLength);
+            }
+            let (name, tail) = tail.split_at(name_len);
+            let (angles, tail) = tail.split_at(4);
+            let name =
+                std::str::from_utf8(name).map_err(|_| PresetError::InvalidName { preset })?;
+            if let Some(&degrees) = angles.iter().find(|&&a| a > 180) {
+                return Err(PresetError::InvalidAngle { preset, degrees });
+            }
+
+            table.insert(
+                name,
+                [angles[0], angles[1], angles[2], angles[3]].map(u32::from),
+            )?;
+            rest = tail;
+        }
+        if !rest.is_empty() {
+            return Err(PresetError::InvalidLength);
+        }
+        Ok(table)
+    }
+}
+
+/// Persistent storage for the encoded `PresetTable`
+pub trait PresetStore {
+    /// Bytes last saved, or `None` if nothing has been saved yet
+    fn load(&mut self) -> Result<Option<Vec<u8>>>;
+
+    /// Replace the saved bytes
+    fn save(&mut self, bytes: &[u8]) -> Result<()>;
+}
+
+/// NVS namespace holding the presets
+#[cfg(feature = "esp32")]
+const PRESET_NAMESPACE: &str = "cobot";
+
+/// NVS key of the preset table blob
+#[cfg(feature = "esp32")]
+const PRESET_KEY: &str = "presets";
+
+/// Presets in the default NVS partition, as one blob
+#[cfg(feature = "esp32")]
+pub struct EspPresetStore {
+    nvs: EspNvs<NvsDefault>,
+}
+
+#[cfg(feature = "esp32")]
+impl EspPresetStore {
+    /// Open the preset namespace for reading and writing
+    pub fn new(partition: EspDefaultNvsPartition) -> Result<Self> {
+        Ok(Self {
+            nvs: EspNvs::new(partition, PRESET_NAMESPACE, true)?,
+        })
+    }
+}
+
+#[cfg(feature = "esp32")]
+impl PresetStore for EspPresetStore {
+    fn load(&mut self) -> Result<Option<Vec<u8>>> {
+        let mut buf = [0; MAX_PRESET_TABLE_LEN];
+        Ok(self.nvs.get_blob(PRESET_KEY, &mut buf)?.map(<[u8]>::to_vec))
+    }
+
+    fn save(&mut self, bytes: &[u8]) -> Result<()> {
+        self.nvs.set_blob(PRESET_KEY, bytes)?;
+        Ok(())
+    }
+}
+
+/// Table saved in `store`, empty if nothing was saved yet
+fn read_table(store: &mut dyn PresetStore) -> Result<PresetTable> {
+    match store.load()? {
+        Some(bytes) => Ok(PresetTable::from_bytes(&bytes)?),
+        None => Ok(PresetTable::new()),
+    }
+}
+
+impl<P: PwmBackend, D: Delay> ServoController<P, D> {
+    /// Save `angles` (in `set_servo_angles` order) as the preset `name`
+    ///
+    /// Nothing is moved. The store is left unchanged if the name is too long
+    /// or the table is full.
+    pub fn save_preset(
+        &mut self,
+        store: &mut dyn PresetStore,
+        name: &str,
+        angles: [u32; 4],
+    ) -> Result<()> {
+        let mut table = read_table(store)?;
+        table.insert(name, angles)?;
+        store.save(&table.to_bytes())?;
+        log_info!(target: self.log_target, "Saved preset '{}': {:?}", name, angles);
+        Ok(())
+    }
+
+    /// Every preset saved in `store`
+    pub fn load_presets(&self, store: &mut dyn PresetStore) -> Result<HashMap<String, [u32; 4]>> {
+        Ok(read_table(store)?.to_map())
+    }
+
+    /// Move all legs to the preset saved as `name`
+    pub fn goto_preset(&mut self, store: &mut dyn PresetStore, name: &str) -> Result<()> {
+        let [right_back, left_back, right_front, left_front] = read_table(store)?
+            .get(name)
+            .ok_or_else(|| PresetError::UnknownPreset(name.to_string()))?;
+        self.set_servo_angles(right_back, left_back, right_front, left_front)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::*;
+    use crate::{LEGS, angle_to_duty};
+
+    /// Store keeping the saved bytes in memory
+    #[derive(Default)]
+    struct MemoryStore(Option<Vec<u8>>);
+
+    impl PresetStore for MemoryStore {
+        fn load(&mut self) -> Result<Option<Vec<u8>>> {
+            Ok(self.0.clone())
+        }
+
+        fn save(&mut self, bytes: &[u8]) -> Result<()> {
+            self.0 = Some(bytes.to_vec());
+            Ok(())
+        }
+    }
+
+    /// Test that a table survives encoding, including a full one
+    #[test]
+    fn test_preset_table_round_trip() {
+        let mut table = PresetTable::new();
+        table.insert("alert", [90, 90, 45, 45]).unwrap();
+        table.insert("rest", [0, 0, 180, 180]).unwrap();
+        table.insert("", [1, 2, 3, 4]).unwrap();
+
+        let bytes = table.to_bytes();
+        assert_eq!(
+            &bytes[..8],
+            &[PRESET_FORMAT_VERSION, 3, 5, b'a', b'l', b'e', b'r', b't']
+        );
+        assert_eq!(PresetTable::from_bytes(&bytes), Ok(table));
+        assert_eq!(
+            PresetTable::from_bytes(&PresetTable::new().to_bytes()),
+            Ok(PresetTable::new())
+        );
+
+        let mut full = PresetTable::new();
+        for i in 0..MAX_PRESETS {
+            full.insert(&format!("{i:0>15}"), [180; 4]).unwrap();
+        }
+        let bytes = full.to_bytes();
+        assert_eq!(bytes.len(), MAX_PRESET_TABLE_LEN);
+        assert_eq!(PresetTable::from_bytes(&bytes), Ok(full));
+    }
+
+    /// Test the name and table size limits
+    #[test]
+    fn test_preset_table_limits() {
+        let mut table = PresetTable::new();
+        assert_eq!(
+            table.insert("a_very_long_name", [90; 4]),
+            Err(PresetError::NameTooLong { max: 15 })
+        );
+        for i in 0..MAX_PRESETS {
+            table.insert(&i.to_string(), [90; 4]).unwrap();
+        }
+        assert_eq!(
+            table.insert("play", [90; 4]),
+            Err(PresetError::TableFull { max: MAX_PRESETS })
+        );
+
+        // Replacing works in a full table, and angles are clamped
+        table.insert("3", [200, 0, 0, 0]).unwrap();
+        assert_eq!(table.get("3"), Some([180, 0, 0, 0]));
+        assert_eq!(table.len(), MAX_PRESETS);
+    }
+
+    /// Test that corrupt tables are rejected
+    #[test]
+    fn test_preset_table_rejects_corrupt() {
+        let mut table = PresetTable::new();
+        table.insert("play", [10, 20, 30, 40]).unwrap();
+        let bytes = table.to_bytes();
+
+        assert_eq!(
+            PresetTable::from_bytes(&[]),
+            Err(PresetError::InvalidLength)
+        );
+        assert_eq!(
+            PresetTable::from_bytes(&[9, 0]),
+            Err(PresetError::UnsupportedVersion(9))
+        );
+        assert_eq!(
+            PresetTable::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(PresetError::InvalidLength)
+        );
+        assert_eq!(
+            PresetTable::from_bytes(&[bytes.as_slice(), &[0]].concat()),
+            Err(PresetError::InvalidLength)
+        );
+
+        let mut bad = bytes.clone();
+        bad[3] = 0xff;
+        assert_eq!(
+            PresetTable::from_bytes(&bad),
+            Err(PresetError::InvalidName { preset: 0 })
+        );
+        let mut bad = bytes;
+        bad[8] = 181;
+        assert_eq!(
+            PresetTable::from_bytes(&bad),
+            Err(PresetError::InvalidAngle {
+                preset: 0,
+                degrees: 181
+            })
+        );
+    }
+
+    /// Test saving, listing and recalling presets through a store
+    #[test]
+    fn test_presets_through_store() {
+        let (mut controller, log) = mock_controller();
+        let mut store = MemoryStore::default();
+        assert!(controller.load_presets(&mut store).unwrap().is_empty());
+
+        controller
+            .save_preset(&mut store, "alert", [90, 90, 45, 45])
+            .unwrap();
+        controller
+            .save_preset(&mut store, "rest", [0, 0, 180, 180])
+            .unwrap();
+        let err = controller
+            .save_preset(&mut store, "much_too_long_name", [90; 4])
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<PresetError>(),
+            Some(&PresetError::NameTooLong { max: 15 })
+        );
+        assert!(log.duties().is_empty(), "saving moves nothing");
+
+        let presets = controller.load_presets(&mut store).unwrap();
+        assert_eq!(presets.len(), 2);
+        assert_eq!(presets["rest"], [0, 0, 180, 180]);
+
+        controller.goto_preset(&mut store, "alert").unwrap();
+        log.assert_duties(&LEGS.map(|leg| {
+            let angle = [90, 90, 45, 45][leg.index()];
+            (leg, angle_to_duty(angle, MOCK_MAX_DUTY))
+        }));
+        let err = controller.goto_preset(&mut store, "play").unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<PresetError>(),
+            Some(&PresetError::UnknownPreset("play".to_string()))
+        );
+    }
+}
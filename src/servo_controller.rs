@@ -7,28 +7,124 @@
 //!
 //! The module is organized into layers:
 //! - **Core Math Functions**: Platform-independent servo calculations
-//! - **Hardware Integration**: ESP32 LEDC driver integration
+//! - **Hardware Abstraction**: `PwmBackend`/`Delay` traits, implemented for ESP32 LEDC
 //! - **Robot Controller**: High-level movement coordination
 //! - **Movement Patterns**: Pre-defined behaviors (walking, waving, etc.)
 //!
 //! ## Usage
 //!
-//! ```rust
-//! use servo_controller::{setup_servos, demo_servo_movements};
+//! ```rust,ignore
+//! use cobot_rs::{setup_servos, MovementBuilder, Leg, Ms};
 //! use esp_idf_hal::peripherals::Peripherals;
 //!
 //! let mut servo_controller = setup_servos(Peripherals::take().unwrap())?;
 //! servo_controller.set_all_servos_angle(90)?; // Center all servos
-//! servo_controller.walk_forward(300)?;        // Execute walking pattern
+//! servo_controller.walk_forward(Ms(300))?;    // Execute walking pattern
+//!
+//! MovementBuilder::new()
+//!     .angle(Leg::RightFront, 45)
+//!     .delay(Ms(200))
+//!     .all(90)
+//!     .run(&mut servo_controller)?;
 //! ```
+//!
+//! ## Features
+//!
+//! - `esp32` (default): ESP32 LEDC hardware integration and `setup_servos`
+//! - `logging` (default): emit `log` records; without it every logging call
+//!   compiles to nothing and the `log` dependency is dropped
+
+// ================================================================================================
+// LOGGING
+// ================================================================================================
+
+// Crate-internal logging macros. With the `logging` feature disabled they expand
+// to dead code so the arguments are still type-checked (no unused-variable
+// warnings) but no formatting code is generated.
+//
+// Controller code passes `target: self.log_target` so every record carries the
+// target configured with `ServoController::with_log_target`.
+//
+// Left to hand formatting: rustfmt indents the nested `macro_rules!` bodies
+// further on every run.
+#[rustfmt::skip]
+macro_rules! define_log_macro {
+    ($name:ident, $level:ident, $d:tt) => {
+        #[cfg(feature = "logging")]
+        #[allow(unused_macros)]
+        macro_rules! $name {
+            ($d($d arg:tt)+) => { log::$level!($d($d arg)+) };
+        }
+
+        #[cfg(not(feature = "logging"))]
+        #[allow(unused_macros)]
+        macro_rules! $name {
+            (target: $d target:expr, $d($d arg:tt)+) => {
+                if false {
+                    let _ = $d target;
+                    let _ = format_args!($d($d arg)+);
+                }
+            };
+            ($d($d arg:tt)+) => {
+                if false {
+                    let _ = format_args!($d($d arg)+);
+                }
+            };
+        }
+    };
+}
+
+define_log_macro!(log_error, error, $);
+define_log_macro!(log_warn, warn, $);
+define_log_macro!(log_info, info, $);
+define_log_macro!(log_debug, debug, $);
 
 use anyhow::Result;
+#[cfg(feature = "esp32")]
 use esp_idf_hal::delay::FreeRtos;
+#[cfg(feature = "esp32")]
 use esp_idf_hal::ledc::{LedcDriver, LedcTimerDriver, config::TimerConfig};
+#[cfg(feature = "esp32")]
 use esp_idf_hal::peripherals::Peripherals;
+#[cfg(feature = "esp32")]
 use esp_idf_hal::units::Hertz;
+use std::collections::HashMap;
+use std::sync::Arc;
+#[cfg(any(feature = "std", test))]
 use std::sync::mpsc;
+#[cfg(any(feature = "std", test))]
 use std::thread;
+use std::time::{Duration, Instant};
+
+pub mod command_queue;
+#[cfg(any(feature = "config-toml", test))]
+pub mod config_toml;
+pub mod console;
+pub mod current;
+pub mod diagnostics;
+pub mod gait;
+pub mod gaits;
+pub mod presets;
+pub mod shared;
+pub mod support;
+#[cfg(test)]
+mod test_support;
+
+pub use command_queue::CommandQueue;
+#[cfg(any(feature = "config-toml", test))]
+pub use config_toml::ConfigTomlError;
+pub use console::{LineBuffer, ShellCommand, UndoHistory, handle_line, parse_command};
+pub use current::CurrentModel;
+pub use diagnostics::{FirmwareInfo, HealthFailure, HealthReport, MoveSample, PositionFeedback};
+pub use gait::{
+    FrameOverrun, Gait, GaitDecodeError, GaitRunner, GaitThresholds, GaitWarning, Pose,
+    SymmetryReport,
+};
+#[cfg(feature = "esp32")]
+pub use presets::EspPresetStore;
+pub use presets::{PresetError, PresetStore, PresetTable};
+pub use shared::SharedController;
+pub use support::SupportInfo;
 
 // ================================================================================================
 // CONSTANTS AND CONFIGURATION
@@ -42,6 +138,115 @@ pub const MIN_PULSE_US: u32 = 500; // Microseconds for 0 degrees (approx 0.5ms)
 pub const MAX_PULSE_US: u32 = 2500; // Microseconds for 180 degrees (approx 2.5ms)
 pub const PERIOD_US: u32 = 20000; // Microseconds for 50Hz (20ms)
 
+/// Maximum number of named poses stored by `define_pose`
+pub const MAX_POSES: usize = 16;
+
+/// Log target used by a controller unless overridden with `with_log_target`
+pub const DEFAULT_LOG_TARGET: &str = "cobot_rs";
+
+// ================================================================================================
+// ERRORS
+// ================================================================================================
+
+/// Errors reported by the servo controller
+///
+/// Returned wrapped in `anyhow::Error`; use `downcast_ref::<ServoError>()` to match on them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServoError {
+    /// A pose did not contain exactly one angle per leg
+    InvalidLength { expected: usize, actual: usize },
+    /// An angle was outside the valid 0-180° range
+    InvalidAngle { leg: Leg, degrees: f32 },
+    /// No pose with this name has been defined
+    UnknownPose(String),
+    /// `MAX_POSES` poses are already stored
+    PoseLimitReached { max: usize },
+    /// A leg's PWM channel reports a max duty of 0, so no angle can be driven
+    InvalidTimerConfig { leg: Leg },
+    /// Correction curve points were unsorted, out of range or too many
+    InvalidCurve,
+    /// A gait time scale factor was not positive and finite
+    InvalidTimeScale { factor: f32 },
+    /// A wiring map did not assign each channel to exactly one leg
+    InvalidWiring,
+    /// `kill` cut the drive and no command is accepted until `clear_halt`
+    Halted,
+    /// The thread calculating a servo's duty panicked
+    CalculationFailed { servo: String },
+    /// A `PositionFeedback` source gave no reading for a leg that needed one
+    NoFeedback { leg: Leg },
+    /// A reset was detected, so the tracked angles may not match the servos;
+    /// no command is accepted until `resync`
+    StateUnknown,
+    /// A move would end in or pass through a forbidden zone of the leg
+    ForbiddenZone {
+        leg: Leg,
+        from: u32,
+        to: u32,
+        zone: (u32, u32),
+    },
+}
+
+impl std::fmt::Display for ServoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServoError::InvalidLength { expected, actual } => {
+                write!(f, "expected {} angles, got {}", expected, actual)
+            }
+            ServoError::InvalidAngle { leg, degrees } => {
+                write!(f, "{} angle {}° is outside 0-180°", leg.name(), degrees)
+            }
+            ServoError::UnknownPose(name) => write!(f, "pose '{}' is not defined", name),
+            ServoError::PoseLimitReached { max } => {
+                write!(f, "cannot store more than {} poses", max)
+            }
+            ServoError::InvalidTimerConfig { leg } => {
+                write!(f, "{} reports a max duty of 0; check the timer", leg.name())
+            }
+            ServoError::InvalidCurve => write!(
+                f,
+                "correction curve needs at most {} points with increasing inputs within 0-180°",
+                MAX_CURVE_POINTS
+            ),
+            ServoError::InvalidTimeScale { factor } => {
+                write!(f, "time scale factor {} must be positive", factor)
+            }
+            ServoError::InvalidWiring => {
+                write!(f, "wiring must assign each channel to exactly one leg")
+            }
+            ServoError::Halted => write!(f, "controller is halted; call clear_halt to resume"),
+            ServoError::CalculationFailed { servo } => {
+                write!(f, "duty calculation for {} failed", servo)
+            }
+            ServoError::NoFeedback { leg } => {
+                write!(f, "{} gave no position reading", leg.name())
+            }
+            ServoError::StateUnknown => {
+                write!(
+                    f,
+                    "servo state unknown after a reset; call resync to resume"
+                )
+            }
+            ServoError::ForbiddenZone {
+                leg,
+                from,
+                to,
+                zone,
+            } => write!(
+                f,
+                "{} cannot move from {}° to {}° through forbidden zone {}-{}°",
+                leg.name(),
+                from,
+                to,
+                zone.0,
+                zone.1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ServoError {}
+
 // ================================================================================================
 // CORE MATHEMATICAL FUNCTIONS
 // ================================================================================================
@@ -60,21 +265,103 @@ pub const PERIOD_US: u32 = 20000; // Microseconds for 50Hz (20ms)
 ///
 /// # Example
 /// ```
-/// let duty = angle_to_duty(90, 1024); // 90° on 10-bit PWM = 76
+/// use cobot_rs::angle_to_duty;
+///
+/// let duty = angle_to_duty(180, 1024); // 180° on 10-bit PWM = 128
+/// assert_eq!(duty, 128);
 /// ```
 pub fn angle_to_duty(angle: u32, max_duty: u32) -> u32 {
+    angle_to_duty_with_period(angle, max_duty, PERIOD_US)
+}
+
+/// Convert angle to duty cycle for a PWM period of `period_us`
+///
+/// Same as `angle_to_duty`, for timers that don't run at exactly
+/// `FREQUENCY_HZ`.
+pub fn angle_to_duty_with_period(angle: u32, max_duty: u32, period_us: u32) -> u32 {
     // Clamp angle to valid range
-    let angle = angle.min(180);
+    expected_duty(
+        angle.min(180),
+        MIN_PULSE_US,
+        MAX_PULSE_US,
+        period_us,
+        max_duty,
+    )
+}
 
-    // Linear interpolation: angle → pulse width
-    let pulse_range = MAX_PULSE_US - MIN_PULSE_US;
-    let pulse_us = MIN_PULSE_US + ((angle * pulse_range) / 180);
+/// Duty cycle value for an angle under the complete servo model
+///
+/// The angle maps linearly onto `min_pulse_us..=max_pulse_us` (0° to 180°),
+/// and the pulse is expressed as a fraction of `period_us` scaled to
+/// `max_duty`, rounded down once at the end. Angles past 180° are
+/// extrapolated, not clamped; the result is always capped at `max_duty`.
+///
+/// With the `fpu` feature the same model is computed in `f32` and rounded to
+/// the nearest duty instead, for chips with a hardware FPU. The `fpu` duty is
+/// never lower and at most 1 LSB higher: 90° on 10-bit PWM is 76.8, giving 76
+/// by default and 77 with `fpu`.
+///
+/// Every duty the controller writes goes through this function.
+///
+/// # Example
+/// ```
+/// use cobot_rs::expected_duty;
+///
+/// // 180° with a 500-2500 µs servo at 50 Hz on 14-bit PWM: 2500 µs of 20 ms
+/// assert_eq!(expected_duty(180, 500, 2500, 20_000, 16_384), 2048);
+/// ```
+pub fn expected_duty(
+    angle: u32,
+    min_pulse_us: u32,
+    max_pulse_us: u32,
+    period_us: u32,
+    max_duty: u32,
+) -> u32 {
+    #[cfg(feature = "fpu")]
+    return expected_duty_f32(angle, min_pulse_us, max_pulse_us, period_us, max_duty);
+    #[cfg(not(feature = "fpu"))]
+    return expected_duty_int(angle, min_pulse_us, max_pulse_us, period_us, max_duty);
+}
+
+/// `expected_duty` in integer arithmetic, rounding down once at the end
+#[cfg(any(not(feature = "fpu"), test))]
+fn expected_duty_int(
+    angle: u32,
+    min_pulse_us: u32,
+    max_pulse_us: u32,
+    period_us: u32,
+    max_duty: u32,
+) -> u32 {
+    // Linear interpolation: angle → pulse width in 1/180 µs, so the fraction
+    // of a microsecond is kept (allows a reversed range)
+    let (min, max) = (i64::from(min_pulse_us), i64::from(max_pulse_us));
+    let pulse_180ths = (min * 180 + i64::from(angle) * (max - min)).max(0) as u64;
 
     // Convert pulse width to duty cycle value
-    let duty = (pulse_us * max_duty) / PERIOD_US;
+    let duty = pulse_180ths * u64::from(max_duty) / (180 * u64::from(period_us.max(1)));
 
     // Safety clamp
-    duty.min(max_duty)
+    duty.min(u64::from(max_duty)) as u32
+}
+
+/// `expected_duty` in `f32`, rounding to the nearest duty at the end
+#[cfg(any(feature = "fpu", test))]
+fn expected_duty_f32(
+    angle: u32,
+    min_pulse_us: u32,
+    max_pulse_us: u32,
+    period_us: u32,
+    max_duty: u32,
+) -> u32 {
+    let (min, max) = (min_pulse_us as f32, max_pulse_us as f32);
+    let pulse_us = (min + angle as f32 * (max - min) / 180.0).max(0.0);
+    let duty = pulse_us * max_duty as f32 / period_us.max(1) as f32;
+    duty.round().min(max_duty as f32) as u32
+}
+
+/// PWM period in microseconds for a timer frequency (0 Hz is treated as 1 Hz)
+pub fn period_us(frequency_hz: u32) -> u32 {
+    1_000_000 / frequency_hz.max(1)
 }
 
 /// Convert duty cycle value back to angle (for verification/debugging)
@@ -95,6 +382,20 @@ pub fn duty_to_angle(duty: u32, max_duty: u32) -> u32 {
     }
 }
 
+/// Convert radians to the 0-180° servo range, rounding to the nearest degree
+///
+/// Inputs outside 0..=π (including negative values) are clamped to the arc;
+/// NaN maps to 0°.
+pub fn radians_to_degrees(radians: f32) -> u32 {
+    let radians = radians.clamp(0.0, std::f32::consts::PI);
+    (radians.to_degrees().round() as u32).min(180)
+}
+
+/// Convert a servo angle in degrees (clamped to 180°) to radians
+pub fn degrees_to_radians(degrees: u32) -> f32 {
+    (degrees.min(180) as f32).to_radians()
+}
+
 /// Calculate expected pulse width for a given angle
 pub fn angle_to_pulse_width(angle: u32) -> u32 {
     let angle = angle.min(180);
@@ -103,471 +404,6369 @@ pub fn angle_to_pulse_width(angle: u32) -> u32 {
 }
 
 // ================================================================================================
-// SERVO OPERATION DATA STRUCTURE
+// MOTION PROFILES
 // ================================================================================================
 
-/// Servo operation for threaded execution
-#[derive(Debug, Clone)]
-pub struct ServoOperation {
-    pub angle: u32,
-    pub max_duty: u32,
-    pub servo_name: String,
-}
+/// Interval between position updates during smooth moves (one 50 Hz PWM period)
+pub const SMOOTH_STEP_MS: u32 = 20;
 
-// ================================================================================================
-// ROBOT CONTROLLER
-// ================================================================================================
+/// Pause between sub-batches when `max_concurrent_moves` splits a batch write
+pub const WAVE_DELAY_MS: u32 = 20;
 
-/// 4-legged robot servo controller with parallel execution capabilities
-pub struct ServoController<'a> {
-    right_back_leg: LedcDriver<'a>,
-    left_back_leg: LedcDriver<'a>,
-    right_front_leg: LedcDriver<'a>,
-    left_front_leg: LedcDriver<'a>,
+/// Longest an open-ended movement runs unless changed with
+/// `set_max_gait_duration`
+pub const DEFAULT_MAX_GAIT_DURATION: Duration = Duration::from_secs(60);
+
+/// Speed and acceleration limits for smooth moves
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MotionLimits {
+    /// Maximum angular speed in degrees per second
+    pub max_speed_dps: f32,
+    /// Maximum angular acceleration in degrees per second squared
+    pub max_accel_dps2: f32,
 }
 
-impl<'a> ServoController<'a> {
-    /// Create a new ServoController with the given LEDC drivers
-    pub fn new(
-        right_back_leg: LedcDriver<'a>,
-        left_back_leg: LedcDriver<'a>,
-        right_front_leg: LedcDriver<'a>,
-        left_front_leg: LedcDriver<'a>,
-    ) -> Self {
+impl Default for MotionLimits {
+    fn default() -> Self {
         Self {
-            right_back_leg,
-            left_back_leg,
-            right_front_leg,
-            left_front_leg,
+            max_speed_dps: 180.0,
+            max_accel_dps2: 720.0,
         }
     }
+}
 
-    /// Set all servos to the same angle using parallel calculation
-    ///
-    /// This function calculates duty values in parallel threads, then applies them
-    /// sequentially to avoid hardware conflicts.
-    pub fn set_all_servos_angle(&mut self, angle: u32) -> Result<()> {
-        let (tx, rx) = mpsc::channel();
-        let mut handles = vec![];
+/// Timing for a single call, overriding the controller's settings
+///
+/// Taken by the `*_with` variants of the smooth-move and gait methods. Fields
+/// left `None` use the configured value; the stored settings are never
+/// changed, so there is nothing to restore afterward.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Timing {
+    /// Limits for smooth moves instead of `set_motion_limits`
+    pub motion_limits: Option<MotionLimits>,
+    /// Wait after each write instead of `set_settle_time_ms`
+    pub settle_time_ms: Option<u32>,
+    /// Blend into a gait's first frame instead of `set_gait_lead_in_ms`
+    pub lead_in_ms: Option<u32>,
+}
 
-        // Prepare servo operations
-        let operations = vec![
-            ServoOperation {
-                angle,
-                max_duty: self.right_back_leg.get_max_duty(),
-                servo_name: "right_back_leg".to_string(),
-            },
-            ServoOperation {
-                angle,
-                max_duty: self.left_back_leg.get_max_duty(),
-                servo_name: "left_back_leg".to_string(),
-            },
-            ServoOperation {
-                angle,
-                max_duty: self.right_front_leg.get_max_duty(),
-                servo_name: "right_front_leg".to_string(),
-            },
-            ServoOperation {
-                angle,
-                max_duty: self.left_front_leg.get_max_duty(),
-                servo_name: "left_front_leg".to_string(),
-            },
-        ];
+/// A time in whole milliseconds, as taken by the gait methods
+///
+/// `walk_forward`, `wave`, the other gait methods and the `gaits`
+/// constructors take `impl Into<Ms>`, so
+/// the unit is spelled out at the call site: `Ms(300)` or
+/// `Duration::from_millis(300)`, never a bare number that could be read as
+/// microseconds. Durations are truncated to whole milliseconds and saturate
+/// at `u32::MAX`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Ms(pub u32);
 
-        // Spawn threads to calculate duty values
-        for op in operations {
-            let tx_clone = tx.clone();
-            let handle = thread::spawn(move || {
-                let duty = angle_to_duty(op.angle, op.max_duty);
-                log::debug!(
-                    "Calculated {} duty: {} for angle: {}",
-                    op.servo_name,
-                    duty,
-                    op.angle
-                );
-                tx_clone.send((op.servo_name, duty)).unwrap();
-            });
-            handles.push(handle);
-        }
+impl From<Duration> for Ms {
+    fn from(duration: Duration) -> Self {
+        Ms(duration.as_millis().min(u128::from(u32::MAX)) as u32)
+    }
+}
 
-        // Drop the original sender
-        drop(tx);
+impl From<Ms> for Duration {
+    fn from(ms: Ms) -> Self {
+        Duration::from_millis(u64::from(ms.0))
+    }
+}
 
-        // Collect results from threads
-        let mut duties = std::collections::HashMap::new();
-        for received in rx {
-            duties.insert(received.0, received.1);
-        }
+/// Easing curve for a timed move, mapping progress 0..=1 onto 0..=1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Easing {
+    /// Constant speed
+    #[default]
+    Linear,
+    /// Start slow, end fast (quadratic)
+    EaseIn,
+    /// Start fast, end slow (quadratic)
+    EaseOut,
+    /// Start and end slow (smoothstep)
+    EaseInOut,
+}
 
-        // Wait for all threads to complete
-        for handle in handles {
-            handle.join().unwrap();
+impl Easing {
+    /// Eased progress for linear progress `t` (clamped to 0..=1)
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
         }
+    }
+}
 
-        // Apply calculated duties to servos sequentially (hardware operations)
-        self.right_back_leg.set_duty(duties["right_back_leg"])?;
-        self.left_back_leg.set_duty(duties["left_back_leg"])?;
-        self.right_front_leg.set_duty(duties["right_front_leg"])?;
-        self.left_front_leg.set_duty(duties["left_front_leg"])?;
+/// An eased move of one leg that can be sampled at any time
+///
+/// Holds no buffers, so external code can keep one per leg and drive the
+/// servos from its own clock: `sample` the elapsed time and write the angle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MoveInterpolator {
+    from: u32,
+    to: u32,
+    duration_ms: u32,
+    easing: Easing,
+}
 
-        log::info!(
-            "All servos set to {} degrees using parallel calculation",
-            angle
-        );
-        Ok(())
+impl MoveInterpolator {
+    /// A move from `from` to `to` (both clamped to 0-180) over `duration_ms`
+    pub fn new(from: u32, to: u32, duration_ms: u32, easing: Easing) -> Self {
+        Self {
+            from: from.min(180),
+            to: to.min(180),
+            duration_ms,
+            easing,
+        }
     }
 
-    /// Set individual servo angles using parallel calculation
-    pub fn set_servo_angles(
-        &mut self,
-        right_back: u32,
-        left_back: u32,
-        right_front: u32,
-        left_front: u32,
-    ) -> Result<()> {
-        let (tx, rx) = mpsc::channel();
-        let mut handles = vec![];
-
-        // Prepare servo operations
-        let operations = vec![
-            ServoOperation {
-                angle: right_back,
-                max_duty: self.right_back_leg.get_max_duty(),
-                servo_name: "right_back_leg".to_string(),
-            },
-            ServoOperation {
-                angle: left_back,
-                max_duty: self.left_back_leg.get_max_duty(),
-                servo_name: "left_back_leg".to_string(),
-            },
-            ServoOperation {
-                angle: right_front,
-                max_duty: self.right_front_leg.get_max_duty(),
-                servo_name: "right_front_leg".to_string(),
-            },
-            ServoOperation {
-                angle: left_front,
-                max_duty: self.left_front_leg.get_max_duty(),
-                servo_name: "left_front_leg".to_string(),
-            },
-        ];
+    /// Length of the move
+    pub fn duration_ms(&self) -> u32 {
+        self.duration_ms
+    }
 
-        // Spawn threads to calculate duty values
-        for op in operations {
-            let tx_clone = tx.clone();
-            let handle = thread::spawn(move || {
-                let duty = angle_to_duty(op.angle, op.max_duty);
-                tx_clone.send((op.servo_name, duty)).unwrap();
-            });
-            handles.push(handle);
+    /// Angle `elapsed_ms` into the move, rounded; `to` from the end onward
+    pub fn sample(&self, elapsed_ms: u32) -> u32 {
+        if elapsed_ms >= self.duration_ms {
+            return self.to;
         }
+        self.at(elapsed_ms as f32 / self.duration_ms as f32)
+    }
 
-        drop(tx);
+    /// Angle at linear progress `t` (clamped to 0..=1), rounded
+    pub fn at(&self, t: f32) -> u32 {
+        let (from, to) = (self.from as f32, self.to as f32);
+        (from + (to - from) * self.easing.apply(t)).round() as u32
+    }
+}
 
-        let mut duties = std::collections::HashMap::new();
-        for received in rx {
-            duties.insert(received.0, received.1);
-        }
+/// Trapezoidal velocity profile for a single-leg move
+///
+/// Speed ramps up at `max_accel` until it reaches `max_speed`, cruises, then
+/// ramps down to zero at the target. Short moves that never reach `max_speed`
+/// degrade to a triangular profile.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrapezoidalProfile {
+    from: f32,
+    to: f32,
+    accel: f32,
+    peak_speed: f32,
+    accel_time_s: f32,
+    cruise_time_s: f32,
+}
+
+impl TrapezoidalProfile {
+    /// Plan a move between two angles (clamped to 0-180)
+    ///
+    /// Non-positive limits are treated as "unlimited" and produce an
+    /// instantaneous move.
+    pub fn new(from: u32, to: u32, max_speed_dps: f32, max_accel_dps2: f32) -> Self {
+        let from = from.min(180) as f32;
+        let to = to.min(180) as f32;
+        let distance = (to - from).abs();
 
-        for handle in handles {
-            handle.join().unwrap();
+        if distance == 0.0 || max_speed_dps <= 0.0 || max_accel_dps2 <= 0.0 {
+            return Self {
+                from,
+                to,
+                accel: 0.0,
+                peak_speed: 0.0,
+                accel_time_s: 0.0,
+                cruise_time_s: 0.0,
+            };
         }
 
-        // Apply calculated duties to servos
-        self.right_back_leg.set_duty(duties["right_back_leg"])?;
-        self.left_back_leg.set_duty(duties["left_back_leg"])?;
-        self.right_front_leg.set_duty(duties["right_front_leg"])?;
-        self.left_front_leg.set_duty(duties["left_front_leg"])?;
+        // Distance covered while ramping up to max speed and back down
+        let ramp_distance = max_speed_dps * max_speed_dps / max_accel_dps2;
+        let (peak_speed, cruise_time_s) = if distance >= ramp_distance {
+            (max_speed_dps, (distance - ramp_distance) / max_speed_dps)
+        } else {
+            // Triangular profile: peak speed is never reached
+            ((distance * max_accel_dps2).sqrt(), 0.0)
+        };
+
+        Self {
+            from,
+            to,
+            accel: max_accel_dps2,
+            peak_speed,
+            accel_time_s: peak_speed / max_accel_dps2,
+            cruise_time_s,
+        }
+    }
 
-        log::debug!("Individual servos set using parallel calculation");
-        Ok(())
+    /// Total move time in seconds
+    pub fn duration_s(&self) -> f32 {
+        2.0 * self.accel_time_s + self.cruise_time_s
     }
 
-    /// Set right side servos to specific angles
-    pub fn set_right_servos(&mut self, back_angle: u32, front_angle: u32) -> Result<()> {
-        self.right_back_leg.set_duty(angle_to_duty(
-            back_angle,
-            self.right_back_leg.get_max_duty(),
-        ))?;
-        self.right_front_leg.set_duty(angle_to_duty(
-            front_angle,
-            self.right_front_leg.get_max_duty(),
-        ))?;
-        Ok(())
+    /// Total move time rounded up to whole milliseconds
+    pub fn duration_ms(&self) -> u32 {
+        (self.duration_s() * 1000.0).ceil() as u32
     }
 
-    /// Set left side servos to specific angles
-    pub fn set_left_servos(&mut self, back_angle: u32, front_angle: u32) -> Result<()> {
-        self.left_back_leg
-            .set_duty(angle_to_duty(back_angle, self.left_back_leg.get_max_duty()))?;
-        self.left_front_leg.set_duty(angle_to_duty(
-            front_angle,
-            self.left_front_leg.get_max_duty(),
-        ))?;
-        Ok(())
+    /// Unsigned speed in degrees per second at time `t_s`
+    pub fn speed_at(&self, t_s: f32) -> f32 {
+        let t = t_s.clamp(0.0, self.duration_s());
+        let decel_start = self.accel_time_s + self.cruise_time_s;
+
+        if t < self.accel_time_s {
+            self.accel * t
+        } else if t <= decel_start {
+            self.peak_speed
+        } else {
+            (self.accel * (self.duration_s() - t)).max(0.0)
+        }
     }
 
-    /// Center all servos to 90 degrees
-    pub fn center_all_servos(&mut self) -> Result<()> {
-        self.set_all_servos_angle(90)
+    /// Exact angle in degrees at time `t_s`
+    pub fn position_at(&self, t_s: f32) -> f32 {
+        let t = t_s.clamp(0.0, self.duration_s());
+        let decel_start = self.accel_time_s + self.cruise_time_s;
+        let accel_distance = 0.5 * self.accel * self.accel_time_s * self.accel_time_s;
+
+        let travelled = if t < self.accel_time_s {
+            0.5 * self.accel * t * t
+        } else if t <= decel_start {
+            accel_distance + self.peak_speed * (t - self.accel_time_s)
+        } else {
+            let remaining = self.duration_s() - t;
+            (self.to - self.from).abs() - 0.5 * self.accel * remaining * remaining
+        };
+
+        if self.to >= self.from {
+            self.from + travelled
+        } else {
+            self.from - travelled
+        }
     }
 
-    /// Get max duty values for debugging
-    pub fn log_max_duties(&self) {
-        log::info!(
-            "Max duty values - right_back_leg: {}, left_back_leg: {}, right_front_leg: {}, left_front_leg: {}",
-            self.right_back_leg.get_max_duty(),
-            self.left_back_leg.get_max_duty(),
-            self.right_front_leg.get_max_duty(),
-            self.left_front_leg.get_max_duty()
-        );
+    /// Angles to command every `step_ms`, ending exactly at the target
+    ///
+    /// The starting angle is not included; an empty profile yields just the target.
+    pub fn steps(&self, step_ms: u32) -> Vec<u32> {
+        let step_s = step_ms.max(1) as f32 / 1000.0;
+        let count = (self.duration_s() / step_s).ceil().max(1.0) as u32;
+
+        (1..=count)
+            .map(|i| self.position_at(i as f32 * step_s).round() as u32)
+            .collect()
     }
+}
 
-    /// Perform a walking motion pattern with parallel servo control
-    pub fn walk_forward(&mut self, delay_ms: u32) -> Result<()> {
-        log::info!("Starting walk forward pattern with parallel servo control");
+// ================================================================================================
+// ANGLE CORRECTION
+// ================================================================================================
+
+/// Per-leg mapping from a commanded (logical) angle to the servo pulse
+///
+/// A commanded angle is mirrored if `inverted`, offset by `trim_deg` and
+/// clamped to 0-180°, then mapped linearly onto `min_pulse_us..=max_pulse_us`.
+/// `center_deg` is the logical angle used by `center_all_servos`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LegCalibration {
+    /// Pulse width at 0°
+    pub min_pulse_us: u32,
+    /// Pulse width at 180°
+    pub max_pulse_us: u32,
+    /// Offset added to every commanded angle
+    pub trim_deg: i32,
+    /// Mirror commanded angles (`180 - angle`) for servos mounted reversed
+    pub inverted: bool,
+    /// Logical angle the leg returns to when centered
+    pub center_deg: u32,
+}
 
-        // Step 1: Lift right legs
-        self.set_servo_angles(45, 90, 45, 90)?;
-        FreeRtos::delay_ms(delay_ms);
+impl Default for LegCalibration {
+    fn default() -> Self {
+        Self {
+            min_pulse_us: MIN_PULSE_US,
+            max_pulse_us: MAX_PULSE_US,
+            trim_deg: 0,
+            inverted: false,
+            center_deg: 90,
+        }
+    }
+}
 
-        // Step 2: Move right legs forward
-        self.set_servo_angles(135, 90, 135, 90)?;
-        FreeRtos::delay_ms(delay_ms);
+impl LegCalibration {
+    /// Physical servo angle for a logical angle (inversion and trim applied)
+    pub fn physical_angle(&self, angle: u32) -> u32 {
+        let angle = angle.min(180);
+        let angle = if self.inverted { 180 - angle } else { angle };
+        (angle as i32 + self.trim_deg).clamp(0, 180) as u32
+    }
 
-        // Step 3: Put right legs down, lift left legs
-        self.set_servo_angles(90, 45, 90, 45)?;
-        FreeRtos::delay_ms(delay_ms);
+    /// Like `physical_angle` but without the 180° limit (still at least 0°)
+    pub fn physical_angle_unclamped(&self, angle: u32) -> u32 {
+        let angle = i64::from(angle);
+        let angle = if self.inverted { 180 - angle } else { angle };
+        (angle + i64::from(self.trim_deg)).max(0) as u32
+    }
 
-        // Step 4: Move left legs forward
-        self.set_servo_angles(90, 135, 90, 135)?;
-        FreeRtos::delay_ms(delay_ms);
+    /// Logical angle for a physical angle: `physical_angle` undone
+    ///
+    /// Physical angles that trim pushed past an end stop come back as the
+    /// nearest logical angle that reaches them.
+    pub fn logical_angle(&self, physical_angle: u32) -> u32 {
+        let angle = (physical_angle.min(180) as i32 - self.trim_deg).clamp(0, 180) as u32;
+        if self.inverted { 180 - angle } else { angle }
+    }
 
-        // Step 5: Return to center
-        self.center_all_servos()?;
-        FreeRtos::delay_ms(delay_ms);
+    /// Physical angle (0-180°, rounded) whose pulse produces `duty`
+    ///
+    /// The inverse of `duty`; duties outside the pulse range clamp to 0° or
+    /// 180°. A `max_duty` of 0 or an empty pulse range gives 0°.
+    pub fn angle_from_duty(&self, duty: u32, max_duty: u32, period_us: u32) -> u32 {
+        if max_duty == 0 || self.min_pulse_us == self.max_pulse_us {
+            return 0;
+        }
+        let pulse_us = duty as f32 * period_us.max(1) as f32 / max_duty as f32;
+        let (min, max) = (self.min_pulse_us as f32, self.max_pulse_us as f32);
+        ((pulse_us - min) * 180.0 / (max - min))
+            .round()
+            .clamp(0.0, 180.0) as u32
+    }
 
-        Ok(())
+    /// Pulse width for a physical angle (extrapolated past 180°)
+    pub fn pulse_us(&self, physical_angle: u32) -> u32 {
+        let angle = i64::from(physical_angle);
+        let (min, max) = (i64::from(self.min_pulse_us), i64::from(self.max_pulse_us));
+        (min + angle * (max - min) / 180).max(0) as u32
     }
 
-    /// Perform a simple wave motion with threaded calculation
-    pub fn wave(&mut self, delay_ms: u32) -> Result<()> {
-        log::info!("Starting wave motion with parallel calculation");
+    /// Duty for a physical angle at the given max duty and PWM period
+    pub fn duty(&self, physical_angle: u32, max_duty: u32, period_us: u32) -> u32 {
+        expected_duty(
+            physical_angle,
+            self.min_pulse_us,
+            self.max_pulse_us,
+            period_us,
+            max_duty,
+        )
+    }
+}
 
-        // Wave with front right leg - forward sweep
-        for angle in (0..=180).step_by(10) {
-            let max_duty = self.right_front_leg.get_max_duty();
+/// Maximum number of points in a `CorrectionCurve`
+pub const MAX_CURVE_POINTS: usize = 8;
 
-            // Calculate duty in a separate thread
-            let handle = thread::spawn(move || angle_to_duty(angle, max_duty));
-            let duty = handle.join().unwrap();
+/// Piecewise-linear remapping of commanded angles, to linearize a servo
+///
+/// The curve passes through each `(input_deg, output_deg)` point and through
+/// (0, 0) and (180, 180) unless those inputs are given explicitly; angles in
+/// between are interpolated. The default curve has no points and is the
+/// identity.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CorrectionCurve {
+    points: Vec<(u32, u32)>,
+}
 
-            self.right_front_leg.set_duty(duty)?;
-            FreeRtos::delay_ms(delay_ms);
+impl CorrectionCurve {
+    /// Build a curve from points sorted by strictly increasing input
+    pub fn new(points: &[(u32, u32)]) -> Result<Self> {
+        let sorted = points.windows(2).all(|pair| pair[0].0 < pair[1].0);
+        let in_range = points
+            .iter()
+            .all(|&(input, output)| input <= 180 && output <= 180);
+        if points.len() > MAX_CURVE_POINTS || !sorted || !in_range {
+            return Err(ServoError::InvalidCurve.into());
         }
+        Ok(Self {
+            points: points.to_vec(),
+        })
+    }
+
+    /// Defined points, without the implicit end points
+    pub fn points(&self) -> &[(u32, u32)] {
+        &self.points
+    }
+
+    /// Defined points with the implicit end points added
+    fn full_curve(&self) -> Vec<(u32, u32)> {
+        let start = match self.points.first() {
+            Some(&(0, _)) => None,
+            _ => Some((0, 0)),
+        };
+        let end = match self.points.last() {
+            Some(&(180, _)) => None,
+            _ => Some((180, 180)),
+        };
+        start
+            .into_iter()
+            .chain(self.points.iter().copied())
+            .chain(end)
+            .collect()
+    }
 
-        // Wave with front right leg - reverse sweep
-        for angle in (0..=180).rev().step_by(10) {
-            let max_duty = self.right_front_leg.get_max_duty();
+    /// Remap an angle (clamped to 180°) through the curve
+    pub fn apply(&self, angle: u32) -> u32 {
+        let angle = angle.min(180);
+        let curve = self.full_curve();
 
-            // Calculate duty in a separate thread
-            let handle = thread::spawn(move || angle_to_duty(angle, max_duty));
-            let duty = handle.join().unwrap();
+        for pair in curve.windows(2) {
+            let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+            if angle <= x1 {
+                let offset = (angle - x0) as i64 * (y1 as i64 - y0 as i64) / (x1 - x0) as i64;
+                return (y0 as i64 + offset) as u32;
+            }
+        }
+        angle
+    }
 
-            self.right_front_leg.set_duty(duty)?;
-            FreeRtos::delay_ms(delay_ms);
+    /// Input angle that `apply` maps to `output` (rounded)
+    ///
+    /// Exact for monotonic curves; otherwise the smallest matching input is
+    /// returned. An output the curve never reaches maps to the end point with
+    /// the nearest output.
+    pub fn invert(&self, output: u32) -> u32 {
+        let curve = self.full_curve();
+        for pair in curve.windows(2) {
+            let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+            if output.clamp(y0.min(y1), y0.max(y1)) != output {
+                continue;
+            }
+            if y0 == y1 {
+                return x0;
+            }
+            let fraction = (output as f32 - y0 as f32) / (y1 as f32 - y0 as f32);
+            return (x0 as f32 + fraction * (x1 - x0) as f32).round() as u32;
         }
 
-        // Return to center
-        self.center_all_servos()?;
-        Ok(())
+        curve
+            .iter()
+            .min_by_key(|&&(_, y)| y.abs_diff(output))
+            .map_or(output, |&(x, _)| x)
     }
 }
 
 // ================================================================================================
-// HARDWARE SETUP FUNCTIONS
+// HARDWARE ABSTRACTION
 // ================================================================================================
 
-/// Set up servo motors and return a ServoController
-pub fn setup_servos(peripherals: Peripherals) -> Result<ServoController<'static>> {
-    log::info!("Setting up servo motors with parallel control capability");
+/// A single PWM output channel driving one servo
+///
+/// Implemented for the ESP32 `LedcDriver`; tests provide a mock so the controller
+/// logic can run off-device.
+pub trait PwmBackend {
+    /// Maximum duty value for the channel's timer resolution
+    fn get_max_duty(&self) -> u32;
 
-    // LEDC Timer configuration
-    let timer_config = TimerConfig::default()
-        .frequency(Hertz(FREQUENCY_HZ).into())
-        .resolution(esp_idf_hal::ledc::Resolution::Bits10);
+    /// Write a new duty value to the channel
+    fn set_duty(&mut self, duty: u32) -> Result<()>;
 
-    let timer = LedcTimerDriver::new(peripherals.ledc.timer0, &timer_config)?;
+    /// Duty value currently configured in the channel's hardware
+    fn get_duty(&self) -> u32;
 
-    // Create LEDC drivers for each servo
-    let right_back_leg =
-        LedcDriver::new(peripherals.ledc.channel0, &timer, peripherals.pins.gpio23)?;
+    /// Stop generating pulses so the servo relaxes
+    fn disable(&mut self) -> Result<()>;
 
-    let left_back_leg =
-        LedcDriver::new(peripherals.ledc.channel1, &timer, peripherals.pins.gpio22)?;
+    /// Resume generating pulses at the last written duty
+    fn enable(&mut self) -> Result<()>;
+}
 
-    let right_front_leg =
-        LedcDriver::new(peripherals.ledc.channel2, &timer, peripherals.pins.gpio19)?;
+#[cfg(feature = "esp32")]
+impl PwmBackend for LedcDriver<'_> {
+    fn get_max_duty(&self) -> u32 {
+        LedcDriver::get_max_duty(self)
+    }
 
-    let left_front_leg =
-        LedcDriver::new(peripherals.ledc.channel3, &timer, peripherals.pins.gpio18)?;
+    fn set_duty(&mut self, duty: u32) -> Result<()> {
+        LedcDriver::set_duty(self, duty)?;
+        Ok(())
+    }
 
-    let servo_controller = ServoController::new(
-        right_back_leg,
-        left_back_leg,
-        right_front_leg,
-        left_front_leg,
-    );
+    fn get_duty(&self) -> u32 {
+        LedcDriver::get_duty(self)
+    }
 
-    servo_controller.log_max_duties();
-    log::info!("Servo controller initialized with parallel execution support");
+    fn disable(&mut self) -> Result<()> {
+        LedcDriver::disable(self)?;
+        Ok(())
+    }
 
-    Ok(servo_controller)
+    fn enable(&mut self) -> Result<()> {
+        LedcDriver::enable(self)?;
+        Ok(())
+    }
 }
 
-/// Demonstrate servo movements with parallel control
-pub fn demo_servo_movements(servo_controller: &mut ServoController) -> Result<()> {
-    log::info!("Starting servo demonstration with parallel control...");
+/// A leg's channel, or an empty slot where its driver failed to initialize
+///
+/// Used by `setup_with_factory` and `setup_with_legs` for controllers with
+/// fewer than four servos. Legs
+/// with a `Missing` channel are disabled, so setters skip them; driving one
+/// anyway returns an error.
+#[derive(Debug)]
+pub enum OptionalChannel<P> {
+    Ready(P),
+    Missing,
+}
 
-    // Set all servos to 180 degrees
-    servo_controller.set_all_servos_angle(180)?;
-    log::info!("All servos set to 180 degrees (parallel execution)");
-    FreeRtos::delay_ms(1000);
+impl<P: PwmBackend> OptionalChannel<P> {
+    fn ready(&mut self) -> Result<&mut P> {
+        match self {
+            OptionalChannel::Ready(channel) => Ok(channel),
+            OptionalChannel::Missing => anyhow::bail!("channel was not initialized"),
+        }
+    }
+}
 
-    // Set all servos to 90 degrees
-    servo_controller.set_all_servos_angle(90)?;
-    log::info!("All servos set to 90 degrees (parallel execution)");
-    FreeRtos::delay_ms(1000);
+impl<P: PwmBackend> PwmBackend for OptionalChannel<P> {
+    fn get_max_duty(&self) -> u32 {
+        match self {
+            OptionalChannel::Ready(channel) => channel.get_max_duty(),
+            OptionalChannel::Missing => 0,
+        }
+    }
 
-    // Set all servos to 0 degrees
-    servo_controller.set_all_servos_angle(0)?;
-    log::info!("All servos set to 0 degrees (parallel execution)");
-    FreeRtos::delay_ms(1000);
+    fn set_duty(&mut self, duty: u32) -> Result<()> {
+        self.ready()?.set_duty(duty)
+    }
 
-    // Test individual leg control
-    log::info!("Testing individual leg movements with parallel calculation...");
-    servo_controller.set_servo_angles(45, 135, 135, 45)?;
-    log::info!("Diagonal movement pattern (parallel execution)");
-    FreeRtos::delay_ms(1000);
+    fn get_duty(&self) -> u32 {
+        match self {
+            OptionalChannel::Ready(channel) => channel.get_duty(),
+            OptionalChannel::Missing => 0,
+        }
+    }
 
-    // Test side movements
-    log::info!("Testing side movements with parallel calculation...");
-    servo_controller.set_right_servos(45, 45)?;
-    FreeRtos::delay_ms(500);
-    servo_controller.set_left_servos(135, 135)?;
-    FreeRtos::delay_ms(500);
+    fn disable(&mut self) -> Result<()> {
+        self.ready()?.disable()
+    }
 
-    // Return to center position
-    servo_controller.center_all_servos()?;
-    log::info!("Servos centered to 90 degrees (parallel execution)");
+    fn enable(&mut self) -> Result<()> {
+        self.ready()?.enable()
+    }
+}
 
-    log::info!("Servo demonstration with parallel control complete");
-    Ok(())
+/// Blocking delay used between movement steps
+pub trait Delay {
+    /// Block for the given number of milliseconds
+    fn delay_ms(&mut self, ms: u32);
 }
 
-// ================================================================================================
-// TESTING MODULE
-// ================================================================================================
+/// FreeRTOS task delay (yields to other tasks while waiting)
+#[cfg(feature = "esp32")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FreeRtosDelay;
 
-/// ## Testing Strategy
-///
-/// This module includes basic embedded tests, but for comprehensive testing
-/// without ESP32 dependencies, run the standalone test suite:
+#[cfg(feature = "esp32")]
+impl Delay for FreeRtosDelay {
+    fn delay_ms(&mut self, ms: u32) {
+        FreeRtos::delay_ms(ms);
+    }
+}
+
+/// Distance sensor used to stop before obstacles (e.g. an HC-SR04)
+pub trait ObstacleSource {
+    /// Distance to the nearest obstacle, or `None` if there is no valid echo
+    fn distance_cm(&mut self) -> Option<u32>;
+}
+
+/// Optional CPU clock control used by `ServoController::enter_sleep`/`wake`
+pub trait PowerControl {
+    /// Drop to the low-power CPU clock
+    fn reduce_clock(&mut self) -> Result<()>;
+
+    /// Return to the normal CPU clock
+    fn restore_clock(&mut self) -> Result<()>;
+}
+
+/// CPU frequency control through ESP-IDF power management
 ///
-/// ```bash
-/// cargo test --features mock
-/// ```
+/// Requires `CONFIG_PM_ENABLE`; without it the calls fail and sleep only
+/// detaches the servos.
+#[cfg(feature = "esp32")]
+#[derive(Debug, Clone, Copy)]
+pub struct EspPowerControl {
+    /// CPU clock while awake
+    pub normal_mhz: i32,
+    /// CPU clock while asleep
+    pub sleep_mhz: i32,
+}
+
+#[cfg(feature = "esp32")]
+impl Default for EspPowerControl {
+    fn default() -> Self {
+        Self {
+            normal_mhz: 240,
+            sleep_mhz: 80,
+        }
+    }
+}
+
+#[cfg(feature = "esp32")]
+impl EspPowerControl {
+    fn configure(max_freq_mhz: i32) -> Result<()> {
+        let config = esp_idf_sys::esp_pm_config_t {
+            max_freq_mhz,
+            min_freq_mhz: max_freq_mhz,
+            light_sleep_enable: false,
+        };
+        esp_idf_sys::esp!(unsafe {
+            esp_idf_sys::esp_pm_configure(&config as *const _ as *const core::ffi::c_void)
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "esp32")]
+impl PowerControl for EspPowerControl {
+    fn reduce_clock(&mut self) -> Result<()> {
+        Self::configure(self.sleep_mhz)
+    }
+
+    fn restore_clock(&mut self) -> Result<()> {
+        Self::configure(self.normal_mhz)
+    }
+}
+
+/// Source of the current time for the controller's timing features
+///
+/// The command rate limiter, timed walks and `GaitRunner`'s overrun check
+/// read the time through this trait, so tests can substitute a clock they
+/// advance by hand.
+pub trait Clock {
+    /// Current monotonic time
+    fn now(&self) -> Instant;
+}
+
+/// `std::time::Instant` clock, backed by `esp_timer` on ESP-IDF
+///
+/// The default clock of every controller.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EspClock;
+
+impl Clock for EspClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Controller type produced by `setup_servos` on the ESP32
+#[cfg(feature = "esp32")]
+pub type EspServoController = ServoController<LedcDriver<'static>, FreeRtosDelay>;
+
+/// Controller type produced by `setup_servos_partial` and
+/// `setup_servos_for_legs` on the ESP32
+#[cfg(feature = "esp32")]
+pub type PartialEspServoController =
+    ServoController<OptionalChannel<LedcDriver<'static>>, FreeRtosDelay>;
+
+// ================================================================================================
+// LEG IDENTIFICATION
+// ================================================================================================
+
+/// One of the robot's four legs
+///
+/// The declaration order matches the positional order used by `set_servo_angles`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Leg {
+    RightBack,
+    LeftBack,
+    RightFront,
+    LeftFront,
+}
+
+impl Leg {
+    /// Every leg, in `set_servo_angles` order (the order of `index`)
+    pub const ALL: [Leg; 4] = [
+        Leg::RightBack,
+        Leg::LeftBack,
+        Leg::RightFront,
+        Leg::LeftFront,
+    ];
+
+    /// Iterate over `Leg::ALL`
+    pub fn iter() -> impl Iterator<Item = Leg> {
+        Leg::ALL.into_iter()
+    }
+
+    /// Position of this leg in per-leg arrays
+    pub const fn index(self) -> usize {
+        match self {
+            Leg::RightBack => 0,
+            Leg::LeftBack => 1,
+            Leg::RightFront => 2,
+            Leg::LeftFront => 3,
+        }
+    }
+
+    /// Name used in log messages
+    pub const fn name(self) -> &'static str {
+        match self {
+            Leg::RightBack => "right_back_leg",
+            Leg::LeftBack => "left_back_leg",
+            Leg::RightFront => "right_front_leg",
+            Leg::LeftFront => "left_front_leg",
+        }
+    }
+
+    /// Look up a leg by its `name`, the name without `_leg`, or its initials
+    /// (`"rb"`, `"lb"`, `"rf"`, `"lf"`)
+    pub fn from_name(name: &str) -> Option<Leg> {
+        LEGS.into_iter().find(|leg| {
+            let full = leg.name();
+            let short = full.trim_end_matches("_leg");
+            let initials: String = short.split('_').filter_map(|w| w.chars().next()).collect();
+            name == full || name == short || name == initials
+        })
+    }
+}
+
+/// All legs in `set_servo_angles` order
+pub(crate) const LEGS: [Leg; 4] = Leg::ALL;
+
+/// How setters treat angles above 180°
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClampMode {
+    /// Clamp to 180°
+    #[default]
+    Saturate,
+    /// Reject with `ServoError::InvalidAngle`
+    Error,
+    /// Pass the angle through unclamped, extrapolating the pulse (debug only;
+    /// the duty is still capped at the channel's max duty)
+    None,
+}
+
+/// What the command rate limiter does with a command that comes too soon
+///
+/// See `ServoController::set_max_command_rate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RateLimitPolicy {
+    /// Discard the command; the setter returns without writing
+    #[default]
+    Drop,
+    /// Keep the command's angles as `submit_target` targets, so the latest
+    /// one is written by the next `tick_control`
+    Coalesce,
+}
+
+/// Whether a wiring map uses every channel exactly once
+pub(crate) fn is_valid_wiring(wiring: [Leg; 4]) -> bool {
+    LEGS.iter().all(|leg| wiring.contains(leg))
+}
+
+/// Recovery performed when a hardware write fails during a `set_*` call
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnError {
+    /// Return the error immediately, leaving legs wherever they ended up
+    #[default]
+    Abort,
+    /// Try to center every leg before returning the error
+    CenterAll,
+    /// Try to detach every leg (stop pulses) before returning the error
+    DetachAll,
+}
+
+/// How setters that move several legs at once write their duties
 ///
-/// The tests validate:
-/// - Core mathematical functions (angle_to_duty, duty_to_angle, etc.)
-/// - Servo operation data structures
-/// - Basic functionality that can run in ESP32 environment
+/// `set_servo_angles` and `set_all_servos_angle` always compute every duty
+/// before writing any, so this applies to the pair setters
+/// (`set_front_servos` and the like) and to `tick_control`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WriteStrategy {
+    /// Compute each leg's duty just before writing it
+    #[default]
+    Immediate,
+    /// Compute every duty first, then write them back to back; if any leg
+    /// cannot be computed, nothing is written
+    Batched,
+}
+
+// ================================================================================================
+// SERVO OPERATION DATA STRUCTURE
+// ================================================================================================
+
+/// Servo operation for threaded execution
+#[derive(Debug, Clone)]
+pub struct ServoOperation {
+    pub angle: u32,
+    pub max_duty: u32,
+    pub servo_name: String,
+}
+
+/// Strategy for calculating the duties of a batch of servo operations
+///
+/// The batch setters only use the duties once all of them are known, so a
+/// backend is free to calculate them in any order. The controller uses
+/// `DefaultDutyBackend`.
+pub trait DutyBackend {
+    /// Duty for every operation, keyed by `servo_name`
+    fn compute_duties(
+        &self,
+        operations: Vec<(ServoOperation, LegCalibration)>,
+        period_us: u32,
+        log_target: &'static str,
+    ) -> Result<HashMap<String, u32>>;
+}
+
+/// Calculates every duty in turn on the calling thread
+///
+/// The default backend: a duty is a few integer operations, far less than the
+/// cost of spawning a thread, and it needs no `std::thread`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SingleThreaded;
+
+impl DutyBackend for SingleThreaded {
+    fn compute_duties(
+        &self,
+        operations: Vec<(ServoOperation, LegCalibration)>,
+        period_us: u32,
+        log_target: &'static str,
+    ) -> Result<HashMap<String, u32>> {
+        Ok(operations
+            .into_iter()
+            .map(|(op, calibration)| operation_duty(op, calibration, period_us, log_target))
+            .collect())
+    }
+}
+
+/// Calculates each duty on its own `std::thread` (`std` feature)
+///
+/// A thread that panics fails the batch with `ServoError::CalculationFailed`
+/// instead of taking the caller down, so the setter's `OnError` recovery
+/// still runs.
+#[cfg(any(feature = "std", test))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdThreads;
+
+#[cfg(any(feature = "std", test))]
+impl DutyBackend for StdThreads {
+    fn compute_duties(
+        &self,
+        operations: Vec<(ServoOperation, LegCalibration)>,
+        period_us: u32,
+        log_target: &'static str,
+    ) -> Result<HashMap<String, u32>> {
+        compute_on_threads(operations, move |op, calibration| {
+            operation_duty(op, calibration, period_us, log_target)
+        })
+    }
+}
+
+/// Run `calculate` for every operation on its own thread
+///
+/// Every thread is joined; the first one that panicked is reported as
+/// `ServoError::CalculationFailed`.
+#[cfg(any(feature = "std", test))]
+fn compute_on_threads(
+    operations: Vec<(ServoOperation, LegCalibration)>,
+    calculate: impl Fn(ServoOperation, LegCalibration) -> (String, u32) + Copy + Send + 'static,
+) -> Result<HashMap<String, u32>> {
+    let (tx, rx) = mpsc::channel();
+    let mut handles = vec![];
+
+    // Spawn threads to calculate duty values
+    for (op, calibration) in operations {
+        let tx_clone = tx.clone();
+        let servo_name = op.servo_name.clone();
+        let handle = thread::spawn(move || {
+            // The receiver is only dropped after every thread is joined
+            let _ = tx_clone.send(calculate(op, calibration));
+        });
+        handles.push((servo_name, handle));
+    }
+
+    // Drop the original sender
+    drop(tx);
+
+    // Collect results from threads
+    let duties = rx.into_iter().collect();
+
+    // Wait for all threads to complete
+    let mut failed = None;
+    for (servo_name, handle) in handles {
+        if handle.join().is_err() {
+            failed.get_or_insert(servo_name);
+        }
+    }
+    match failed {
+        Some(servo) => Err(ServoError::CalculationFailed { servo }.into()),
+        None => Ok(duties),
+    }
+}
+
+/// Backend used by the controller's batch setters
+#[cfg(feature = "std")]
+pub type DefaultDutyBackend = StdThreads;
+
+/// Backend used by the controller's batch setters
+#[cfg(not(feature = "std"))]
+pub type DefaultDutyBackend = SingleThreaded;
+
+/// Duty of one operation, keyed by its servo name
+fn operation_duty(
+    op: ServoOperation,
+    calibration: LegCalibration,
+    period_us: u32,
+    log_target: &'static str,
+) -> (String, u32) {
+    let duty = calibration.duty(op.angle, op.max_duty, period_us);
+    log_debug!(
+        target: log_target,
+        "Calculated {} duty: {} for angle: {}",
+        op.servo_name,
+        duty,
+        op.angle
+    );
+    (op.servo_name, duty)
+}
+
+// ================================================================================================
+// ROBOT CONTROLLER
+// ================================================================================================
+
+/// Snapshot of every tunable controller setting
 ///
-/// For more comprehensive testing with mock hardware, see the documentation
-/// on testing strategies for embedded Rust projects.
+/// Taken with `ServoController::config` and restored with `apply_config`, so
+/// whole profiles (e.g. "tuning" and "demo") can be swapped at once. Per-leg
+/// arrays are in `set_servo_angles` order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControllerConfig {
+    pub calibration: [LegCalibration; 4],
+    pub curves: [CorrectionCurve; 4],
+    pub motion_limits: MotionLimits,
+    pub enabled: [bool; 4],
+    pub on_error: OnError,
+    pub clamp_mode: ClampMode,
+    pub settle_time_ms: u32,
+    pub deadband_deg: u32,
+    pub detach_pulse_us: Option<u32>,
+    pub soft_limits: [(u32, u32); 4],
+    pub payload_offset: (i32, i32),
+    pub wiring: [Leg; 4],
+    pub max_concurrent_moves: usize,
+    pub stagger_ms: u32,
+    pub retry_attempts: u32,
+    pub retry_delay_ms: u32,
+    pub park_pose: [u32; 4],
+    pub max_command_rate_hz: u32,
+    pub rate_limit_policy: RateLimitPolicy,
+    pub control_interval: Duration,
+    pub auto_center_after: Option<Duration>,
+    pub current_model: CurrentModel,
+    pub forbidden_zones: [Vec<(u32, u32)>; 4],
+    pub quantize: bool,
+    pub max_gait_duration: Option<Duration>,
+    pub gait_lead_in_ms: u32,
+    pub write_strategy: WriteStrategy,
+}
+
+impl Default for ControllerConfig {
+    fn default() -> Self {
+        Self {
+            calibration: [LegCalibration::default(); 4],
+            curves: Default::default(),
+            motion_limits: MotionLimits::default(),
+            enabled: [true; 4],
+            on_error: OnError::default(),
+            clamp_mode: ClampMode::default(),
+            settle_time_ms: 0,
+            deadband_deg: 0,
+            detach_pulse_us: None,
+            soft_limits: [(0, 180); 4],
+            payload_offset: (0, 0),
+            wiring: LEGS,
+            max_concurrent_moves: LEGS.len(),
+            stagger_ms: 0,
+            retry_attempts: 0,
+            retry_delay_ms: 0,
+            park_pose: PARK_FLAT_POSE,
+            max_command_rate_hz: 0,
+            rate_limit_policy: RateLimitPolicy::default(),
+            control_interval: Duration::from_millis(SMOOTH_STEP_MS.into()),
+            auto_center_after: None,
+            current_model: CurrentModel::default(),
+            forbidden_zones: Default::default(),
+            quantize: false,
+            max_gait_duration: Some(DEFAULT_MAX_GAIT_DURATION),
+            gait_lead_in_ms: 0,
+            write_strategy: WriteStrategy::default(),
+        }
+    }
+}
+
+/// 4-legged robot servo controller with parallel execution capabilities
+pub struct ServoController<P: PwmBackend, D: Delay> {
+    /// PWM channels indexed by `Leg::index` of the leg they were created for
+    legs: [P; 4],
+    /// Channel driving each leg, named by the leg it was created for
+    wiring: [Leg; 4],
+    delay: D,
+    /// Last commanded angle per leg (assumed centered until first command)
+    angles: [u32; 4],
+    /// Last duty successfully written per leg, `None` until the first write
+    duties: [Option<u32>; 4],
+    /// Speed/acceleration limits used by smooth moves
+    motion_limits: MotionLimits,
+    /// Legs currently detached, their channel disabled or parked
+    detached: [bool; 4],
+    /// Recovery performed when a write fails
+    on_error: OnError,
+    /// Whether multi-leg setters compute every duty before writing
+    write_strategy: WriteStrategy,
+    /// Treatment of angles above 180°
+    clamp_mode: ClampMode,
+    /// Wait after each successful setter for the servos to arrive
+    settle_time_ms: u32,
+    /// Time `play_sequence` takes to blend into a gait's first frame
+    gait_lead_in_ms: u32,
+    /// Commands this close to a leg's tracked angle are not written
+    deadband_deg: u32,
+    /// Snap commands to duty steps and skip those that keep a leg's duty
+    quantize: bool,
+    /// Pulse a detached leg is parked at, or `None` to disable its channel
+    detach_pulse_us: Option<u32>,
+    /// Per-leg `(min, max)` range every command is clamped to
+    soft_limits: [(u32, u32); 4],
+    /// Per-leg `(lo, hi)` ranges no move may end in or pass through
+    forbidden_zones: [Vec<(u32, u32)>; 4],
+    /// Payload offset `(fore_aft, left_right)` the stance angles compensate for
+    payload_offset: (i32, i32),
+    /// Legs that are driven; disabled legs are skipped by every setter
+    enabled: [bool; 4],
+    /// Most legs a batch setter writes before pausing for `WAVE_DELAY_MS`
+    max_concurrent_moves: usize,
+    /// Pause between consecutive leg writes of a batch setter
+    stagger_ms: u32,
+    /// Target attached to every log record emitted by the controller
+    log_target: &'static str,
+    /// Frequency the PWM timer actually runs at, used for duty conversion
+    actual_frequency_hz: u32,
+    /// Extra attempts for a failed duty write, and the pause before each
+    retry_attempts: u32,
+    retry_delay_ms: u32,
+    /// Poses stored with `define_pose`, at most `MAX_POSES`
+    poses: Vec<(String, [u32; 4])>,
+    /// Per-leg angle correction applied before the pulse calculation
+    curves: [CorrectionCurve; 4],
+    /// Per-leg pulse range, trim, inversion and center
+    calibration: [LegCalibration; 4],
+    /// Clock control used by `enter_sleep`/`wake`, if any
+    power: Option<Box<dyn PowerControl + Send>>,
+    /// Whether `enter_sleep` has been called without a matching `wake`
+    sleeping: bool,
+    /// Set by `kill`; every write is refused until `clear_halt`
+    halted: bool,
+    /// Set by `mark_state_unknown`; every write is refused until `resync`
+    state_unknown: bool,
+    /// Latest angle passed to `submit_target` per leg, not yet applied
+    targets: [Option<u32>; 4],
+    /// Shortest time between two `tick_control` writes
+    control_interval: Duration,
+    /// When `tick_control` last wrote targets
+    last_control_tick: Option<Instant>,
+    /// Most commands let through per second, 0 for no limit
+    max_command_rate_hz: u32,
+    /// Treatment of commands above `max_command_rate_hz`
+    rate_limit_policy: RateLimitPolicy,
+    /// When the rate limiter last let a command through
+    last_command: Option<Instant>,
+    /// Folded posture `park_flat` moves to
+    park_pose: [u32; 4],
+    /// Time source for the timing features
+    clock: Arc<dyn Clock + Send + Sync>,
+    /// Coefficients of `estimated_current_ma`
+    current_model: CurrentModel,
+    /// Size and time of each leg's latest move, for `estimated_current_ma`
+    last_moves: [Option<(u32, Instant)>; 4],
+    /// Idle time after which `tick_control` centers the legs, if set
+    auto_center_after: Option<Duration>,
+    /// Time after which open-ended movements stop, if set
+    max_gait_duration: Option<Duration>,
+    /// Time of the last write or submitted target, `None` once centered or
+    /// detached
+    last_activity: Option<Instant>,
+}
+
+impl<P: PwmBackend, D: Delay> ServoController<P, D> {
+    /// Create a new ServoController with the given PWM channels and delay provider
+    pub fn new(
+        right_back_leg: P,
+        left_back_leg: P,
+        right_front_leg: P,
+        left_front_leg: P,
+        delay: D,
+    ) -> Self {
+        Self {
+            legs: [
+                right_back_leg,
+                left_back_leg,
+                right_front_leg,
+                left_front_leg,
+            ],
+            wiring: LEGS,
+            delay,
+            angles: [90; 4],
+            duties: [None; 4],
+            motion_limits: MotionLimits::default(),
+            detached: [false; 4],
+            on_error: OnError::default(),
+            write_strategy: WriteStrategy::default(),
+            clamp_mode: ClampMode::default(),
+            settle_time_ms: 0,
+            gait_lead_in_ms: 0,
+            deadband_deg: 0,
+            quantize: false,
+            detach_pulse_us: None,
+            soft_limits: [(0, 180); 4],
+            forbidden_zones: Default::default(),
+            payload_offset: (0, 0),
+            enabled: [true; 4],
+            max_concurrent_moves: LEGS.len(),
+            stagger_ms: 0,
+            log_target: DEFAULT_LOG_TARGET,
+            actual_frequency_hz: FREQUENCY_HZ,
+            retry_attempts: 0,
+            retry_delay_ms: 0,
+            poses: Vec::new(),
+            curves: Default::default(),
+            calibration: [LegCalibration::default(); 4],
+            power: None,
+            sleeping: false,
+            halted: false,
+            state_unknown: false,
+            targets: [None; 4],
+            control_interval: Duration::from_millis(SMOOTH_STEP_MS.into()),
+            last_control_tick: None,
+            max_command_rate_hz: 0,
+            rate_limit_policy: RateLimitPolicy::default(),
+            last_command: None,
+            park_pose: PARK_FLAT_POSE,
+            clock: Arc::new(EspClock),
+            current_model: CurrentModel::default(),
+            last_moves: [None; 4],
+            auto_center_after: None,
+            max_gait_duration: Some(DEFAULT_MAX_GAIT_DURATION),
+            last_activity: None,
+        }
+    }
+
+    /// Convert angles to duty using the frequency the timer actually achieved
+    ///
+    /// LEDC can't always hit `FREQUENCY_HZ` exactly because of its clock
+    /// divider; `setup_servos` reads the real value back from the timer.
+    pub fn with_actual_frequency_hz(mut self, frequency_hz: u32) -> Self {
+        self.actual_frequency_hz = frequency_hz;
+        self
+    }
+
+    /// Frequency used for duty conversion (`FREQUENCY_HZ` unless overridden)
+    pub fn actual_frequency_hz(&self) -> u32 {
+        self.actual_frequency_hz
+    }
+
+    /// Use `target` (e.g. `"cobot.servo"`) for every log record the controller
+    /// emits, so servo logs can be filtered from the rest of the firmware
+    pub fn with_log_target(mut self, target: &'static str) -> Self {
+        self.log_target = target;
+        self
+    }
+
+    /// Read the time from `clock` instead of `EspClock`
+    pub fn with_clock(mut self, clock: impl Clock + Send + Sync + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Current time of the controller's clock
+    pub fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    /// Shared handle to the controller's clock
+    pub(crate) fn clock(&self) -> Arc<dyn Clock + Send + Sync> {
+        Arc::clone(&self.clock)
+    }
+
+    /// Target attached to the controller's log records
+    pub fn log_target(&self) -> &'static str {
+        self.log_target
+    }
+
+    /// Enable or disable a leg
+    ///
+    /// Disabled legs are skipped by every setter: no duty is written, no error
+    /// is returned and the tracked angle is left unchanged. Useful when only
+    /// some servos are connected on the bench.
+    pub fn set_leg_enabled(&mut self, leg: Leg, enabled: bool) {
+        self.enabled[leg.index()] = enabled;
+    }
+
+    /// Whether a leg is driven by the setters
+    pub fn is_leg_enabled(&self, leg: Leg) -> bool {
+        self.enabled[leg.index()]
+    }
+
+    /// Limit how many legs a batch setter moves at once to keep peak current down
+    ///
+    /// Batch setters (`set_servo_angles`, `set_all_servos_angle`, the pair
+    /// setters, ...) write at most `n` legs, pause for `WAVE_DELAY_MS`, then
+    /// write the next `n`. Disabled legs don't count. `n` of 0 is treated as 1;
+    /// the default is all legs at once.
+    pub fn set_max_concurrent_moves(&mut self, n: usize) {
+        self.max_concurrent_moves = n.max(1);
+    }
+
+    /// Most legs a batch setter moves at once
+    pub fn max_concurrent_moves(&self) -> usize {
+        self.max_concurrent_moves
+    }
+
+    /// Spread the leg writes of a batch setter `ms` apart
+    ///
+    /// Energizing every servo in the same instant makes them pop and jerk
+    /// together, most noticeably on the first move after startup. With a
+    /// stagger, batch setters pause `ms` between consecutive leg writes; where
+    /// `set_max_concurrent_moves` also pauses, the longer of the two pauses
+    /// is used. Single-leg setters are unaffected. The default of 0 writes
+    /// all legs back to back.
+    pub fn set_stagger_ms(&mut self, ms: u32) {
+        self.stagger_ms = ms;
+    }
+
+    /// Stagger set with `set_stagger_ms`
+    pub fn stagger_ms(&self) -> u32 {
+        self.stagger_ms
+    }
+
+    /// Retry failed duty writes to ride out transient errors
+    ///
+    /// A failed `set_duty` is retried up to `attempts` more times, waiting
+    /// `delay_ms` before each retry; the last error is returned once retries
+    /// are exhausted (and only then does `OnError` recovery run). The default
+    /// of 0 attempts never retries.
+    pub fn set_retry_policy(&mut self, attempts: u32, delay_ms: u32) {
+        self.retry_attempts = attempts;
+        self.retry_delay_ms = delay_ms;
+    }
+
+    /// Wait `ms` after every successful `set_*` call before returning
+    ///
+    /// Gives callers a "movement complete" guarantee without their own delays.
+    /// Movements built on the setters (gaits, poses) settle after each frame,
+    /// on top of their frame delay; smooth moves settle once at the end. The
+    /// default of 0 returns as soon as the duties are written.
+    pub fn set_settle_time_ms(&mut self, ms: u32) {
+        self.settle_time_ms = ms;
+    }
+
+    /// Wait applied after every successful `set_*` call
+    pub fn settle_time_ms(&self) -> u32 {
+        self.settle_time_ms
+    }
+
+    /// Clamp every command to `leg` to `min_deg..=max_deg`
+    ///
+    /// Protects a leg whose motion is restricted by the chassis from being
+    /// driven into it, by gaits as much as by direct commands. The limits are
+    /// logical angles, applied before calibration; they are swapped if given
+    /// in the wrong order and capped at 180°. Defaults to the full 0-180°. In
+    /// `ClampMode::Error` angles above 180° are still rejected rather than
+    /// clamped.
+    pub fn set_soft_limits(&mut self, leg: Leg, min_deg: u32, max_deg: u32) {
+        let (min_deg, max_deg) = if min_deg > max_deg {
+            (max_deg, min_deg)
+        } else {
+            (min_deg, max_deg)
+        };
+        self.soft_limits[leg.index()] = (min_deg.min(180), max_deg.min(180));
+    }
+
+    /// Soft limits of a leg as `(min_deg, max_deg)`
+    pub fn soft_limits(&self, leg: Leg) -> (u32, u32) {
+        self.soft_limits[leg.index()]
+    }
+
+    /// Forbid a leg from entering `lo..=hi`, e.g. where it catches a wire
+    ///
+    /// A joint's range does not wrap around, so there is no long way round a
+    /// zone: the only path between two angles is the straight one. Moves
+    /// that would end in a zone or pass through one are therefore rejected
+    /// with `ServoError::ForbiddenZone` before anything is written, and no
+    /// `OnError` recovery runs; this includes a whole batch if any of its
+    /// legs is rejected, and a smooth move before its first step. Moves that
+    /// stay clear are unaffected, and a leg already inside a zone may leave
+    /// it toward either side. Paths are checked between tracked angles, so a
+    /// leg that was never written is taken to be at its initial 90°. The
+    /// bounds are in commanded degrees, swapped if given in reverse and
+    /// clamped to 0-180°.
+    pub fn add_forbidden_zone(&mut self, leg: Leg, lo: u32, hi: u32) {
+        let (lo, hi) = if lo > hi { (hi, lo) } else { (lo, hi) };
+        self.forbidden_zones[leg.index()].push((lo.min(180), hi.min(180)));
+    }
+
+    /// Remove every forbidden zone of a leg
+    pub fn clear_forbidden_zones(&mut self, leg: Leg) {
+        self.forbidden_zones[leg.index()].clear();
+    }
+
+    /// Forbidden zones of a leg as `(lo, hi)`, in the order they were added
+    pub fn forbidden_zones(&self, leg: Leg) -> &[(u32, u32)] {
+        &self.forbidden_zones[leg.index()]
+    }
+
+    /// Reject a move of `leg` from its tracked angle to `angle` that would
+    /// end in or cross one of its forbidden zones
+    fn check_path(&self, leg: Leg, angle: u32) -> Result<()> {
+        let from = self.angles[leg.index()];
+        let to = self.limit_angle(leg, angle);
+        let (low, high) = (from.min(to), from.max(to));
+        let blocking = self.forbidden_zones[leg.index()]
+            .iter()
+            .find(|&&(lo, hi)| (lo..=hi).contains(&to) || (low < lo && hi < high));
+        match blocking {
+            Some(&zone) => Err(ServoError::ForbiddenZone {
+                leg,
+                from,
+                to,
+                zone,
+            }
+            .into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Angle a command to `leg` is clamped to by 0-180° and its soft limits
+    fn limit_angle(&self, leg: Leg, angle: u32) -> u32 {
+        let (min_deg, max_deg) = self.soft_limits[leg.index()];
+        angle.clamp(min_deg, max_deg)
+    }
+
+    /// Ignore commands within `deg` of a leg's tracked angle, to stop chatter
+    ///
+    /// A skipped command writes nothing and leaves the tracked angle as it
+    /// was, so a slow drift is still applied once it exceeds the deadband.
+    /// The first write to a leg and writes to detached legs always go through.
+    /// The default of 0 writes every command.
+    pub fn set_deadband(&mut self, deg: u32) {
+        self.deadband_deg = deg;
+    }
+
+    /// Deadband set with `set_deadband`
+    pub fn deadband(&self) -> u32 {
+        self.deadband_deg
+    }
+
+    /// Snap commanded angles to the duty steps the timer can produce
+    ///
+    /// At coarse resolutions several whole degrees drive the same duty (see
+    /// `angle_resolution`). With quantization on, every setter snaps an angle
+    /// to the middle of the run of degrees sharing its duty, so they are
+    /// tracked as one angle, and a command that would leave an attached
+    /// leg's duty unchanged is skipped like one inside the deadband. Off by
+    /// default; angles above 180° (`ClampMode::None`) are not snapped.
+    pub fn set_quantize(&mut self, quantize: bool) {
+        self.quantize = quantize;
+    }
+
+    /// Whether `set_quantize` is on
+    pub fn quantize(&self) -> bool {
+        self.quantize
+    }
+
+    /// Angle a command to `leg` is snapped to by `set_quantize`
+    fn quantize_angle(&self, leg: Leg, angle: u32) -> u32 {
+        if !self.quantize || angle > 180 {
+            return angle;
+        }
+        let angle = self.limit_angle(leg, angle);
+        let Ok(duty) = self.duty_for(leg, angle) else {
+            return angle;
+        };
+        let (min_deg, max_deg) = self.soft_limits[leg.index()];
+        let same_duty = |a: &u32| self.duty_for(leg, *a).ok() == Some(duty);
+        let low = (min_deg..angle).rev().take_while(same_duty).last();
+        let high = (angle + 1..=max_deg).take_while(same_duty).last();
+        (low.unwrap_or(angle) + high.unwrap_or(angle)) / 2
+    }
+
+    /// Choose what detaching a leg leaves on its channel
+    ///
+    /// With `Some(pulse_us)`, detaching drives that pulse once and then stops
+    /// updating the leg, for servos that misbehave without a pulse. With
+    /// `None` (the default), detaching disables the channel so no pulse is
+    /// generated.
+    pub fn set_detach_pulse_us(&mut self, pulse_us: Option<u32>) {
+        self.detach_pulse_us = pulse_us;
+    }
+
+    /// Park pulse set with `set_detach_pulse_us`
+    pub fn detach_pulse_us(&self) -> Option<u32> {
+        self.detach_pulse_us
+    }
+
+    /// Limit `tick_control` to at most `hz` writes per second (minimum 1)
+    ///
+    /// Defaults to one write per 50 Hz PWM period.
+    pub fn set_control_rate_hz(&mut self, hz: u32) {
+        self.control_interval = Duration::from_secs(1) / hz.max(1);
+    }
+
+    /// Let through at most `hz` commands per second (0, the default, for no
+    /// limit)
+    ///
+    /// A safety valve against runaway control loops. It applies to the direct
+    /// setters (`set_leg_angle`, `set_servo_angles`, `set_all_servos_angle`
+    /// and the pair setters) and so to the poses and gaits built on them.
+    /// Smooth-move steps, `tick_control` and error recovery are not limited.
+    /// A command arriving within `1 / hz` s of the last one let through is
+    /// handled by the `RateLimitPolicy`:
+    ///
+    /// - `Drop` (the default) discards it: nothing is written and the setter
+    ///   returns `Ok`. Suits loops that resend their full state anyway.
+    /// - `Coalesce` stores its angles as targets, replacing older ones, so the
+    ///   latest command is still applied by the next `tick_control`. A command
+    ///   let through cancels the pending targets of the legs it sets.
+    pub fn set_max_command_rate(&mut self, hz: u32) {
+        self.max_command_rate_hz = hz;
+        self.last_command = None;
+    }
+
+    /// Rate set with `set_max_command_rate`
+    pub fn max_command_rate(&self) -> u32 {
+        self.max_command_rate_hz
+    }
+
+    /// Choose what the rate limiter does with commands that come too soon
+    pub fn set_rate_limit_policy(&mut self, policy: RateLimitPolicy) {
+        self.rate_limit_policy = policy;
+    }
+
+    /// Policy set with `set_rate_limit_policy`
+    pub fn rate_limit_policy(&self) -> RateLimitPolicy {
+        self.rate_limit_policy
+    }
+
+    /// Whether a command for `writes` arriving at `now` passes the rate
+    /// limiter; a command that doesn't is dropped or coalesced
+    fn admit_command(&mut self, now: Instant, writes: &[(Leg, u32)]) -> bool {
+        if self.max_command_rate_hz == 0 {
+            return true;
+        }
+
+        let interval = Duration::from_secs(1) / self.max_command_rate_hz;
+        if let Some(last) = self.last_command
+            && now.saturating_duration_since(last) < interval
+        {
+            match self.rate_limit_policy {
+                RateLimitPolicy::Drop => {
+                    log_debug!(target: self.log_target, "Rate limit: command dropped");
+                }
+                RateLimitPolicy::Coalesce => {
+                    for &(leg, angle) in writes {
+                        self.targets[leg.index()] = Some(angle);
+                    }
+                }
+            }
+            return false;
+        }
+
+        self.last_command = Some(now);
+        for &(leg, _) in writes {
+            self.targets[leg.index()] = None;
+        }
+        true
+    }
+
+    /// Set how setters treat angles above 180°
+    pub fn set_clamp_mode(&mut self, mode: ClampMode) {
+        self.clamp_mode = mode;
+    }
+
+    /// How setters treat angles above 180°
+    pub fn clamp_mode(&self) -> ClampMode {
+        self.clamp_mode
+    }
+
+    /// Set the recovery performed when a `set_*` call fails to write
+    pub fn set_on_error(&mut self, policy: OnError) {
+        self.on_error = policy;
+    }
+
+    /// Recovery performed when a `set_*` call fails to write
+    pub fn on_error(&self) -> OnError {
+        self.on_error
+    }
+
+    /// Choose whether multi-leg setters compute every duty before writing
+    ///
+    /// `WriteStrategy::Batched` narrows the gap between the legs' updates to
+    /// the back-to-back channel writes, and never leaves a batch half
+    /// written because a later leg could not be computed (an angle rejected
+    /// by `ClampMode::Error` or a forbidden zone, a dead timer). The LEDC
+    /// HAL has no multi-channel commit, so the legs still update one channel
+    /// at a time: each new duty takes effect at its channel's next period,
+    /// which usually puts all four on the same 20 ms boundary, but a
+    /// boundary can fall between two writes. Pacing set with
+    /// `set_max_concurrent_moves` or `set_stagger_ms` still spreads the
+    /// writes out; a failed write still stops the batch.
+    pub fn set_write_strategy(&mut self, strategy: WriteStrategy) {
+        self.write_strategy = strategy;
+    }
+
+    /// Strategy set with `set_write_strategy`
+    pub fn write_strategy(&self) -> WriteStrategy {
+        self.write_strategy
+    }
+
+    /// Last commanded angle for a leg
+    pub fn current_angle(&self, leg: Leg) -> u32 {
+        self.angles[leg.index()]
+    }
+
+    /// Last commanded angles in `set_servo_angles` order
+    pub fn current_angles(&self) -> [u32; 4] {
+        self.angles
+    }
+
+    /// Set the speed and acceleration limits used by `move_leg_smooth`
+    pub fn set_motion_limits(&mut self, limits: MotionLimits) {
+        self.motion_limits = limits;
+    }
+
+    /// Speed and acceleration limits used by `move_leg_smooth`
+    pub fn motion_limits(&self) -> MotionLimits {
+        self.motion_limits
+    }
+
+    /// Block for `ms` milliseconds using the controller's delay provider
+    pub fn delay_ms(&mut self, ms: u32) {
+        self.delay.delay_ms(ms);
+    }
+
+    /// Route each leg to a different channel, to correct swapped connectors
+    ///
+    /// `wiring[leg.index()]` names the channel (by the leg it was created for)
+    /// that now drives `leg`; see `diagnostics::detect_wiring_remap`.
+    /// Calibration and tracked angles stay with the leg.
+    pub fn set_wiring(&mut self, wiring: [Leg; 4]) -> Result<()> {
+        if !is_valid_wiring(wiring) {
+            return Err(ServoError::InvalidWiring.into());
+        }
+        self.wiring = wiring;
+        Ok(())
+    }
+
+    /// Channel driving each leg (`LEGS` unless remapped)
+    pub fn wiring(&self) -> [Leg; 4] {
+        self.wiring
+    }
+
+    /// Index into `legs` of the channel driving `leg`
+    fn channel_index(&self, leg: Leg) -> usize {
+        self.wiring[leg.index()].index()
+    }
+
+    /// Max duty of a leg's channel, rejecting a misconfigured timer (max duty 0)
+    fn max_duty(&self, leg: Leg) -> Result<u32> {
+        match self.legs[self.channel_index(leg)].get_max_duty() {
+            0 => Err(ServoError::InvalidTimerConfig { leg }.into()),
+            max_duty => Ok(max_duty),
+        }
+    }
+
+    /// Check that every enabled leg's channel reports a usable max duty
+    pub fn check_timer_config(&self) -> Result<()> {
+        for leg in LEGS {
+            if self.enabled[leg.index()] {
+                self.max_duty(leg)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Physical angle for a logical angle, following the `ClampMode` and the
+    /// leg's soft limits
+    fn physical_angle(&self, leg: Leg, angle: u32) -> Result<u32> {
+        let calibration = &self.calibration[leg.index()];
+        let unlimited_top = self.soft_limits[leg.index()].1 == 180;
+        match self.clamp_mode {
+            ClampMode::Error if angle > 180 => Err(ServoError::InvalidAngle {
+                leg,
+                degrees: angle as f32,
+            }
+            .into()),
+            ClampMode::None if angle > 180 && unlimited_top => {
+                Ok(calibration.physical_angle_unclamped(angle))
+            }
+            _ => {
+                let angle = self.limit_angle(leg, self.stance_angle(leg, angle));
+                Ok(self.curves[leg.index()].apply(calibration.physical_angle(angle)))
+            }
+        }
+    }
+
+    /// Duty that drives a leg to a logical angle, with its calibration and
+    /// correction curve applied
+    fn duty_for(&self, leg: Leg, angle: u32) -> Result<u32> {
+        let calibration = &self.calibration[leg.index()];
+        let physical = self.physical_angle(leg, angle)?;
+        Ok(calibration.duty(
+            physical,
+            self.max_duty(leg)?,
+            period_us(self.actual_frequency_hz),
+        ))
+    }
+
+    /// Write a duty value to a leg, re-attaching it first if detached
+    fn write_duty(&mut self, leg: Leg, duty: u32) -> Result<()> {
+        if self.halted {
+            return Err(ServoError::Halted.into());
+        }
+        if self.state_unknown {
+            return Err(ServoError::StateUnknown.into());
+        }
+        let channel = self.channel_index(leg);
+        if self.detached[leg.index()] {
+            self.legs[channel].enable()?;
+            self.detached[leg.index()] = false;
+        }
+
+        let mut result = self.legs[channel].set_duty(duty);
+        for attempt in 1..=self.retry_attempts {
+            let Err(err) = &result else {
+                break;
+            };
+            log_warn!(
+                target: self.log_target,
+                "Write to {} failed: {}; retry {} of {}",
+                leg.name(),
+                err,
+                attempt,
+                self.retry_attempts
+            );
+            self.delay.delay_ms(self.retry_delay_ms);
+            result = self.legs[channel].set_duty(duty);
+        }
+        if result.is_ok() {
+            self.duties[leg.index()] = Some(duty);
+            self.last_activity = Some(self.clock.now());
+        }
+        result
+    }
+
+    /// Write an angle to a leg and track it, without error recovery
+    fn write_angle(&mut self, leg: Leg, angle: u32) -> Result<()> {
+        let angle = self.quantize_angle(leg, angle);
+        if !self.should_write(leg, angle) {
+            return Ok(());
+        }
+
+        self.check_path(leg, angle)?;
+        let duty = self.duty_for(leg, angle)?;
+        self.commit_angle(leg, angle, duty)
+    }
+
+    /// Write a computed duty to a leg and track its angle
+    fn commit_angle(&mut self, leg: Leg, angle: u32, duty: u32) -> Result<()> {
+        self.write_duty(leg, duty)?;
+        self.track_angle(leg, angle);
+        log_debug!(
+            target: self.log_target,
+            "{} set to {} degrees (duty: {})",
+            leg.name(),
+            angle,
+            duty
+        );
+        Ok(())
+    }
+
+    /// Record `angle` as written to `leg`, noting the size of the move
+    fn track_angle(&mut self, leg: Leg, angle: u32) {
+        let i = leg.index();
+        let angle = self.limit_angle(leg, angle);
+        self.last_moves[i] = Some((angle.abs_diff(self.angles[i]), self.clock.now()));
+        self.angles[i] = angle;
+    }
+
+    /// Whether a command to `leg` is written: the leg is enabled and the angle
+    /// is outside the deadband
+    fn should_write(&self, leg: Leg, angle: u32) -> bool {
+        let i = leg.index();
+        let within_deadband = self.deadband_deg > 0
+            && self.duties[i].is_some()
+            && !self.detached[i]
+            && self.limit_angle(leg, angle).abs_diff(self.angles[i]) <= self.deadband_deg;
+        let same_duty = self.quantize
+            && self.duties[i].is_some()
+            && !self.detached[i]
+            && self.duty_for(leg, angle).ok() == self.duties[i];
+        self.enabled[i] && !within_deadband && !same_duty
+    }
+
+    /// Pause between sub-batches once `moved` legs of a batch have been written
+    fn pace_wave(&mut self, moved: usize) {
+        if moved == 0 {
+            return;
+        }
+        let pause_ms = if moved.is_multiple_of(self.max_concurrent_moves) {
+            WAVE_DELAY_MS.max(self.stagger_ms)
+        } else {
+            self.stagger_ms
+        };
+        if pause_ms > 0 {
+            self.delay_ms(pause_ms);
+        }
+    }
+
+    /// Write several legs in sub-batches of `max_concurrent_moves`, without
+    /// error recovery
+    fn write_batch(&mut self, writes: &[(Leg, u32)]) -> Result<()> {
+        if self.write_strategy == WriteStrategy::Batched {
+            let mut planned = Vec::with_capacity(writes.len());
+            for &(leg, angle) in writes {
+                let angle = self.quantize_angle(leg, angle);
+                if !self.should_write(leg, angle) {
+                    continue;
+                }
+                self.check_path(leg, angle)?;
+                planned.push((leg, angle, self.duty_for(leg, angle)?));
+            }
+            for (moved, (leg, angle, duty)) in planned.into_iter().enumerate() {
+                self.pace_wave(moved);
+                self.commit_angle(leg, angle, duty)?;
+            }
+            return Ok(());
+        }
+
+        let mut moved = 0;
+        for &(leg, angle) in writes {
+            if !self.should_write(leg, angle) {
+                continue;
+            }
+            self.pace_wave(moved);
+            self.write_angle(leg, angle)?;
+            moved += 1;
+        }
+        Ok(())
+    }
+
+    /// Run the configured `OnError` recovery if `result` is an error
+    ///
+    /// Recovery is best-effort: its own failures are logged and the original
+    /// error is returned.
+    fn recover_on_error(&mut self, result: Result<()>) -> Result<()> {
+        let Err(err) = result else {
+            return Ok(());
+        };
+
+        log_error!(
+            target: self.log_target,
+            "Servo write failed: {}; recovery: {:?}",
+            err,
+            self.on_error
+        );
+        // A killed or unsynced controller has nothing to recover to, and a
+        // move rejected by a forbidden zone wrote nothing
+        let rejected = matches!(
+            err.downcast_ref::<ServoError>(),
+            Some(ServoError::ForbiddenZone { .. })
+        );
+        if self.halted || self.state_unknown || rejected {
+            return Err(err);
+        }
+        for leg in LEGS {
+            let recovery = match self.on_error {
+                OnError::Abort => break,
+                OnError::CenterAll => {
+                    self.write_angle(leg, self.calibration[leg.index()].center_deg)
+                }
+                OnError::DetachAll => self.detach_leg(leg),
+            };
+            if let Err(recovery_err) = recovery {
+                log_warn!(
+                    target: self.log_target,
+                    "Recovery failed for {}: {}",
+                    leg.name(),
+                    recovery_err
+                );
+            }
+        }
+        Err(err)
+    }
+
+    /// Run `f` with `timing` in effect, then put the configured timing back
+    ///
+    /// The settings are restored whether or not `f` fails.
+    pub(crate) fn with_timing<R>(
+        &mut self,
+        timing: Timing,
+        f: impl FnOnce(&mut Self) -> Result<R>,
+    ) -> Result<R> {
+        let (motion_limits, settle_time_ms, gait_lead_in_ms) = (
+            self.motion_limits,
+            self.settle_time_ms,
+            self.gait_lead_in_ms,
+        );
+        self.motion_limits = timing.motion_limits.unwrap_or(motion_limits);
+        self.settle_time_ms = timing.settle_time_ms.unwrap_or(settle_time_ms);
+        self.gait_lead_in_ms = timing.lead_in_ms.unwrap_or(gait_lead_in_ms);
+        let result = f(self);
+        self.motion_limits = motion_limits;
+        self.settle_time_ms = settle_time_ms;
+        self.gait_lead_in_ms = gait_lead_in_ms;
+        result
+    }
+
+    /// Finish a setter: recover from a failed write, or wait the settle time
+    fn finish_move(&mut self, result: Result<()>) -> Result<()> {
+        self.recover_on_error(result)?;
+        if self.settle_time_ms > 0 {
+            self.delay_ms(self.settle_time_ms);
+        }
+        Ok(())
+    }
+
+    /// Set a single leg to the given angle
+    pub fn set_leg_angle(&mut self, leg: Leg, angle: u32) -> Result<()> {
+        if !self.admit_command(self.now(), &[(leg, angle)]) {
+            return Ok(());
+        }
+        let result = self.write_angle(leg, angle);
+        self.finish_move(result)
+    }
+
+    /// Stop driving a leg so it relaxes; the next write re-attaches it
+    ///
+    /// The channel is disabled, or parked at the pulse set with
+    /// `set_detach_pulse_us`.
+    pub fn detach_leg(&mut self, leg: Leg) -> Result<()> {
+        if !self.enabled[leg.index()] {
+            return Ok(());
+        }
+
+        let channel = self.channel_index(leg);
+        match self.detach_pulse_us {
+            None => self.legs[channel].disable()?,
+            Some(pulse_us) => {
+                if self.halted {
+                    return Err(ServoError::Halted.into());
+                }
+                let max_duty = self.max_duty(leg)?;
+                let period = u64::from(period_us(self.actual_frequency_hz));
+                let duty = (u64::from(pulse_us) * u64::from(max_duty) / period)
+                    .min(u64::from(max_duty)) as u32;
+                self.legs[channel].set_duty(duty)?;
+                self.duties[leg.index()] = Some(duty);
+            }
+        }
+        self.detached[leg.index()] = true;
+        // Centering later would re-attach the leg
+        self.last_activity = None;
+        log_debug!(target: self.log_target, "{} detached", leg.name());
+        Ok(())
+    }
+
+    /// Stop driving every leg
+    pub fn detach_all(&mut self) -> Result<()> {
+        self.for_each_leg(Self::detach_leg)
+    }
+
+    /// Run `f` on every leg in `Leg::ALL` order, stopping at the first error
+    ///
+    /// ```rust,ignore
+    /// controller.for_each_leg(|controller, leg| controller.set_leg_angle(leg, 90))?;
+    /// ```
+    pub fn for_each_leg(&mut self, mut f: impl FnMut(&mut Self, Leg) -> Result<()>) -> Result<()> {
+        for leg in Leg::ALL {
+            f(self, leg)?;
+        }
+        Ok(())
+    }
+
+    /// Hard stop: cut the pulse on every channel at once and halt
+    ///
+    /// Writes a duty of 0 to all four channels, including disabled legs,
+    /// without any intermediate motion, so the servos stop holding and go
+    /// limp wherever they are. Unlike `detach_all` the controller then refuses
+    /// every movement with `ServoError::Halted` until `clear_halt`. Tracked
+    /// angles are left as last commanded. Every channel is attempted even if
+    /// one fails; the first failure is returned.
+    pub fn kill(&mut self) -> Result<()> {
+        self.halted = true;
+        log_error!(target: self.log_target, "Kill: cutting drive to all servos");
+
+        let mut result = Ok(());
+        for leg in LEGS {
+            match self.legs[self.channel_index(leg)].set_duty(0) {
+                Ok(()) => self.duties[leg.index()] = Some(0),
+                Err(err) => {
+                    log_error!(
+                        target: self.log_target,
+                        "Could not cut drive to {}: {}",
+                        leg.name(),
+                        err
+                    );
+                    if result.is_ok() {
+                        result = Err(err);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Accept commands again after `kill`
+    ///
+    /// Servos stay limp until the next command drives them.
+    pub fn clear_halt(&mut self) {
+        self.halted = false;
+        log_info!(target: self.log_target, "Halt cleared");
+    }
+
+    /// Whether `kill` has been called without a matching `clear_halt`
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Record that a reset was detected, e.g. the chip restarted and this
+    /// controller was rebuilt from saved state
+    ///
+    /// The tracked angles may no longer match where the servos are, so every
+    /// movement fails with `ServoError::StateUnknown` until `resync` drives
+    /// the hardware to a known position again. Detaching is still allowed.
+    pub fn mark_state_unknown(&mut self) {
+        self.state_unknown = true;
+        log_warn!(target: self.log_target, "Servo state unknown; resync required");
+    }
+
+    /// Whether `mark_state_unknown` has been called without a successful
+    /// `resync`
+    pub fn is_state_unknown(&self) -> bool {
+        self.state_unknown
+    }
+
+    /// Drive every enabled leg to a known position and accept commands again
+    ///
+    /// Legs written since the controller was built are re-commanded to their
+    /// tracked angle, ignoring the deadband; legs never written are centered.
+    /// If a write fails the state stays unknown and the error is returned.
+    pub fn resync(&mut self) -> Result<()> {
+        self.state_unknown = false;
+        let mut moved = 0;
+        for leg in LEGS {
+            let i = leg.index();
+            if !self.enabled[i] {
+                continue;
+            }
+            let angle = match self.duties[i] {
+                Some(_) => self.angles[i],
+                None => self.calibration[i].center_deg,
+            };
+            self.pace_wave(moved);
+            let result = self
+                .duty_for(leg, angle)
+                .and_then(|duty| self.write_duty(leg, duty));
+            if let Err(err) = result {
+                self.state_unknown = true;
+                return Err(err);
+            }
+            self.track_angle(leg, angle);
+            moved += 1;
+        }
+        log_info!(target: self.log_target, "Servo state resynced");
+        Ok(())
+    }
+
+    /// Logical angle that `leg` is driven to by a raw `duty`
+    ///
+    /// Undoes the leg's pulse range, correction curve, trim and inversion, e.g.
+    /// to restore tracked state from saved duties. Duties past a leg's end
+    /// stops clamp to the nearest reachable angle; a channel with a max duty
+    /// of 0 gives 0°.
+    pub fn angle_from_duty(&self, leg: Leg, duty: u32) -> u32 {
+        let calibration = &self.calibration[leg.index()];
+        let max_duty = self.legs[self.channel_index(leg)].get_max_duty();
+        let physical =
+            calibration.angle_from_duty(duty, max_duty, period_us(self.actual_frequency_hz));
+        calibration.logical_angle(self.curves[leg.index()].invert(physical))
+    }
+
+    /// Duty currently configured in a leg's channel, read back from hardware
+    pub fn read_duty(&self, leg: Leg) -> u32 {
+        self.legs[self.channel_index(leg)].get_duty()
+    }
+
+    /// Check every channel's hardware duty against the last duty written
+    ///
+    /// Catches writes that reported success but didn't take. Legs that were
+    /// never written or are detached are skipped. On mismatch, returns each
+    /// affected leg with its expected and actual duty.
+    pub fn verify_duties(&self) -> std::result::Result<(), Vec<(Leg, u32, u32)>> {
+        let mismatches: Vec<(Leg, u32, u32)> = LEGS
+            .into_iter()
+            .filter(|leg| !self.detached[leg.index()])
+            .filter_map(|leg| {
+                let expected = self.duties[leg.index()]?;
+                let actual = self.read_duty(leg);
+                (actual != expected).then_some((leg, expected, actual))
+            })
+            .collect();
+
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(mismatches)
+        }
+    }
+
+    /// Whether a leg is currently detached
+    pub fn is_detached(&self, leg: Leg) -> bool {
+        self.detached[leg.index()]
+    }
+
+    /// Remap a leg's commanded angles through `curve` before driving it
+    ///
+    /// Tracked angles (`current_angle`) stay the commanded, uncorrected values.
+    pub fn set_correction_curve(&mut self, leg: Leg, curve: CorrectionCurve) {
+        self.curves[leg.index()] = curve;
+    }
+
+    /// Correction curve applied to a leg
+    pub fn correction_curve(&self, leg: Leg) -> &CorrectionCurve {
+        &self.curves[leg.index()]
+    }
+
+    /// Set a leg's pulse range, trim, inversion and center
+    pub fn set_calibration(&mut self, leg: Leg, calibration: LegCalibration) {
+        self.calibration[leg.index()] = calibration;
+    }
+
+    /// Calibration of a leg
+    pub fn calibration(&self, leg: Leg) -> LegCalibration {
+        self.calibration[leg.index()]
+    }
+
+    /// Snapshot every tunable setting
+    pub fn config(&self) -> ControllerConfig {
+        ControllerConfig {
+            calibration: self.calibration,
+            curves: self.curves.clone(),
+            motion_limits: self.motion_limits,
+            enabled: self.enabled,
+            on_error: self.on_error,
+            clamp_mode: self.clamp_mode,
+            settle_time_ms: self.settle_time_ms,
+            deadband_deg: self.deadband_deg,
+            detach_pulse_us: self.detach_pulse_us,
+            soft_limits: self.soft_limits,
+            payload_offset: self.payload_offset,
+            wiring: self.wiring,
+            max_concurrent_moves: self.max_concurrent_moves,
+            stagger_ms: self.stagger_ms,
+            retry_attempts: self.retry_attempts,
+            retry_delay_ms: self.retry_delay_ms,
+            park_pose: self.park_pose,
+            max_command_rate_hz: self.max_command_rate_hz,
+            rate_limit_policy: self.rate_limit_policy,
+            control_interval: self.control_interval,
+            auto_center_after: self.auto_center_after,
+            current_model: self.current_model,
+            forbidden_zones: self.forbidden_zones.clone(),
+            quantize: self.quantize,
+            max_gait_duration: self.max_gait_duration,
+            gait_lead_in_ms: self.gait_lead_in_ms,
+            write_strategy: self.write_strategy,
+        }
+    }
+
+    /// Restore every tunable setting from a snapshot
+    ///
+    /// Nothing is moved; the new calibration takes effect on the next write.
+    /// An invalid wiring map is ignored.
+    pub fn apply_config(&mut self, config: &ControllerConfig) {
+        self.calibration = config.calibration;
+        self.curves = config.curves.clone();
+        self.motion_limits = config.motion_limits;
+        self.enabled = config.enabled;
+        self.on_error = config.on_error;
+        self.clamp_mode = config.clamp_mode;
+        self.settle_time_ms = config.settle_time_ms;
+        self.deadband_deg = config.deadband_deg;
+        self.detach_pulse_us = config.detach_pulse_us;
+        for (leg, (min_deg, max_deg)) in LEGS.into_iter().zip(config.soft_limits) {
+            self.set_soft_limits(leg, min_deg, max_deg);
+        }
+        self.payload_offset = config.payload_offset;
+        if self.set_wiring(config.wiring).is_err() {
+            log_warn!(
+                target: self.log_target,
+                "Ignoring invalid wiring {:?}",
+                config.wiring
+            );
+        }
+        self.set_max_concurrent_moves(config.max_concurrent_moves);
+        self.stagger_ms = config.stagger_ms;
+        self.set_retry_policy(config.retry_attempts, config.retry_delay_ms);
+        self.set_park_pose(config.park_pose);
+        self.set_max_command_rate(config.max_command_rate_hz);
+        self.rate_limit_policy = config.rate_limit_policy;
+        self.control_interval = config.control_interval;
+        self.auto_center_after = config.auto_center_after;
+        self.current_model = config.current_model;
+        for (leg, zones) in LEGS.into_iter().zip(&config.forbidden_zones) {
+            self.clear_forbidden_zones(leg);
+            for &(lo, hi) in zones {
+                self.add_forbidden_zone(leg, lo, hi);
+            }
+        }
+        self.quantize = config.quantize;
+        self.max_gait_duration = config.max_gait_duration;
+        self.gait_lead_in_ms = config.gait_lead_in_ms;
+        self.write_strategy = config.write_strategy;
+    }
+
+    /// Use `power` to lower the CPU clock while asleep
+    pub fn set_power_control(&mut self, power: Box<dyn PowerControl + Send>) {
+        self.power = Some(power);
+    }
+
+    /// Park the robot: detach every servo and lower the CPU clock if possible
+    ///
+    /// Clock changes are best-effort; a failure is logged and the servos stay
+    /// detached.
+    pub fn enter_sleep(&mut self) -> Result<()> {
+        self.detach_all()?;
+        if let Some(power) = &mut self.power
+            && let Err(err) = power.reduce_clock()
+        {
+            log_warn!(target: self.log_target, "Could not reduce CPU clock: {}", err);
+        }
+        self.sleeping = true;
+        log_info!(target: self.log_target, "Entered sleep mode");
+        Ok(())
+    }
+
+    /// Restore the CPU clock, then re-attach and center every servo
+    pub fn wake(&mut self) -> Result<()> {
+        if let Some(power) = &mut self.power
+            && let Err(err) = power.restore_clock()
+        {
+            log_warn!(target: self.log_target, "Could not restore CPU clock: {}", err);
+        }
+        self.sleeping = false;
+        self.center_all_servos()?;
+        log_info!(target: self.log_target, "Woke from sleep mode");
+        Ok(())
+    }
+
+    /// Whether the robot is in sleep mode
+    pub fn is_sleeping(&self) -> bool {
+        self.sleeping
+    }
+
+    /// Move a leg to `target` following a trapezoidal velocity profile
+    ///
+    /// The leg accelerates up to the configured max speed, cruises, then
+    /// decelerates to a stop at the target. One position is written every
+    /// `SMOOTH_STEP_MS`.
+    pub fn move_leg_smooth(&mut self, leg: Leg, target: u32) -> Result<()> {
+        let target = self.quantize_angle(leg, target);
+        if self.should_write(leg, target) {
+            self.check_path(leg, target)?;
+        }
+        let profile = TrapezoidalProfile::new(
+            self.current_angle(leg),
+            target,
+            self.motion_limits.max_speed_dps,
+            self.motion_limits.max_accel_dps2,
+        );
+
+        log_debug!(
+            target: self.log_target,
+            "Smooth move of {} to {} degrees over {} ms",
+            leg.name(),
+            target.min(180),
+            profile.duration_ms()
+        );
+
+        for angle in profile.steps(SMOOTH_STEP_MS) {
+            let result = self.write_angle(leg, angle);
+            self.recover_on_error(result)?;
+            self.delay_ms(SMOOTH_STEP_MS);
+        }
+        self.finish_move(Ok(()))
+    }
+
+    /// `move_leg_smooth` with `timing` overriding the configured limits and
+    /// settle time for this move only
+    pub fn move_leg_smooth_with(&mut self, leg: Leg, target: u32, timing: Timing) -> Result<()> {
+        self.with_timing(timing, |controller| controller.move_leg_smooth(leg, target))
+    }
+
+    /// Calculate duty values with `DefaultDutyBackend` and apply them sequentially
+    ///
+    /// `angles` is in `set_servo_angles` order. The hardware writes happen on the
+    /// calling thread to avoid conflicts.
+    fn apply_parallel(&mut self, angles: [u32; 4]) -> Result<()> {
+        let angles = LEGS.map(|leg| self.quantize_angle(leg, angles[leg.index()]));
+
+        // Reject the whole batch before writing if any leg is blocked
+        for leg in LEGS {
+            if self.should_write(leg, angles[leg.index()]) {
+                self.check_path(leg, angles[leg.index()])?;
+            }
+        }
+
+        // Prepare servo operations for the enabled legs
+        let operations = LEGS
+            .iter()
+            .filter(|leg| self.enabled[leg.index()])
+            .map(|&leg| {
+                let op = ServoOperation {
+                    angle: self.physical_angle(leg, angles[leg.index()])?,
+                    max_duty: self.max_duty(leg)?,
+                    servo_name: leg.name().to_string(),
+                };
+                Ok((op, self.calibration[leg.index()]))
+            })
+            .collect::<Result<Vec<(ServoOperation, LegCalibration)>>>()?;
+
+        let period = period_us(self.actual_frequency_hz);
+        let duties =
+            DefaultDutyBackend::default().compute_duties(operations, period, self.log_target)?;
+
+        // Apply calculated duties to servos sequentially (hardware operations),
+        // in waves of at most `max_concurrent_moves` legs
+        let mut moved = 0;
+        for leg in LEGS {
+            if !self.should_write(leg, angles[leg.index()]) {
+                continue;
+            }
+            self.pace_wave(moved);
+            self.write_duty(leg, duties[leg.name()])?;
+            self.track_angle(leg, angles[leg.index()]);
+            moved += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Set all servos to the same angle using parallel calculation
+    ///
+    /// This function calculates duty values in parallel threads, then applies them
+    /// sequentially to avoid hardware conflicts.
+    pub fn set_all_servos_angle(&mut self, angle: u32) -> Result<()> {
+        if !self.admit_command(self.now(), &LEGS.map(|leg| (leg, angle))) {
+            return Ok(());
+        }
+        let result = self.apply_parallel([angle; 4]);
+        self.finish_move(result)?;
+
+        log_info!(
+            target: self.log_target,
+            "All servos set to {} degrees using parallel calculation",
+            angle
+        );
+        Ok(())
+    }
+
+    /// Set individual servo angles using parallel calculation
+    pub fn set_servo_angles(
+        &mut self,
+        right_back: u32,
+        left_back: u32,
+        right_front: u32,
+        left_front: u32,
+    ) -> Result<()> {
+        let angles = [right_back, left_back, right_front, left_front];
+        if !self.admit_command(self.now(), &LEGS.map(|leg| (leg, angles[leg.index()]))) {
+            return Ok(());
+        }
+        let result = self.apply_parallel(angles);
+        self.finish_move(result)?;
+
+        log_debug!(target: self.log_target, "Individual servos set using parallel calculation");
+        Ok(())
+    }
+
+    /// Set individual servo angles, all or nothing
+    ///
+    /// Like `set_servo_angles`, but if a leg's write fails the legs already
+    /// written are put back to their previous duty (and detached again if they
+    /// were detached), so the robot isn't left in a half-applied pose. The
+    /// rollback replaces the `OnError` recovery. It is best-effort: its own
+    /// failures are logged, and the original error is returned either way.
+    pub fn set_servo_angles_atomic(
+        &mut self,
+        right_back: u32,
+        left_back: u32,
+        right_front: u32,
+        left_front: u32,
+    ) -> Result<()> {
+        let angles = [right_back, left_back, right_front, left_front];
+        if !self.admit_command(self.now(), &LEGS.map(|leg| (leg, angles[leg.index()]))) {
+            return Ok(());
+        }
+        let (prior_angles, prior_duties, prior_detached) =
+            (self.angles, self.duties, self.detached);
+
+        let Err(err) = self.apply_parallel(angles) else {
+            return self.finish_move(Ok(()));
+        };
+        for leg in LEGS {
+            let i = leg.index();
+            if self.duties[i] == prior_duties[i] {
+                continue;
+            }
+            let restore = match prior_duties[i] {
+                Some(duty) => Ok(duty),
+                None => self.duty_for(leg, prior_angles[i]),
+            };
+            let mut result = restore.and_then(|duty| self.write_duty(leg, duty));
+            if prior_detached[i] {
+                result = result.and_then(|()| self.detach_leg(leg));
+            }
+            match result {
+                Ok(()) => self.track_angle(leg, prior_angles[i]),
+                Err(rollback_err) => log_warn!(
+                    target: self.log_target,
+                    "Rollback failed for {}: {}",
+                    leg.name(),
+                    rollback_err
+                ),
+            }
+        }
+        log_error!(target: self.log_target, "Pose not applied, rolled back: {}", err);
+        Err(err)
+    }
+
+    /// Set individual servo angles in radians (0..=π, clamped)
+    ///
+    /// NaN or infinite inputs fail with `ServoError::InvalidAngle` before any
+    /// leg moves.
+    pub fn set_servo_angles_rad(
+        &mut self,
+        right_back: f32,
+        left_back: f32,
+        right_front: f32,
+        left_front: f32,
+    ) -> Result<()> {
+        let radians = [right_back, left_back, right_front, left_front];
+        for leg in LEGS {
+            let value = radians[leg.index()];
+            if !value.is_finite() {
+                return Err(ServoError::InvalidAngle {
+                    leg,
+                    degrees: value.to_degrees(),
+                }
+                .into());
+            }
+        }
+
+        self.set_servo_angles(
+            radians_to_degrees(right_back),
+            radians_to_degrees(left_back),
+            radians_to_degrees(right_front),
+            radians_to_degrees(left_front),
+        )
+    }
+
+    /// Store a target angle for `tick_control` to apply; nothing is written
+    ///
+    /// Cheap enough to call at any input rate: a newer target for the same
+    /// leg replaces one not yet applied.
+    pub fn submit_target(&mut self, leg: Leg, angle: u32) {
+        self.targets[leg.index()] = Some(angle);
+        self.last_activity = Some(self.clock.now());
+    }
+
+    /// Center the legs once they have been idle for `timeout`
+    ///
+    /// Keeps the robot tidy between actions instead of leaving it in the last
+    /// frame of a gait. The check runs in `tick_control`, so the control loop
+    /// must keep calling it. Every write and `submit_target` restarts the
+    /// wait; detaching a leg cancels it until the next write. `None` (the
+    /// default) turns it off.
+    pub fn auto_center_after(&mut self, timeout: Option<Duration>) {
+        self.auto_center_after = timeout;
+    }
+
+    /// Apply the latest submitted targets, at most once per control interval
+    ///
+    /// Call this from the control loop as often as convenient. Returns `true`
+    /// if targets were written at `now`; `false` if none were pending or the
+    /// interval set with `set_control_rate_hz` has not passed since the last
+    /// write. Legs without a new target hold their angle. Pending targets are
+    /// consumed even if a write fails. No settle time is waited, so a
+    /// real-time loop isn't blocked.
+    ///
+    /// With no targets pending, this is also where `auto_center_after` centers
+    /// idle legs, which returns `true` as well.
+    pub fn tick_control(&mut self, now: Instant) -> Result<bool> {
+        if let Some(last) = self.last_control_tick
+            && now.saturating_duration_since(last) < self.control_interval
+        {
+            return Ok(false);
+        }
+
+        let writes: Vec<(Leg, u32)> = LEGS
+            .into_iter()
+            .filter_map(|leg| Some((leg, self.targets[leg.index()].take()?)))
+            .collect();
+        if writes.is_empty() {
+            return self.tick_auto_center(now);
+        }
+
+        self.last_control_tick = Some(now);
+        let result = self.write_batch(&writes);
+        self.recover_on_error(result)?;
+        Ok(true)
+    }
+
+    /// Center every leg if `auto_center_after` has passed since the last
+    /// activity; `true` if it did
+    fn tick_auto_center(&mut self, now: Instant) -> Result<bool> {
+        let (Some(timeout), Some(last)) = (self.auto_center_after, self.last_activity) else {
+            return Ok(false);
+        };
+        if now.saturating_duration_since(last) < timeout {
+            return Ok(false);
+        }
+
+        log_debug!(target: self.log_target, "Idle for {:?}, centering", timeout);
+        let writes = LEGS.map(|leg| (leg, self.calibration[leg.index()].center_deg));
+        let result = self.write_batch(&writes);
+        self.last_activity = None;
+        self.last_control_tick = Some(now);
+        self.recover_on_error(result)?;
+        Ok(true)
+    }
+
+    /// Set all four legs from a slice in `set_servo_angles` order
+    ///
+    /// Unlike the other setters, angles are not clamped: the slice must contain
+    /// exactly four angles, each within 0-180°, or nothing is moved.
+    pub fn set_from_slice(&mut self, angles: &[u32]) -> Result<()> {
+        let angles: [u32; 4] = angles.try_into().map_err(|_| ServoError::InvalidLength {
+            expected: LEGS.len(),
+            actual: angles.len(),
+        })?;
+
+        for leg in LEGS {
+            let angle = angles[leg.index()];
+            if angle > 180 {
+                return Err(ServoError::InvalidAngle {
+                    leg,
+                    degrees: angle as f32,
+                }
+                .into());
+            }
+        }
+
+        let [right_back, left_back, right_front, left_front] = angles;
+        self.set_servo_angles(right_back, left_back, right_front, left_front)
+    }
+
+    /// Store `angles` (in `set_servo_angles` order) under `name`
+    ///
+    /// Redefining an existing name replaces it. At most `MAX_POSES` distinct
+    /// names can be stored.
+    pub fn define_pose(&mut self, name: &str, angles: [u32; 4]) -> Result<()> {
+        if let Some((_, stored)) = self.poses.iter_mut().find(|(n, _)| n == name) {
+            *stored = angles;
+            return Ok(());
+        }
+        if self.poses.len() == MAX_POSES {
+            return Err(ServoError::PoseLimitReached { max: MAX_POSES }.into());
+        }
+
+        self.poses.push((name.to_string(), angles));
+        Ok(())
+    }
+
+    /// Angles stored under `name`, if defined
+    pub fn pose(&self, name: &str) -> Option<[u32; 4]> {
+        self.poses
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|&(_, angles)| angles)
+    }
+
+    /// Move all legs to the pose stored under `name`
+    pub fn goto_pose(&mut self, name: &str) -> Result<()> {
+        let [right_back, left_back, right_front, left_front] = self
+            .pose(name)
+            .ok_or_else(|| ServoError::UnknownPose(name.to_string()))?;
+        self.set_servo_angles(right_back, left_back, right_front, left_front)
+    }
+
+    /// Move all legs to the blend of two poses (see `blend_pose`)
+    pub fn set_blended_pose(&mut self, a: [u32; 4], b: [u32; 4], t: f32) -> Result<()> {
+        let [right_back, left_back, right_front, left_front] = blend_pose(a, b, t);
+        self.set_servo_angles(right_back, left_back, right_front, left_front)
+    }
+
+    /// Move all legs to the blend of the poses stored under `a` and `b`
+    pub fn goto_blended_pose(&mut self, a: &str, b: &str, t: f32) -> Result<()> {
+        let lookup = |name: &str| {
+            self.pose(name)
+                .ok_or_else(|| ServoError::UnknownPose(name.to_string()))
+        };
+        let (a, b) = (lookup(a)?, lookup(b)?);
+        self.set_blended_pose(a, b, t)
+    }
+
+    /// Set two legs at once, through the rate limiter
+    fn set_pair(&mut self, writes: [(Leg, u32); 2]) -> Result<()> {
+        if !self.admit_command(self.now(), &writes) {
+            return Ok(());
+        }
+        let result = self.write_batch(&writes);
+        self.finish_move(result)
+    }
+
+    /// Set right side servos to specific angles
+    pub fn set_right_servos(&mut self, back_angle: u32, front_angle: u32) -> Result<()> {
+        self.set_pair([(Leg::RightBack, back_angle), (Leg::RightFront, front_angle)])
+    }
+
+    /// Set left side servos to specific angles
+    pub fn set_left_servos(&mut self, back_angle: u32, front_angle: u32) -> Result<()> {
+        self.set_pair([(Leg::LeftBack, back_angle), (Leg::LeftFront, front_angle)])
+    }
+
+    /// Set front servos to specific angles
+    pub fn set_front_servos(&mut self, right_angle: u32, left_angle: u32) -> Result<()> {
+        self.set_pair([(Leg::RightFront, right_angle), (Leg::LeftFront, left_angle)])
+    }
+
+    /// Set back servos to specific angles
+    pub fn set_back_servos(&mut self, right_angle: u32, left_angle: u32) -> Result<()> {
+        self.set_pair([(Leg::RightBack, right_angle), (Leg::LeftBack, left_angle)])
+    }
+
+    /// Center all servos to 90 degrees
+    ///
+    /// Each leg goes to its calibrated `center_deg` (90° unless calibrated).
+    pub fn center_all_servos(&mut self) -> Result<()> {
+        let [right_back, left_back, right_front, left_front] =
+            self.calibration.map(|calibration| calibration.center_deg);
+        self.set_servo_angles(right_back, left_back, right_front, left_front)
+    }
+
+    /// Get max duty values for debugging
+    pub fn log_max_duties(&self) {
+        log_info!(
+            target: self.log_target,
+            "Max duty values - right_back_leg: {}, left_back_leg: {}, right_front_leg: {}, left_front_leg: {}",
+            self.legs[self.channel_index(Leg::RightBack)].get_max_duty(),
+            self.legs[self.channel_index(Leg::LeftBack)].get_max_duty(),
+            self.legs[self.channel_index(Leg::RightFront)].get_max_duty(),
+            self.legs[self.channel_index(Leg::LeftFront)].get_max_duty()
+        );
+    }
+
+    /// Perform a walking motion pattern with parallel servo control
+    pub fn walk_forward(&mut self, delay: impl Into<Ms>) -> Result<()> {
+        let delay_ms = delay.into().0;
+        log_info!(
+            target: self.log_target,
+            "Starting walk forward pattern with parallel servo control"
+        );
+
+        // Lift and reach with the right legs, then the left, then center
+        self.play_sequence(&gaits::walk(gaits::DEFAULT_STEP_DEG, Ms(delay_ms)))
+    }
+
+    /// Walk forward along a curve for `cycles` cycles
+    ///
+    /// `turn_bias` (-100..=100, clamped) shortens one side's steps: positive
+    /// values shorten the right steps and arc to the right, negative values
+    /// the left (see `gaits::arc_steps`). Each frame is followed by
+    /// `delay`.
+    pub fn walk_arc(&mut self, delay: impl Into<Ms>, turn_bias: i32, cycles: u32) -> Result<()> {
+        let delay_ms = delay.into().0;
+        log_info!(
+            target: self.log_target,
+            "Starting arc walk for {} cycles (turn bias {})",
+            cycles,
+            turn_bias
+        );
+
+        let gait = gaits::walk_arc(gaits::DEFAULT_STEP_DEG, turn_bias, Ms(delay_ms));
+        for _ in 0..cycles {
+            self.play_sequence(&gait)?;
+        }
+        Ok(())
+    }
+
+    /// Walk forward `total_cycles` times, easing in over `warmup_cycles`
+    ///
+    /// Starting at full step height from cold can stress the mechanism, so
+    /// the first `warmup_cycles` walks (counted within `total_cycles`) swing
+    /// the legs less, ramping up to the full step (see `gaits::warmup_step`).
+    pub fn walk_forward_warmup(
+        &mut self,
+        delay: impl Into<Ms>,
+        warmup_cycles: u32,
+        total_cycles: u32,
+    ) -> Result<()> {
+        let delay_ms = delay.into().0;
+        log_info!(
+            target: self.log_target,
+            "Walking forward {} times, warming up over {}",
+            total_cycles,
+            warmup_cycles
+        );
+
+        for cycle in 0..total_cycles {
+            let step_deg = gaits::warmup_step(gaits::DEFAULT_STEP_DEG, cycle, warmup_cycles);
+            self.play_sequence(&gaits::walk(step_deg, Ms(delay_ms)))?;
+        }
+        Ok(())
+    }
+
+    /// Walk forward until `total` of wall-clock time has passed
+    ///
+    /// Loops the walk gait, checking the controller's clock before each frame, so the
+    /// frame in progress always finishes (dwell included) and the walk ends at
+    /// most one frame past `total`. The legs are left in the last frame
+    /// played. The first frame always plays, even when `total` is shorter
+    /// than a frame; a `total` of 0 does nothing. A `total` beyond the
+    /// `set_max_gait_duration` cutoff is cut short there.
+    pub fn walk_forward_for(&mut self, delay: impl Into<Ms>, total: impl Into<Ms>) -> Result<()> {
+        let total = Duration::from(total.into());
+        log_info!(
+            target: self.log_target,
+            "Walking forward for {} ms",
+            total.as_millis()
+        );
+
+        let clock = self.clock();
+        let start = clock.now();
+        self.walk_forward_while(delay, || {
+            clock.now().saturating_duration_since(start) < total
+        })?;
+        Ok(())
+    }
+
+    /// Walk forward for as long as `keep_walking` returns `true`
+    ///
+    /// Loops the walk gait, calling `keep_walking` before each frame, so the
+    /// frame in progress always finishes. Whatever it returns, the walk also
+    /// stops before the next frame once it has run for the maximum set with
+    /// `set_max_gait_duration`, so a predicate that never turns false cannot
+    /// walk the robot off the table. The legs are left in the last frame
+    /// played. Returns `true` if the cutoff stopped the walk.
+    pub fn walk_forward_while(
+        &mut self,
+        delay: impl Into<Ms>,
+        mut keep_walking: impl FnMut() -> bool,
+    ) -> Result<bool> {
+        let delay_ms = delay.into().0;
+        let start = self.clock.now();
+        let gait = gaits::walk(gaits::DEFAULT_STEP_DEG, Ms(delay_ms));
+        for frame in gait.frames().iter().cycle() {
+            if self.gait_cutoff_reached(start) {
+                return Ok(true);
+            }
+            if !keep_walking() {
+                break;
+            }
+            let [right_back, left_back, right_front, left_front] = frame.angles;
+            self.set_servo_angles(right_back, left_back, right_front, left_front)?;
+            self.delay_ms(frame.dwell_ms);
+        }
+        Ok(false)
+    }
+
+    /// Stop open-ended movements after `max` regardless of their own
+    /// condition, measured on the controller's clock
+    ///
+    /// Applies to `walk_forward_while`, `walk_forward_for` and `demo_reel`,
+    /// which check it before each frame or segment. Defaults to
+    /// `DEFAULT_MAX_GAIT_DURATION`; `None` lets them run for as long as they
+    /// ask to.
+    pub fn set_max_gait_duration(&mut self, max: Option<Duration>) {
+        self.max_gait_duration = max;
+    }
+
+    /// Cutoff set with `set_max_gait_duration`
+    pub fn max_gait_duration(&self) -> Option<Duration> {
+        self.max_gait_duration
+    }
+
+    /// Whether a movement started at `start` has reached the gait cutoff
+    fn gait_cutoff_reached(&self, start: Instant) -> bool {
+        let Some(max) = self.max_gait_duration else {
+            return false;
+        };
+        let reached = self.clock.now().saturating_duration_since(start) >= max;
+        if reached {
+            log_warn!(
+                target: self.log_target,
+                "Stopping movement at the {} ms cutoff",
+                max.as_millis()
+            );
+        }
+        reached
+    }
+
+    /// Walk forward for `cycles` cycles keeping the body level
+    ///
+    /// Plays `gaits::walk_level`; see there for how the stance legs compensate
+    /// for the swinging pair. Each frame is followed by `delay`.
+    pub fn walk_level(&mut self, delay: impl Into<Ms>, cycles: u32) -> Result<()> {
+        let delay_ms = delay.into().0;
+        log_info!(target: self.log_target, "Starting level walk for {} cycles", cycles);
+
+        let gait = gaits::walk_level(gaits::DEFAULT_STEP_DEG, Ms(delay_ms));
+        for _ in 0..cycles {
+            self.play_sequence(&gait)?;
+        }
+        Ok(())
+    }
+
+    /// Creep forward for `cycles` cycles, one leg at a time
+    ///
+    /// Plays `gaits::creep`: the legs step in `gaits::CREEP_ORDER` (right
+    /// front, left back, left front, right back) with the other three planted,
+    /// for maximum stability under load. Each frame is followed by `delay`.
+    pub fn creep(&mut self, delay: impl Into<Ms>, cycles: u32) -> Result<()> {
+        let delay_ms = delay.into().0;
+        log_info!(target: self.log_target, "Starting creep for {} cycles", cycles);
+
+        let gait = gaits::creep(gaits::DEFAULT_STEP_DEG, Ms(delay_ms));
+        for _ in 0..cycles {
+            self.play_sequence(&gait)?;
+        }
+        Ok(())
+    }
+
+    /// March in place for `cycles` cycles to warm up the servos
+    ///
+    /// Plays `gaits::march_in_place`: the legs lift and lower in
+    /// `gaits::MARCH_ORDER` (right front, right back, left back, left front)
+    /// with the other three planted, without walking anywhere. Each frame is
+    /// followed by `delay`.
+    pub fn march_in_place(&mut self, delay: impl Into<Ms>, cycles: u32) -> Result<()> {
+        let delay_ms = delay.into().0;
+        log_info!(target: self.log_target, "Starting march in place for {} cycles", cycles);
+
+        let gait = gaits::march_in_place(gaits::DEFAULT_STEP_DEG, Ms(delay_ms));
+        for _ in 0..cycles {
+            self.play_sequence(&gait)?;
+        }
+        Ok(())
+    }
+
+    /// Walk forward once, stopping early if an obstacle comes within `stop_cm`
+    ///
+    /// `source` is checked before each frame of the walk gait. When the
+    /// distance is below `stop_cm` the legs are centered and the walk ends.
+    /// A missing reading counts as clear. Returns `true` if an obstacle
+    /// stopped the walk.
+    pub fn walk_forward_with_obstacle(
+        &mut self,
+        delay: impl Into<Ms>,
+        stop_cm: u32,
+        source: &mut dyn ObstacleSource,
+    ) -> Result<bool> {
+        let delay_ms = delay.into().0;
+        for frame in gaits::walk(gaits::DEFAULT_STEP_DEG, Ms(delay_ms)).frames() {
+            if let Some(distance) = source.distance_cm()
+                && distance < stop_cm
+            {
+                log_info!(
+                    target: self.log_target,
+                    "Obstacle at {} cm (limit {} cm), stopping",
+                    distance,
+                    stop_cm
+                );
+                self.center_all_servos()?;
+                return Ok(true);
+            }
+
+            let [right_back, left_back, right_front, left_front] = frame.angles;
+            self.set_servo_angles(right_back, left_back, right_front, left_front)?;
+            self.delay_ms(frame.dwell_ms);
+        }
+        Ok(false)
+    }
+
+    /// Sweep one leg back and forth between two angles
+    ///
+    /// Each cycle sweeps `low` → `high` then `high` → `low` in increments of
+    /// `step` degrees, waiting `delay` after every position. Bounds are
+    /// swapped if `low > high` and clamped to 180°; a `step` of 0 is treated
+    /// as 1. `cycles == 0` does nothing.
+    pub fn oscillate(
+        &mut self,
+        leg: Leg,
+        low: u32,
+        high: u32,
+        step: u32,
+        delay: impl Into<Ms>,
+        cycles: u32,
+    ) -> Result<()> {
+        let delay_ms = delay.into().0;
+        let sweep = oscillation_sweep(low, high, step);
+
+        for _ in 0..cycles {
+            for &angle in &sweep {
+                self.set_leg_angle(leg, angle)?;
+                self.delay_ms(delay_ms);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sweep the two sides as mirror images, then center
+    ///
+    /// The right legs sweep 0° → 180° → 0° in increments of `step` degrees
+    /// (0 is treated as 1) while the left legs move the opposite way, so each
+    /// left leg is always at `180 - right`. Waits `delay` after every
+    /// position and centers all legs at the end.
+    pub fn mirror_sweep(&mut self, step: u32, delay: impl Into<Ms>) -> Result<()> {
+        let delay_ms = delay.into().0;
+        log_info!(target: self.log_target, "Starting mirror sweep");
+
+        for right in oscillation_sweep(0, 180, step) {
+            let left = 180 - right;
+            self.set_servo_angles(right, left, right, left)?;
+            self.delay_ms(delay_ms);
+        }
+        self.center_all_servos()
+    }
+
+    /// Perform a simple wave motion with the front right leg
+    pub fn wave(&mut self, delay: impl Into<Ms>) -> Result<()> {
+        log_info!(target: self.log_target, "Starting wave motion");
+
+        self.oscillate(Leg::RightFront, 0, 180, 10, delay, 1)?;
+
+        // Return to center
+        self.center_all_servos()?;
+        Ok(())
+    }
+
+    /// Bound: the front pair moves, then the back pair
+    ///
+    /// Left and right legs of a pair are in phase; the front and back pairs are
+    /// half a cycle apart, so one pair holds while the other lifts and plants.
+    /// Each frame of `BOUND_FRAMES` is followed by `delay`.
+    pub fn bound(&mut self, delay: impl Into<Ms>, cycles: u32) -> Result<()> {
+        let delay_ms = delay.into().0;
+        log_info!(target: self.log_target, "Starting bound for {} cycles", cycles);
+
+        for _ in 0..cycles {
+            for &(pair, angle) in &BOUND_FRAMES {
+                match pair {
+                    LegPair::Front => self.set_front_servos(angle, angle)?,
+                    LegPair::Back => self.set_back_servos(angle, angle)?,
+                }
+                self.delay_ms(delay_ms);
+            }
+        }
+        Ok(())
+    }
+
+    /// Pronk: all four legs lift and plant together
+    ///
+    /// Every leg is in phase. Each frame of `PRONK_FRAMES` is followed by
+    /// `delay`.
+    pub fn pronk(&mut self, delay: impl Into<Ms>, cycles: u32) -> Result<()> {
+        let delay_ms = delay.into().0;
+        log_info!(target: self.log_target, "Starting pronk for {} cycles", cycles);
+
+        for _ in 0..cycles {
+            for &angle in &PRONK_FRAMES {
+                self.set_all_servos_angle(angle)?;
+                self.delay_ms(delay_ms);
+            }
+        }
+        Ok(())
+    }
+
+    /// Wander: move to `steps` pseudo-random poses within `WANDER_RANGE`
+    ///
+    /// The poses come from `wander_poses`, so the same seed always produces
+    /// the same movements. Each pose is followed by `delay`.
+    pub fn wander(&mut self, seed: u32, steps: u32, delay: impl Into<Ms>) -> Result<()> {
+        let delay_ms = delay.into().0;
+        log_info!(target: self.log_target, "Wandering for {} steps (seed {})", steps, seed);
+
+        for [right_back, left_back, right_front, left_front] in wander_poses(seed, steps) {
+            self.set_servo_angles(right_back, left_back, right_front, left_front)?;
+            self.delay_ms(delay_ms);
+        }
+        Ok(())
+    }
+
+    /// Swim: every leg oscillates around center, each a fixed phase behind
+    /// the previous one
+    ///
+    /// Angles come from `swim_angles`, sampled every `SMOOTH_STEP_MS` for
+    /// `cycles` periods of `period`; legs are centered at the end.
+    /// `amplitude` is clamped to 90°. `cycles == 0` does nothing.
+    pub fn swim(
+        &mut self,
+        period: impl Into<Ms>,
+        amplitude: u32,
+        phase_step_deg: u32,
+        cycles: u32,
+    ) -> Result<()> {
+        let period_ms = period.into().0;
+        if cycles == 0 {
+            return Ok(());
+        }
+        log_info!(
+            target: self.log_target,
+            "Swimming for {} cycles ({} ms period, ±{}°)",
+            cycles,
+            period_ms,
+            amplitude.min(90)
+        );
+
+        let samples = (period_ms / SMOOTH_STEP_MS).max(1);
+        let sample_ms = period_ms / samples;
+        for _ in 0..cycles {
+            for sample in 0..samples {
+                let t_ms = sample * sample_ms;
+                let [right_back, left_back, right_front, left_front] =
+                    swim_angles(Ms(t_ms), Ms(period_ms), amplitude, phase_step_deg);
+                self.set_servo_angles(right_back, left_back, right_front, left_front)?;
+                self.delay_ms(sample_ms);
+            }
+        }
+        self.center_all_servos()
+    }
+
+    /// Get up after a fall, if `upside_down` says the robot is on its back
+    ///
+    /// `upside_down` comes from an external tilt sensor. When set, plays
+    /// `recovery_gait(Ms(RECOVERY_DWELL_MS))` once and returns `true`; otherwise
+    /// nothing moves and `false` is returned. With one joint per leg this is
+    /// best-effort: call again with a fresh reading to see whether it worked.
+    pub fn recover(&mut self, upside_down: bool) -> Result<bool> {
+        if !upside_down {
+            return Ok(false);
+        }
+
+        log_warn!(target: self.log_target, "Upside down, running recovery");
+        self.play_sequence(&recovery_gait(Ms(RECOVERY_DWELL_MS)))?;
+        Ok(true)
+    }
+
+    /// Set the folded posture `park_flat` moves to, in `set_servo_angles`
+    /// order (default `PARK_FLAT_POSE`)
+    pub fn set_park_pose(&mut self, angles: [u32; 4]) {
+        self.park_pose = angles.map(|angle| angle.min(180));
+    }
+
+    /// Posture set with `set_park_pose`
+    pub fn park_pose(&self) -> [u32; 4] {
+        self.park_pose
+    }
+
+    /// Fold the legs flat for transport, then detach them
+    ///
+    /// All legs move together from their tracked angles to the park pose,
+    /// blended over `duration` with one frame every `SMOOTH_STEP_MS`. The
+    /// legs are then detached so they stay relaxed (and can be pushed flat)
+    /// while packed; the next command re-attaches them.
+    pub fn park_flat(&mut self, duration: impl Into<Ms>) -> Result<()> {
+        let duration_ms = duration.into().0;
+        log_info!(
+            target: self.log_target,
+            "Parking legs at {:?} over {} ms",
+            self.park_pose,
+            duration_ms
+        );
+
+        let (from, to) = (self.current_angles(), self.park_pose);
+        let steps = (duration_ms / SMOOTH_STEP_MS).max(1);
+        for step in 1..=steps {
+            self.set_blended_pose(from, to, step as f32 / steps as f32)?;
+            self.delay_ms(duration_ms / steps);
+        }
+        self.detach_all()
+    }
+
+    /// Play `DEMO_REEL` in a loop, each segment for `each`
+    ///
+    /// Runs until a write fails, returning that error, or until the cutoff
+    /// set with `set_max_gait_duration` is reached at the end of a segment.
+    pub fn demo_reel(&mut self, each: impl Into<Ms>) -> Result<()> {
+        let each = each.into();
+        log_info!(target: self.log_target, "Starting demo reel ({} ms per segment)", each.0);
+        let start = self.clock.now();
+        loop {
+            for segment in DEMO_REEL {
+                if self.gait_cutoff_reached(start) {
+                    return Ok(());
+                }
+                self.play_demo_reel(&[segment], each)?;
+            }
+        }
+    }
+
+    /// Play each segment of `reel` once, for `each` apiece
+    ///
+    /// Postures are held for the whole segment. Gaits use `DEFAULT_STEP_DEG`
+    /// and `DEMO_DWELL_MS`, looping as needed; the last frame is cut short at
+    /// the end of the segment.
+    pub fn play_demo_reel(&mut self, reel: &[DemoSegment], each: impl Into<Ms>) -> Result<()> {
+        let each_ms = each.into().0;
+        for &segment in reel {
+            log_debug!(target: self.log_target, "Demo segment: {:?}", segment);
+            let gait = match segment {
+                DemoSegment::Center => {
+                    self.center_all_servos()?;
+                    self.delay_ms(each_ms);
+                    continue;
+                }
+                DemoSegment::Sit => posture(SIT_POSE, each_ms),
+                DemoSegment::Stand => posture(STAND_POSE, each_ms),
+                DemoSegment::Wave => gaits::wave(gaits::DEFAULT_STEP_DEG, Ms(DEMO_DWELL_MS)),
+                DemoSegment::Walk => gaits::walk(gaits::DEFAULT_STEP_DEG, Ms(DEMO_DWELL_MS)),
+                DemoSegment::Trot => gaits::trot(gaits::DEFAULT_STEP_DEG, Ms(DEMO_DWELL_MS)),
+            };
+            self.play_for(&gait, each_ms)?;
+        }
+        Ok(())
+    }
+
+    /// Loop `gait` for `duration_ms`, cutting the last frame short
+    fn play_for(&mut self, gait: &Gait, duration_ms: u32) -> Result<()> {
+        if gait.duration_ms() == 0 {
+            self.delay_ms(duration_ms);
+            return Ok(());
+        }
+
+        let mut elapsed_ms = 0;
+        for frame in gait.frames().iter().cycle() {
+            if elapsed_ms >= duration_ms {
+                break;
+            }
+            let [right_back, left_back, right_front, left_front] = frame.angles;
+            self.set_servo_angles(right_back, left_back, right_front, left_front)?;
+            let hold_ms = frame.dwell_ms.min(duration_ms - elapsed_ms);
+            self.delay_ms(hold_ms);
+            elapsed_ms += hold_ms;
+        }
+        Ok(())
+    }
+
+    /// Limp: crawl one cycle on three legs when `dead_leg` has failed
+    ///
+    /// The dead leg is centered and detached so it neither pushes nor drags.
+    /// The three live legs then step one at a time (each lifts, swings forward
+    /// and plants via `step_leg`) while the other two hold, so the robot always
+    /// rests on a tripod of the live pair plus the parked leg.
+    pub fn limp_gait(&mut self, dead_leg: Leg, delay: impl Into<Ms>) -> Result<()> {
+        let delay = delay.into();
+        log_info!(
+            target: self.log_target,
+            "Limping with {} parked",
+            dead_leg.name()
+        );
+
+        let center = self.calibration[dead_leg.index()].center_deg;
+        self.set_leg_angle(dead_leg, center)?;
+        self.detach_leg(dead_leg)?;
+
+        for leg in LEGS.into_iter().filter(|&leg| leg != dead_leg) {
+            self.step_leg(leg, gaits::DEFAULT_STEP_DEG, gaits::DEFAULT_STEP_DEG, delay)?;
+        }
+        Ok(())
+    }
+
+    /// Take one deliberate high-clearance step with a single leg
+    ///
+    /// Relative to the leg's current angle, the step lifts by `lift_deg`, swings
+    /// forward to `forward_deg` past it, then plants back at the starting angle
+    /// (the same lift/forward/down convention as `walk_forward`). Each phase is
+    /// followed by `delay`; computed angles are clamped to 0-180° and the
+    /// other legs are not written.
+    pub fn step_leg(
+        &mut self,
+        leg: Leg,
+        lift_deg: u32,
+        forward_deg: u32,
+        delay: impl Into<Ms>,
+    ) -> Result<()> {
+        let delay_ms = delay.into().0;
+        let plant = self.current_angle(leg);
+        let lift = plant.saturating_sub(lift_deg);
+        let swing = plant.saturating_add(forward_deg).min(180);
+
+        log_info!(
+            target: self.log_target,
+            "Stepping {} (lift: {}, swing: {})",
+            leg.name(),
+            lift,
+            swing
+        );
+
+        for angle in [lift, swing, plant] {
+            self.set_leg_angle(leg, angle)?;
+            self.delay_ms(delay_ms);
+        }
+        Ok(())
+    }
+}
+
+/// Front or back pair of legs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LegPair {
+    Front,
+    Back,
+}
+
+/// One `bound` cycle: the pair that moves and the angle both its legs take
+const BOUND_FRAMES: [(LegPair, u32); 4] = [
+    (LegPair::Front, 45),
+    (LegPair::Front, 90),
+    (LegPair::Back, 45),
+    (LegPair::Back, 90),
+];
+
+/// One `pronk` cycle: the angle every leg takes
+const PRONK_FRAMES: [u32; 2] = [45, 90];
+
+/// Dwell of each `recovery_gait` frame used by `recover`
+pub const RECOVERY_DWELL_MS: u32 = 400;
+
+/// Pushes `recover` uses to roll the robot off its back, in
+/// `set_servo_angles` order
+///
+/// On its back the legs point up, so only a leg swung far to one side reaches
+/// the ground. The right legs fold out of the way while the left legs brace,
+/// the left legs then kick over to roll the body onto its right side, and the
+/// right legs sweep through to tip it the rest of the way before every leg
+/// returns to center to stand.
+pub const RECOVERY_FRAMES: [[u32; 4]; 5] = [
+    // Gather
+    [90, 90, 90, 90],
+    // Right legs fold back, left legs brace against the ground
+    [180, 0, 180, 0],
+    // Left legs kick over, rolling the body onto its right side
+    [180, 180, 180, 180],
+    // Right legs sweep through to tip the body upright
+    [0, 180, 0, 180],
+    // Stand
+    [90, 90, 90, 90],
+];
+
+/// A single-frame gait holding `angles` for `dwell_ms`
+fn posture(angles: [u32; 4], dwell_ms: u32) -> Gait {
+    Gait::new(vec![Pose::new(angles, dwell_ms)])
+}
+
+/// `RECOVERY_FRAMES` as a gait, each frame held for `dwell`
+pub fn recovery_gait(dwell: impl Into<Ms>) -> Gait {
+    let dwell_ms = dwell.into().0;
+    Gait::new(
+        RECOVERY_FRAMES
+            .iter()
+            .map(|&angles| Pose::new(angles, dwell_ms))
+            .collect(),
+    )
+}
+
+/// Sitting posture: back legs folded, front legs upright
+pub const SIT_POSE: [u32; 4] = [0, 0, 90, 90];
+
+/// Standing posture: every leg upright
+pub const STAND_POSE: [u32; 4] = [90; 4];
+
+/// Transport posture used by `park_flat` unless changed with `set_park_pose`:
+/// back legs folded back (0°) and front legs folded forward (180°), so every
+/// leg lies along the body
+pub const PARK_FLAT_POSE: [u32; 4] = [0, 0, 180, 180];
+
+/// Dwell of each gait frame in a demo reel
+pub const DEMO_DWELL_MS: u32 = 250;
+
+/// One segment of a demo reel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DemoSegment {
+    /// Hold every leg at its calibrated center
+    Center,
+    /// Play `gaits::wave`
+    Wave,
+    /// Play `gaits::walk`
+    Walk,
+    /// Play `gaits::trot`
+    Trot,
+    /// Hold `SIT_POSE`
+    Sit,
+    /// Hold `STAND_POSE`
+    Stand,
+}
+
+/// Segments `demo_reel` plays, in order
+///
+/// Copy and reorder it to build a custom reel for `play_demo_reel`.
+pub const DEMO_REEL: [DemoSegment; 7] = [
+    DemoSegment::Center,
+    DemoSegment::Wave,
+    DemoSegment::Walk,
+    DemoSegment::Trot,
+    DemoSegment::Sit,
+    DemoSegment::Stand,
+    DemoSegment::Center,
+];
+
+/// Angles `wander` picks from, kept near center to stay stable
+pub const WANDER_RANGE: std::ops::RangeInclusive<u32> = 60..=120;
+
+/// Poses visited by `wander`, in `set_servo_angles` order
+///
+/// Uses a xorshift32 generator seeded with `seed` (0 is replaced by a fixed
+/// non-zero seed, since xorshift would otherwise only produce zeros).
+pub fn wander_poses(seed: u32, steps: u32) -> Vec<[u32; 4]> {
+    let mut state = if seed == 0 { 0x9E37_79B9 } else { seed };
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state
+    };
+
+    let low = *WANDER_RANGE.start();
+    let span = WANDER_RANGE.end() - low + 1;
+    (0..steps)
+        .map(|_| [(); 4].map(|()| low + next() % span))
+        .collect()
+}
+
+/// Sine of the phase `t_ms` into a `period_ms` cycle, delayed by `lag_deg`
+///
+/// A `period_ms` of 0 samples the start of the cycle.
+pub fn sine_sample(t_ms: u32, period_ms: u32, lag_deg: f32) -> f32 {
+    let phase = if period_ms == 0 {
+        0.0
+    } else {
+        (t_ms % period_ms) as f32 / period_ms as f32 * 360.0
+    };
+    (phase - lag_deg).to_radians().sin()
+}
+
+/// Leg angles `swim` commands `t` into a cycle of `period`, in
+/// `set_servo_angles` order
+///
+/// Each leg swings `amplitude` degrees (clamped to 90°) either side of center
+/// and lags the previous leg by `phase_step_deg`, rounded to the nearest
+/// degree.
+pub fn swim_angles(
+    t: impl Into<Ms>,
+    period: impl Into<Ms>,
+    amplitude: u32,
+    phase_step_deg: u32,
+) -> [u32; 4] {
+    let (t_ms, period_ms) = (t.into().0, period.into().0);
+    let amplitude = amplitude.min(90) as f32;
+    std::array::from_fn(|i| {
+        let lag_deg = (i as u32 * (phase_step_deg % 360)) as f32;
+        (90.0 + amplitude * sine_sample(t_ms, period_ms, lag_deg)).round() as u32
+    })
+}
+
+/// Weighted average of two poses: `a` at `t = 0`, `b` at `t = 1`
+///
+/// `t` is clamped to 0..=1 (NaN counts as 0) and each angle is rounded to the
+/// nearest degree. Input angles are clamped to 180°.
+pub fn blend_pose(a: [u32; 4], b: [u32; 4], t: f32) -> [u32; 4] {
+    let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+    std::array::from_fn(|i| {
+        let (a, b) = (a[i].min(180) as f32, b[i].min(180) as f32);
+        (a + (b - a) * t).round() as u32
+    })
+}
+
+/// Angles visited by one `oscillate` cycle: an up sweep followed by a down sweep
+pub fn oscillation_sweep(low: u32, high: u32, step: u32) -> Vec<u32> {
+    let (low, high) = if low > high { (high, low) } else { (low, high) };
+    let (low, high) = (low.min(180), high.min(180));
+    let step = step.max(1) as usize;
+
+    (low..=high)
+        .step_by(step)
+        .chain((low..=high).rev().step_by(step))
+        .collect()
+}
+
+// ================================================================================================
+// MOVEMENT SEQUENCES
+// ================================================================================================
+
+/// A single step of a `MovementBuilder` sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovementStep {
+    /// Set one leg to an angle
+    Angle(Leg, u32),
+    /// Set all legs to the same angle
+    All(u32),
+    /// Set each leg individually, in `set_servo_angles` order
+    Angles([u32; 4]),
+    /// Center all legs
+    Center,
+    /// Wait for the given number of milliseconds
+    Delay(u32),
+}
+
+/// Fluent builder for chaining movements into a sequence
+///
+/// Steps are executed in the order they were declared. Execution stops at the
+/// first failing step and returns its error.
+///
+/// ```rust,ignore
+/// MovementBuilder::new()
+///     .angle(Leg::RightFront, 45)
+///     .delay(300)
+///     .all(90)
+///     .run(&mut servo_controller)?;
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MovementBuilder {
+    steps: Vec<MovementStep>,
+}
+
+impl MovementBuilder {
+    /// Create an empty sequence
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set one leg to an angle
+    pub fn angle(mut self, leg: Leg, angle: u32) -> Self {
+        self.steps.push(MovementStep::Angle(leg, angle));
+        self
+    }
+
+    /// Set all legs to the same angle
+    pub fn all(mut self, angle: u32) -> Self {
+        self.steps.push(MovementStep::All(angle));
+        self
+    }
+
+    /// Set each leg individually
+    pub fn angles(
+        mut self,
+        right_back: u32,
+        left_back: u32,
+        right_front: u32,
+        left_front: u32,
+    ) -> Self {
+        self.steps.push(MovementStep::Angles([
+            right_back,
+            left_back,
+            right_front,
+            left_front,
+        ]));
+        self
+    }
+
+    /// Center all legs
+    pub fn center(mut self) -> Self {
+        self.steps.push(MovementStep::Center);
+        self
+    }
+
+    /// Wait before the next step
+    pub fn delay(mut self, delay: impl Into<Ms>) -> Self {
+        self.steps.push(MovementStep::Delay(delay.into().0));
+        self
+    }
+
+    /// Steps in execution order
+    pub fn steps(&self) -> &[MovementStep] {
+        &self.steps
+    }
+
+    /// Execute the sequence on a controller, stopping at the first error
+    pub fn run<P: PwmBackend, D: Delay>(
+        &self,
+        controller: &mut ServoController<P, D>,
+    ) -> Result<()> {
+        for step in &self.steps {
+            match *step {
+                MovementStep::Angle(leg, angle) => controller.set_leg_angle(leg, angle)?,
+                MovementStep::All(angle) => controller.set_all_servos_angle(angle)?,
+                MovementStep::Angles([rb, lb, rf, lf]) => {
+                    controller.set_servo_angles(rb, lb, rf, lf)?
+                }
+                MovementStep::Center => controller.center_all_servos()?,
+                MovementStep::Delay(ms) => controller.delay_ms(ms),
+            }
+        }
+        Ok(())
+    }
+}
+
+// ================================================================================================
+// HARDWARE SETUP FUNCTIONS
+// ================================================================================================
+
+/// Set up servo motors and return a ServoController
+///
+/// The legs are driven on LEDC channels 0-3 at GPIO 23 (right back), 22
+/// (left back), 19 (right front) and 18 (left front). Pins are taken as
+/// `esp-idf-hal` peripheral singletons rather than numbers, so one GPIO cannot
+/// be handed to two legs: the second use fails to compile.
+#[cfg(feature = "esp32")]
+pub fn setup_servos(peripherals: Peripherals) -> Result<EspServoController> {
+    log_info!(
+        target: DEFAULT_LOG_TARGET,
+        "Setting up servo motors with parallel control capability"
+    );
+
+    // LEDC Timer configuration
+    let timer_config = TimerConfig::default()
+        .frequency(Hertz(FREQUENCY_HZ))
+        .resolution(esp_idf_hal::ledc::Resolution::Bits10);
+
+    let timer = LedcTimerDriver::new(peripherals.ledc.timer0, &timer_config)?;
+    let actual_frequency_hz = timer.get_freq().0;
+    log_info!(
+        target: DEFAULT_LOG_TARGET,
+        "LEDC timer running at {} Hz (requested {} Hz)",
+        actual_frequency_hz,
+        FREQUENCY_HZ
+    );
+
+    // Create LEDC drivers for each servo
+    let right_back_leg =
+        LedcDriver::new(peripherals.ledc.channel0, &timer, peripherals.pins.gpio23)?;
+
+    let left_back_leg =
+        LedcDriver::new(peripherals.ledc.channel1, &timer, peripherals.pins.gpio22)?;
+
+    let right_front_leg =
+        LedcDriver::new(peripherals.ledc.channel2, &timer, peripherals.pins.gpio19)?;
+
+    let left_front_leg =
+        LedcDriver::new(peripherals.ledc.channel3, &timer, peripherals.pins.gpio18)?;
+
+    let servo_controller = ServoController::new(
+        right_back_leg,
+        left_back_leg,
+        right_front_leg,
+        left_front_leg,
+        FreeRtosDelay,
+    )
+    .with_actual_frequency_hz(actual_frequency_hz);
+
+    servo_controller.log_max_duties();
+    servo_controller.check_timer_config()?;
+    log_info!(
+        target: DEFAULT_LOG_TARGET,
+        "Servo controller initialized with parallel execution support"
+    );
+
+    Ok(servo_controller)
+}
+
+/// Set up servo motors, keeping the legs whose driver could be created
+///
+/// Like `setup_servos`, but a failed `LedcDriver::new` doesn't abort the
+/// setup: that leg is disabled and reported in the returned list (see
+/// `setup_with_factory`). Useful for bench setups with fewer than four servos.
+/// A timer that fails to start is still an error.
+#[cfg(feature = "esp32")]
+pub fn setup_servos_partial(
+    peripherals: Peripherals,
+) -> Result<(PartialEspServoController, Vec<Leg>)> {
+    let timer_config = TimerConfig::default()
+        .frequency(Hertz(FREQUENCY_HZ))
+        .resolution(esp_idf_hal::ledc::Resolution::Bits10);
+
+    let timer = LedcTimerDriver::new(peripherals.ledc.timer0, &timer_config)?;
+    let actual_frequency_hz = timer.get_freq().0;
+
+    // In `Leg::index` order
+    let mut drivers = [
+        LedcDriver::new(peripherals.ledc.channel0, &timer, peripherals.pins.gpio23),
+        LedcDriver::new(peripherals.ledc.channel1, &timer, peripherals.pins.gpio22),
+        LedcDriver::new(peripherals.ledc.channel2, &timer, peripherals.pins.gpio19),
+        LedcDriver::new(peripherals.ledc.channel3, &timer, peripherals.pins.gpio18),
+    ]
+    .map(Some);
+
+    let (servo_controller, failed) = setup_with_factory(FreeRtosDelay, |leg| {
+        let driver = drivers[leg.index()].take().expect("each leg is built once");
+        Ok(driver?)
+    });
+    let servo_controller = servo_controller.with_actual_frequency_hz(actual_frequency_hz);
+
+    servo_controller.log_max_duties();
+    servo_controller.check_timer_config()?;
+    Ok((servo_controller, failed))
+}
+
+/// Set up only the servos of `legs`, e.g. `[Leg::RightFront, Leg::LeftFront]`
+///
+/// For robots with one to three servos, or boards where some of the four
+/// LEDC channels are taken: each leg keeps its usual channel and pin, and the
+/// channels of the other legs are left untouched. The other legs are disabled
+/// (see `setup_with_legs`). A leg listed twice is `ServoError::InvalidWiring`.
+#[cfg(feature = "esp32")]
+pub fn setup_servos_for_legs<const N: usize>(
+    peripherals: Peripherals,
+    legs: [Leg; N],
+) -> Result<PartialEspServoController> {
+    let timer_config = TimerConfig::default()
+        .frequency(Hertz(FREQUENCY_HZ))
+        .resolution(esp_idf_hal::ledc::Resolution::Bits10);
+
+    let ledc = peripherals.ledc;
+    let pins = peripherals.pins;
+    let timer = LedcTimerDriver::new(ledc.timer0, &timer_config)?;
+    let actual_frequency_hz = timer.get_freq().0;
+
+    let mut channels = Vec::with_capacity(N);
+    if legs.contains(&Leg::RightBack) {
+        let driver = LedcDriver::new(ledc.channel0, &timer, pins.gpio23)?;
+        channels.push((Leg::RightBack, driver));
+    }
+    if legs.contains(&Leg::LeftBack) {
+        let driver = LedcDriver::new(ledc.channel1, &timer, pins.gpio22)?;
+        channels.push((Leg::LeftBack, driver));
+    }
+    if legs.contains(&Leg::RightFront) {
+        let driver = LedcDriver::new(ledc.channel2, &timer, pins.gpio19)?;
+        channels.push((Leg::RightFront, driver));
+    }
+    if legs.contains(&Leg::LeftFront) {
+        let driver = LedcDriver::new(ledc.channel3, &timer, pins.gpio18)?;
+        channels.push((Leg::LeftFront, driver));
+    }
+    if channels.len() != N {
+        return Err(ServoError::InvalidWiring.into());
+    }
+
+    let servo_controller =
+        setup_with_legs(channels, FreeRtosDelay)?.with_actual_frequency_hz(actual_frequency_hz);
+    servo_controller.log_max_duties();
+    servo_controller.check_timer_config()?;
+    log_info!(
+        target: DEFAULT_LOG_TARGET,
+        "Servo controller initialized with {} of 4 legs",
+        N
+    );
+    Ok(servo_controller)
+}
+
+/// Build a controller from a per-leg driver factory, tolerating failures
+///
+/// `make_channel` is called once per leg in `LEGS` order. Legs whose channel
+/// fails to build get an `OptionalChannel::Missing` slot and are disabled;
+/// they are returned (with their errors logged) so the caller can report them.
+pub fn setup_with_factory<P: PwmBackend, D: Delay>(
+    delay: D,
+    mut make_channel: impl FnMut(Leg) -> Result<P>,
+) -> (ServoController<OptionalChannel<P>, D>, Vec<Leg>) {
+    let mut failed = Vec::new();
+    let [right_back, left_back, right_front, left_front] =
+        LEGS.map(|leg| match make_channel(leg) {
+            Ok(channel) => OptionalChannel::Ready(channel),
+            Err(err) => {
+                log_error!(
+                    target: DEFAULT_LOG_TARGET,
+                    "Could not set up {}: {}",
+                    leg.name(),
+                    err
+                );
+                failed.push(leg);
+                OptionalChannel::Missing
+            }
+        });
+
+    let mut servo_controller =
+        ServoController::new(right_back, left_back, right_front, left_front, delay);
+    for &leg in &failed {
+        servo_controller.set_leg_enabled(leg, false);
+    }
+    (servo_controller, failed)
+}
+
+/// Build a controller that drives only the legs given a channel
+///
+/// Every other leg gets an `OptionalChannel::Missing` slot and is disabled,
+/// so the usual setters work unchanged: angles for the missing legs are
+/// accepted and skipped. A leg given two channels is
+/// `ServoError::InvalidWiring`.
+pub fn setup_with_legs<P: PwmBackend, D: Delay>(
+    channels: impl IntoIterator<Item = (Leg, P)>,
+    delay: D,
+) -> Result<ServoController<OptionalChannel<P>, D>> {
+    let mut slots = LEGS.map(|_| OptionalChannel::Missing);
+    for (leg, channel) in channels {
+        let slot = &mut slots[leg.index()];
+        if matches!(slot, OptionalChannel::Ready(_)) {
+            return Err(ServoError::InvalidWiring.into());
+        }
+        *slot = OptionalChannel::Ready(channel);
+    }
+
+    let present = LEGS.map(|leg| matches!(slots[leg.index()], OptionalChannel::Ready(_)));
+    let [right_back, left_back, right_front, left_front] = slots;
+    let mut servo_controller =
+        ServoController::new(right_back, left_back, right_front, left_front, delay);
+    for leg in LEGS {
+        servo_controller.set_leg_enabled(leg, present[leg.index()]);
+    }
+    Ok(servo_controller)
+}
+
+/// Set up servo motors and run a timed center health check
+///
+/// The stock LEDC hardware has no position feedback, so the check only confirms
+/// that centering succeeds; use `ServoController::health_check` directly to
+/// verify positions with a `PositionFeedback` source.
+#[cfg(feature = "esp32")]
+pub fn setup_servos_with_health_check(
+    peripherals: Peripherals,
+    settle_ms: u32,
+) -> Result<(EspServoController, HealthReport)> {
+    let mut servo_controller = setup_servos(peripherals)?;
+    let report = servo_controller.health_check(settle_ms, 0, None)?;
+    Ok((servo_controller, report))
+}
+
+/// Demonstrate servo movements with parallel control
+pub fn demo_servo_movements<P: PwmBackend, D: Delay>(
+    servo_controller: &mut ServoController<P, D>,
+) -> Result<()> {
+    let log_target = servo_controller.log_target();
+    log_info!(target: log_target, "Starting servo demonstration with parallel control...");
+
+    // Set all servos to 180 degrees
+    servo_controller.set_all_servos_angle(180)?;
+    log_info!(target: log_target, "All servos set to 180 degrees (parallel execution)");
+    servo_controller.delay_ms(1000);
+
+    // Set all servos to 90 degrees
+    servo_controller.set_all_servos_angle(90)?;
+    log_info!(target: log_target, "All servos set to 90 degrees (parallel execution)");
+    servo_controller.delay_ms(1000);
+
+    // Set all servos to 0 degrees
+    servo_controller.set_all_servos_angle(0)?;
+    log_info!(target: log_target, "All servos set to 0 degrees (parallel execution)");
+    servo_controller.delay_ms(1000);
+
+    // Test individual leg control
+    log_info!(target: log_target, "Testing individual leg movements with parallel calculation...");
+    servo_controller.set_servo_angles(45, 135, 135, 45)?;
+    log_info!(target: log_target, "Diagonal movement pattern (parallel execution)");
+    servo_controller.delay_ms(1000);
+
+    // Test side movements
+    log_info!(target: log_target, "Testing side movements with parallel calculation...");
+    servo_controller.set_right_servos(45, 45)?;
+    servo_controller.delay_ms(500);
+    servo_controller.set_left_servos(135, 135)?;
+    servo_controller.delay_ms(500);
+
+    // Return to center position
+    servo_controller.center_all_servos()?;
+    log_info!(target: log_target, "Servos centered to 90 degrees (parallel execution)");
+
+    log_info!(target: log_target, "Servo demonstration with parallel control complete");
+    Ok(())
+}
+
+// ================================================================================================
+// TESTING MODULE
+// ================================================================================================
+
+// ## Testing Strategy
+//
+// This module includes basic embedded tests, but for comprehensive testing
+// without ESP32 dependencies, run the standalone test suite:
+//
+// ```bash
+// cargo test --features mock
+// ```
+//
+// The tests validate:
+// - Core mathematical functions (angle_to_duty, duty_to_angle, etc.)
+// - Servo operation data structures
+// - Basic functionality that can run in ESP32 environment
+//
+// For more comprehensive testing with mock hardware, see the documentation
+// on testing strategies for embedded Rust projects.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::*;
+
+    /// Test basic angle_to_duty calculation for ESP32 10-bit LEDC
+    #[test]
+    fn test_angle_to_duty_basic() {
+        let max_duty = 1024; // ESP32 10-bit default
+
+        // Test key angles; `fpu` rounds 25.6 and 76.8 to the nearest duty
+        let (zero, center) = if cfg!(feature = "fpu") {
+            (26, 77)
+        } else {
+            (25, 76)
+        };
+        assert_eq!(angle_to_duty(0, max_duty), zero); // 0°
+        assert_eq!(angle_to_duty(90, max_duty), center); // 90° (center)
+        assert_eq!(angle_to_duty(180, max_duty), 128); // 180°
+    }
+
+    /// Test that duty values are within valid range
+    #[test]
+    fn test_duty_bounds() {
+        let max_duty = 1024;
+
+        // Test all angles produce valid duty values
+        for angle in 0..=180 {
+            let duty = angle_to_duty(angle, max_duty);
+            assert!(
+                duty <= max_duty,
+                "Duty {} exceeds max_duty for angle {}",
+                duty,
+                angle
+            );
+        }
+    }
+
+    /// Test angle clamping behavior
+    #[test]
+    fn test_angle_clamping() {
+        let max_duty = 1024;
+
+        // Angles over 180 should be clamped
+        assert_eq!(angle_to_duty(200, max_duty), angle_to_duty(180, max_duty));
+        assert_eq!(angle_to_duty(999, max_duty), angle_to_duty(180, max_duty));
+    }
+
+    /// Test roundtrip conversion accuracy
+    #[test]
+    fn test_roundtrip_conversion() {
+        let max_duty = 65536; // Higher precision for better roundtrip
+
+        for angle in (0..=180).step_by(10) {
+            let duty = angle_to_duty(angle, max_duty);
+            let recovered_angle = duty_to_angle(duty, max_duty);
+            let diff = (recovered_angle as i32 - angle as i32).abs();
+
+            assert!(
+                diff <= 2,
+                "Roundtrip failed: {} → {} → {} (diff: {})",
+                angle,
+                duty,
+                recovered_angle,
+                diff
+            );
+        }
+    }
+
+    /// Test ServoOperation struct
+    #[test]
+    fn test_servo_operation() {
+        let op = ServoOperation {
+            angle: 90,
+            max_duty: 1024,
+            servo_name: "test_servo".to_string(),
+        };
+
+        assert_eq!(op.angle, 90);
+        assert_eq!(op.max_duty, 1024);
+        assert_eq!(op.servo_name, "test_servo");
+    }
+
+    /// Test that the single-threaded and std-thread backends agree
+    #[test]
+    fn test_duty_backends_match() {
+        let shifted = LegCalibration {
+            trim_deg: 7,
+            inverted: true,
+            min_pulse_us: 600,
+            ..LegCalibration::default()
+        };
+        for angle in [0, 1, 45, 90, 133, 180] {
+            let operations: Vec<_> = LEGS
+                .iter()
+                .zip([
+                    LegCalibration::default(),
+                    shifted,
+                    shifted,
+                    LegCalibration::default(),
+                ])
+                .zip([1024, 1024, 16383, 8191])
+                .map(|((leg, calibration), max_duty)| {
+                    let op = ServoOperation {
+                        angle,
+                        max_duty,
+                        servo_name: leg.name().to_string(),
+                    };
+                    (op, calibration)
+                })
+                .collect();
+
+            let single = SingleThreaded
+                .compute_duties(operations.clone(), 20_000, "test")
+                .unwrap();
+            let threaded = StdThreads
+                .compute_duties(operations, 20_000, "test")
+                .unwrap();
+
+            assert_eq!(single.len(), 4);
+            assert_eq!(single, threaded, "angle {angle}");
+        }
+    }
+
+    /// Test that a panicking calculation thread becomes an error
+    #[test]
+    fn test_calculation_panic_is_an_error() {
+        let operations: Vec<_> = LEGS
+            .iter()
+            .map(|leg| {
+                let op = ServoOperation {
+                    angle: 90,
+                    max_duty: 1024,
+                    servo_name: leg.name().to_string(),
+                };
+                (op, LegCalibration::default())
+            })
+            .collect();
+
+        let result = compute_on_threads(operations, |op, _| {
+            if op.servo_name == Leg::RightFront.name() {
+                panic!("injected calculation failure");
+            }
+            (op.servo_name, 0)
+        });
+
+        let err = result.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ServoError>(),
+            Some(&ServoError::CalculationFailed {
+                servo: "right_front_leg".to_string()
+            })
+        );
+        assert_eq!(
+            err.to_string(),
+            "duty calculation for right_front_leg failed"
+        );
+    }
+
+    /// Test that the controller and the free conversion functions share one
+    /// servo model, so an entry point using either drives the same positions
+    #[test]
+    fn test_default_calibration_matches_free_functions() {
+        let (controller, _log) = mock_controller();
+        let calibration = LegCalibration::default();
+        assert_eq!(
+            (calibration.min_pulse_us, calibration.max_pulse_us),
+            (MIN_PULSE_US, MAX_PULSE_US)
+        );
+
+        for angle in [0, 1, 45, 89, 90, 135, 179, 180] {
+            assert_eq!(calibration.pulse_us(angle), angle_to_pulse_width(angle));
+            for leg in LEGS {
+                assert_eq!(
+                    controller.duty_for(leg, angle).unwrap(),
+                    angle_to_duty(angle, MOCK_MAX_DUTY),
+                    "{}° on {}",
+                    angle,
+                    leg.name()
+                );
+            }
+        }
+    }
+
+    /// Test the integer duty model against hand-computed values
+    #[test]
+    fn test_expected_duty_table() {
+        // (angle, min_pulse_us, max_pulse_us, period_us, max_duty, expected)
+        let cases = [
+            // 50 Hz, 10-bit: 500 µs → 25.6, 1500 µs → 76.8, 2500 µs → 128
+            (0, 500, 2500, 20_000, 1024, 25),
+            (90, 500, 2500, 20_000, 1024, 76),
+            (180, 500, 2500, 20_000, 1024, 128),
+            // 50 Hz, 14-bit: 500 µs → 409.6, 1500 µs → 1228.8
+            (0, 500, 2500, 20_000, 16_384, 409),
+            (90, 500, 2500, 20_000, 16_384, 1228),
+            // 100 Hz, 10-bit: 45° → 1000 µs → 102.4, 2500 µs → 256
+            (45, 500, 2500, 10_000, 1024, 102),
+            (180, 500, 2500, 10_000, 1024, 256),
+            // 330 Hz (3030 µs period), 13-bit: 1500 * 8192 / 3030 → 4055.4
+            (90, 500, 2500, period_us(330), 8192, 4055),
+            // 1000-2000 µs servo: 0° → 51.2, 90° → 76.8
+            (0, 1000, 2000, 20_000, 1024, 51),
+            (90, 1000, 2000, 20_000, 1024, 76),
+            // Reversed pulse range: 0° → 2500 µs
+            (0, 2500, 500, 20_000, 1024, 128),
+            // Past 180° extrapolates: 198° → 2700 µs → 138.2
+            (198, 500, 2500, 20_000, 1024, 138),
+            // Pulse longer than the period saturates at max duty
+            (180, 500, 2500, 2000, 1024, 1024),
+            (90, 500, 2500, 0, 1024, 1024),
+        ];
+        for (angle, min, max, period, max_duty, expected) in cases {
+            assert_eq!(
+                expected_duty_int(angle, min, max, period, max_duty),
+                expected,
+                "{angle}° {min}-{max} µs, period {period} µs, max duty {max_duty}"
+            );
+        }
+
+        // The public conversions and calibration agree with it
+        let calibration = LegCalibration::default();
+        for angle in (0..=180).step_by(15) {
+            let duty = expected_duty(angle, MIN_PULSE_US, MAX_PULSE_US, PERIOD_US, 4096);
+            assert_eq!(angle_to_duty(angle, 4096), duty);
+            assert_eq!(calibration.duty(angle, 4096, PERIOD_US), duty);
+        }
+    }
+
+    /// Test that the f32 duty path stays within 1 LSB of the integer one
+    #[test]
+    fn test_fpu_duty_within_one_lsb() {
+        // (min_pulse_us, max_pulse_us, period_us, max_duty, max difference)
+        let configs = [
+            (500, 2500, 20_000, 1024, 1),
+            (1000, 2000, 10_000, 1024, 1),
+            (2500, 500, period_us(330), 1024, 1),
+            (600, 2400, 20_000, 8192, 1),
+            (500, 2500, 2048, 1024, 1),
+            (500, 2500, 20_000, 16_384, 1),
+            (500, 2500, 20_000, 65_536, 1),
+        ];
+        for (min, max, period, max_duty, tolerance) in configs {
+            for angle in 0..=200 {
+                let int = expected_duty_int(angle, min, max, period, max_duty);
+                let fpu = expected_duty_f32(angle, min, max, period, max_duty);
+                assert!(
+                    (int..=int + tolerance).contains(&fpu),
+                    "{angle}° {min}-{max} µs, period {period} µs: {int} vs {fpu}"
+                );
+            }
+        }
+        // Rounding instead of flooring: 76.8 → 77
+        assert_eq!(expected_duty_f32(90, 500, 2500, 20_000, 1024), 77);
+        assert_eq!(expected_duty_int(90, 500, 2500, 20_000, 1024), 76);
+    }
+
+    /// Test pulse width calculation
+    #[test]
+    fn test_pulse_width_calculation() {
+        assert_eq!(angle_to_pulse_width(0), MIN_PULSE_US);
+        assert_eq!(angle_to_pulse_width(90), 1500);
+        assert_eq!(angle_to_pulse_width(180), MAX_PULSE_US);
+    }
+
+    /// Test that the movement builder runs steps in declared order
+    #[test]
+    fn test_movement_builder_order() {
+        let (mut controller, log) = mock_controller();
+
+        MovementBuilder::new()
+            .angle(Leg::RightFront, 0)
+            .delay(Ms(100))
+            .all(180)
+            .delay(Ms(50))
+            .center()
+            .run(&mut controller)
+            .unwrap();
+
+        let mut expected = vec![
+            Command::Duty(Leg::RightFront, angle_to_duty(0, 1024)),
+            Command::Delay(100),
+        ];
+        expected.extend(
+            LEGS.iter()
+                .map(|&leg| Command::Duty(leg, angle_to_duty(180, 1024))),
+        );
+        expected.push(Command::Delay(50));
+        expected.extend(
+            LEGS.iter()
+                .map(|&leg| Command::Duty(leg, angle_to_duty(90, 1024))),
+        );
+        log.assert_commands(&expected);
+    }
+
+    /// Test that the builder stops at the first failing step
+    #[test]
+    fn test_movement_builder_stops_on_error() {
+        let (mut controller, log) = mock_controller();
+        log.fail_writes(Leg::LeftBack, 1);
+
+        let result = MovementBuilder::new()
+            .delay(Ms(10))
+            .angle(Leg::LeftBack, 45)
+            .delay(Ms(20))
+            .run(&mut controller);
+
+        assert!(result.is_err());
+        log.assert_commands(&[Command::Delay(10)]);
+    }
+
+    /// Test that a trapezoidal profile respects the acceleration limit
+    #[test]
+    fn test_trapezoidal_profile_accel_limit() {
+        let max_speed = 180.0;
+        let max_accel = 720.0;
+        let profile = TrapezoidalProfile::new(0, 180, max_speed, max_accel);
+        let dt = SMOOTH_STEP_MS as f32 / 1000.0;
+
+        // Sample exact positions and differentiate numerically
+        let samples = (profile.duration_s() / dt).ceil() as usize;
+        let positions: Vec<f32> = (0..=samples)
+            .map(|i| profile.position_at(i as f32 * dt))
+            .collect();
+        let velocities: Vec<f32> = positions.windows(2).map(|w| (w[1] - w[0]) / dt).collect();
+
+        for v in &velocities {
+            assert!(*v <= max_speed + 0.01, "Speed {} exceeds limit", v);
+        }
+        for w in velocities.windows(2) {
+            let accel = ((w[1] - w[0]) / dt).abs();
+            assert!(
+                accel <= max_accel + 0.5,
+                "Acceleration {} exceeds limit",
+                accel
+            );
+        }
+
+        // Starts and ends at rest
+        assert_eq!(profile.speed_at(0.0), 0.0);
+        assert_eq!(profile.speed_at(profile.duration_s()), 0.0);
+        assert!(velocities.last().unwrap().abs() <= max_accel * dt);
+        assert_eq!(profile.position_at(profile.duration_s()), 180.0);
+    }
+
+    /// Test that short moves fall back to a triangular profile
+    #[test]
+    fn test_trapezoidal_profile_triangular() {
+        // 180°/s at 720°/s² needs 45° to reach cruise speed; 20° never gets there
+        let profile = TrapezoidalProfile::new(100, 80, 180.0, 720.0);
+        let peak = profile.speed_at(profile.duration_s() / 2.0);
+
+        assert!(peak < 180.0);
+        assert!((profile.position_at(profile.duration_s() / 2.0) - 90.0).abs() < 0.01);
+        assert_eq!(*profile.steps(SMOOTH_STEP_MS).last().unwrap(), 80);
+    }
+
+    /// Test that an interpolated move starts, passes halfway and ends on time
+    #[test]
+    fn test_move_interpolator() {
+        for easing in [Easing::Linear, Easing::EaseInOut] {
+            let up = MoveInterpolator::new(30, 130, 600, easing);
+            assert_eq!(up.sample(0), 30);
+            assert_eq!(up.sample(300), 80);
+            assert_eq!(up.sample(600), 130);
+            assert_eq!(up.sample(10_000), 130);
+
+            let down = MoveInterpolator::new(180, 0, 1000, easing);
+            assert_eq!(
+                [0, 500, 1000].map(|t| down.sample(t)),
+                [180, 90, 0],
+                "{easing:?}"
+            );
+        }
+
+        // Easing shapes the path between the ends
+        let linear = MoveInterpolator::new(0, 100, 1000, Easing::Linear);
+        let eased = MoveInterpolator::new(0, 100, 1000, Easing::EaseIn);
+        assert_eq!(linear.sample(250), 25);
+        assert_eq!(eased.sample(250), 6);
+
+        // Zero-length and out-of-range moves
+        assert_eq!(
+            MoveInterpolator::new(10, 50, 0, Easing::Linear).sample(0),
+            50
+        );
+        assert_eq!(
+            MoveInterpolator::new(0, 500, 100, Easing::Linear).sample(100),
+            180
+        );
+    }
+
+    /// Test that a smooth move ends at the target and tracks the angle
+    #[test]
+    fn test_move_leg_smooth() {
+        let (mut controller, log) = mock_controller();
+
+        controller.move_leg_smooth(Leg::LeftFront, 180).unwrap();
+
+        let duties = log.duties_for(Leg::LeftFront);
+        assert!(duties.len() > 1);
+        assert!(duties.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(*duties.last().unwrap(), angle_to_duty(180, 1024));
+        assert!(log.commands().contains(&Command::Delay(SMOOTH_STEP_MS)));
+        assert_eq!(controller.current_angle(Leg::LeftFront), 180);
+    }
+
+    /// Test that core logic runs with logging compiled out
+    #[cfg(not(feature = "logging"))]
+    #[test]
+    fn test_core_logic_without_logging() {
+        let (mut controller, log) = mock_controller();
+
+        controller.set_servo_angles(0, 45, 135, 180).unwrap();
+        controller.walk_forward(Ms(10)).unwrap();
+
+        assert_eq!(controller.current_angles(), [90; 4]);
+        assert_eq!(
+            log.commands()[0],
+            Command::Duty(Leg::RightBack, angle_to_duty(0, MOCK_MAX_DUTY))
+        );
+    }
+
+    /// Test setting all legs from a slice
+    #[test]
+    fn test_set_from_slice() {
+        let (mut controller, _log) = mock_controller();
+
+        controller.set_from_slice(&[0, 45, 135, 180]).unwrap();
+        assert_eq!(controller.current_angles(), [0, 45, 135, 180]);
+    }
+
+    /// Test that a wrong-length slice is rejected without moving
+    #[test]
+    fn test_set_from_slice_wrong_length() {
+        let (mut controller, log) = mock_controller();
+
+        for angles in [&[90, 90, 90][..], &[90, 90, 90, 90, 90][..], &[][..]] {
+            let err = controller.set_from_slice(angles).unwrap_err();
+            assert_eq!(
+                err.downcast_ref::<ServoError>(),
+                Some(&ServoError::InvalidLength {
+                    expected: 4,
+                    actual: angles.len()
+                })
+            );
+        }
+        assert!(log.commands().is_empty());
+    }
+
+    /// Test that an out-of-range entry is rejected without moving
+    #[test]
+    fn test_set_from_slice_out_of_range() {
+        let (mut controller, log) = mock_controller();
+
+        let err = controller.set_from_slice(&[90, 90, 181, 90]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ServoError>(),
+            Some(&ServoError::InvalidAngle {
+                leg: Leg::RightFront,
+                degrees: 181.0
+            })
+        );
+        assert!(log.commands().is_empty());
+    }
+
+    /// Test the angles visited by a single oscillation cycle
+    #[test]
+    fn test_oscillation_sweep() {
+        assert_eq!(
+            oscillation_sweep(0, 40, 10),
+            vec![0, 10, 20, 30, 40, 40, 30, 20, 10, 0]
+        );
+
+        // Reversed bounds are swapped, zero step is treated as 1
+        assert_eq!(oscillation_sweep(92, 90, 0), vec![90, 91, 92, 92, 91, 90]);
+
+        // Bounds are clamped to the servo range
+        assert_eq!(
+            oscillation_sweep(170, 250, 5),
+            vec![170, 175, 180, 180, 175, 170]
+        );
+    }
+
+    /// Test that oscillate drives only the chosen leg, one delay per position
+    #[test]
+    fn test_oscillate_single_cycle() {
+        let (mut controller, log) = mock_controller();
+
+        controller
+            .oscillate(Leg::LeftBack, 0, 180, 90, Ms(15), 1)
+            .unwrap();
+
+        let expected: Vec<Command> = [0, 90, 180, 180, 90, 0]
+            .iter()
+            .flat_map(|&angle| {
+                [
+                    Command::Duty(Leg::LeftBack, angle_to_duty(angle, 1024)),
+                    Command::Delay(15),
+                ]
+            })
+            .collect();
+        log.assert_commands(&expected);
+    }
+
+    /// Test that the left side always mirrors the right side
+    #[test]
+    fn test_mirror_sweep() {
+        let (mut controller, log) = mock_controller();
+
+        controller.mirror_sweep(45, Ms(10)).unwrap();
+
+        let sweep = [0, 45, 90, 135, 180, 180, 135, 90, 45, 0];
+        let duties = |angles: &[u32]| -> Vec<u32> {
+            angles
+                .iter()
+                .map(|&a| angle_to_duty(a, 1024))
+                .chain([angle_to_duty(90, 1024)])
+                .collect()
+        };
+        let mirrored: Vec<u32> = sweep.iter().map(|&a| 180 - a).collect();
+        for leg in [Leg::RightBack, Leg::RightFront] {
+            assert_eq!(log.duties_for(leg), duties(&sweep));
+        }
+        for leg in [Leg::LeftBack, Leg::LeftFront] {
+            assert_eq!(log.duties_for(leg), duties(&mirrored));
+        }
+        assert_eq!(log.now_ms(), 10 * sweep.len() as u64);
+        assert_eq!(controller.current_angles(), [90; 4]);
+    }
+
+    /// Test that step_leg lifts, swings and plants only the chosen leg
+    #[test]
+    fn test_step_leg_phases() {
+        let (mut controller, log) = mock_controller();
+
+        controller.step_leg(Leg::LeftFront, 60, 45, Ms(30)).unwrap();
+
+        log.assert_commands(&[
+            Command::Duty(Leg::LeftFront, angle_to_duty(30, 1024)),
+            Command::Delay(30),
+            Command::Duty(Leg::LeftFront, angle_to_duty(135, 1024)),
+            Command::Delay(30),
+            Command::Duty(Leg::LeftFront, angle_to_duty(90, 1024)),
+            Command::Delay(30),
+        ]);
+        assert_eq!(controller.current_angles(), [90; 4]);
+    }
+
+    /// Test that step_leg clamps the lift and swing angles
+    #[test]
+    fn test_step_leg_clamps() {
+        let (mut controller, log) = mock_controller();
+
+        controller
+            .step_leg(Leg::RightBack, 500, 500, Ms(0))
+            .unwrap();
+
+        assert_eq!(
+            log.duties_for(Leg::RightBack),
+            vec![
+                angle_to_duty(0, 1024),
+                angle_to_duty(180, 1024),
+                angle_to_duty(90, 1024)
+            ]
+        );
+    }
+
+    /// Test that zero cycles does nothing
+    #[test]
+    fn test_oscillate_zero_cycles() {
+        let (mut controller, log) = mock_controller();
+
+        controller
+            .oscillate(Leg::LeftBack, 0, 180, 10, Ms(15), 0)
+            .unwrap();
+        assert!(log.commands().is_empty());
+    }
+
+    /// Test that the default policy returns the error without recovery
+    #[test]
+    fn test_on_error_abort() {
+        let (mut controller, log) = mock_controller();
+        log.fail_writes(Leg::RightFront, 1);
+
+        assert!(controller.set_servo_angles(0, 0, 0, 0).is_err());
+
+        // Legs before the failure moved, nothing after it
+        log.assert_commands(&[
+            Command::Duty(Leg::RightBack, angle_to_duty(0, 1024)),
+            Command::Duty(Leg::LeftBack, angle_to_duty(0, 1024)),
+        ]);
+    }
+
+    /// Test that CenterAll centers every leg after a failed write
+    #[test]
+    fn test_on_error_center_all() {
+        let (mut controller, log) = mock_controller();
+        controller.set_on_error(OnError::CenterAll);
+        log.fail_writes(Leg::LeftBack, 1);
+
+        let err = controller.set_all_servos_angle(180).unwrap_err();
+        assert!(err.to_string().contains("left_back_leg"));
+
+        log.assert_commands(&[
+            Command::Duty(Leg::RightBack, angle_to_duty(180, 1024)),
+            Command::Duty(Leg::RightBack, angle_to_duty(90, 1024)),
+            Command::Duty(Leg::LeftBack, angle_to_duty(90, 1024)),
+            Command::Duty(Leg::RightFront, angle_to_duty(90, 1024)),
+            Command::Duty(Leg::LeftFront, angle_to_duty(90, 1024)),
+        ]);
+        assert_eq!(controller.current_angles(), [90; 4]);
+    }
+
+    /// Test that DetachAll detaches every leg after a failed write
+    #[test]
+    fn test_on_error_detach_all() {
+        let (mut controller, log) = mock_controller();
+        controller.set_on_error(OnError::DetachAll);
+        log.fail_writes(Leg::LeftFront, 1);
+
+        assert!(controller.set_left_servos(45, 45).is_err());
+
+        log.assert_commands(&[
+            Command::Duty(Leg::LeftBack, angle_to_duty(45, 1024)),
+            Command::Disable(Leg::RightBack),
+            Command::Disable(Leg::LeftBack),
+            Command::Disable(Leg::RightFront),
+            Command::Disable(Leg::LeftFront),
+        ]);
+        assert!(LEGS.iter().all(|&leg| controller.is_detached(leg)));
+
+        // The next write re-attaches the leg
+        log.clear();
+        controller.set_leg_angle(Leg::LeftFront, 90).unwrap();
+        log.assert_commands(&[
+            Command::Enable(Leg::LeftFront),
+            Command::Duty(Leg::LeftFront, angle_to_duty(90, 1024)),
+        ]);
+        assert!(!controller.is_detached(Leg::LeftFront));
+    }
+
+    /// Test that the leg list and iterators follow the documented order
+    #[test]
+    fn test_leg_iteration() {
+        let visited: Vec<(usize, &str)> =
+            Leg::iter().map(|leg| (leg.index(), leg.name())).collect();
+        assert_eq!(
+            visited,
+            vec![
+                (0, "right_back_leg"),
+                (1, "left_back_leg"),
+                (2, "right_front_leg"),
+                (3, "left_front_leg"),
+            ]
+        );
+        assert_eq!(Leg::ALL.map(Leg::index), [0, 1, 2, 3]);
+
+        let (mut controller, log) = mock_controller();
+        let mut order = Vec::new();
+        controller
+            .for_each_leg(|controller, leg| {
+                order.push(leg);
+                controller.set_leg_angle(leg, 10 * (leg.index() as u32 + 1))
+            })
+            .unwrap();
+        assert_eq!(order, Leg::ALL);
+        assert_eq!(controller.current_angles(), [10, 20, 30, 40]);
+
+        // The first error stops the walk
+        log.fail_writes(Leg::LeftBack, 1);
+        let mut visited = 0;
+        let result = controller.for_each_leg(|controller, leg| {
+            visited += 1;
+            controller.set_leg_angle(leg, 90)
+        });
+        assert!(result.is_err());
+        assert_eq!(visited, 2);
+    }
+
+    /// Test both detach behaviors: disabling the channel or parking a pulse
+    #[test]
+    fn test_detach_pulse() {
+        let (mut controller, log) = mock_controller();
+        assert_eq!(controller.detach_pulse_us(), None);
+
+        controller.detach_leg(Leg::RightBack).unwrap();
+        log.assert_commands(&[Command::Disable(Leg::RightBack)]);
+
+        // 1500 µs of a 20 ms period at 1024 steps
+        log.clear();
+        controller.set_detach_pulse_us(Some(1500));
+        controller.detach_all().unwrap();
+        log.assert_duties(&LEGS.map(|leg| (leg, 76)));
+        assert!(
+            !log.commands()
+                .iter()
+                .any(|command| matches!(command, Command::Disable(_))),
+            "a parked channel keeps its pulse"
+        );
+        assert!(LEGS.iter().all(|&leg| controller.is_detached(leg)));
+
+        // Parked legs hold the pulse: nothing more is written until the next
+        // command, which re-attaches the leg
+        log.clear();
+        controller.delay_ms(100);
+        controller.set_leg_angle(Leg::LeftFront, 0).unwrap();
+        log.assert_commands(&[
+            Command::Delay(100),
+            Command::Enable(Leg::LeftFront),
+            Command::Duty(Leg::LeftFront, angle_to_duty(0, 1024)),
+        ]);
+        assert!(!controller.is_detached(Leg::LeftFront));
+        assert!(controller.is_detached(Leg::RightFront));
+
+        // Pulses past the period saturate
+        log.clear();
+        controller.set_detach_pulse_us(Some(50_000));
+        controller.detach_leg(Leg::LeftFront).unwrap();
+        log.assert_duties(&[(Leg::LeftFront, 1024)]);
+    }
+
+    /// Test that disabled legs receive no writes from any setter
+    #[test]
+    fn test_disabled_legs_are_skipped() {
+        let (mut controller, log) = mock_controller();
+        controller.set_leg_enabled(Leg::LeftBack, false);
+        controller.set_leg_enabled(Leg::RightFront, false);
+
+        controller.set_all_servos_angle(0).unwrap();
+        controller.set_servo_angles(180, 180, 180, 180).unwrap();
+        controller.set_leg_angle(Leg::RightFront, 45).unwrap();
+        controller.set_left_servos(45, 45).unwrap();
+        controller.detach_all().unwrap();
+
+        log.assert_commands(&[
+            Command::Duty(Leg::RightBack, angle_to_duty(0, 1024)),
+            Command::Duty(Leg::LeftFront, angle_to_duty(0, 1024)),
+            Command::Duty(Leg::RightBack, angle_to_duty(180, 1024)),
+            Command::Duty(Leg::LeftFront, angle_to_duty(180, 1024)),
+            Command::Duty(Leg::LeftFront, angle_to_duty(45, 1024)),
+            Command::Disable(Leg::RightBack),
+            Command::Disable(Leg::LeftFront),
+        ]);
+        assert_eq!(controller.current_angles(), [180, 90, 90, 45]);
+    }
+
+    /// Test that re-enabling a leg resumes writes to it
+    #[test]
+    fn test_reenabled_leg_is_driven() {
+        let (mut controller, log) = mock_controller();
+        controller.set_leg_enabled(Leg::RightBack, false);
+        assert!(!controller.is_leg_enabled(Leg::RightBack));
+
+        controller.set_leg_enabled(Leg::RightBack, true);
+        controller.set_leg_angle(Leg::RightBack, 0).unwrap();
+        log.assert_duties(&[(Leg::RightBack, angle_to_duty(0, 1024))]);
+    }
+
+    /// Test that a limit of 2 applies a four-leg set in two waves
+    #[test]
+    fn test_max_concurrent_moves_waves() {
+        let (mut controller, log) = mock_controller();
+        controller.set_max_concurrent_moves(2);
+
+        controller.set_servo_angles(0, 0, 180, 180).unwrap();
+
+        log.assert_commands(&[
+            Command::Duty(Leg::RightBack, angle_to_duty(0, 1024)),
+            Command::Duty(Leg::LeftBack, angle_to_duty(0, 1024)),
+            Command::Delay(WAVE_DELAY_MS),
+            Command::Duty(Leg::RightFront, angle_to_duty(180, 1024)),
+            Command::Duty(Leg::LeftFront, angle_to_duty(180, 1024)),
+        ]);
+    }
+
+    /// Test that a stagger separates the writes of every batch setter
+    #[test]
+    fn test_stagger() {
+        let (mut controller, log) = mock_controller();
+        assert_eq!(controller.stagger_ms(), 0);
+        controller.set_stagger_ms(5);
+
+        controller.center_all_servos().unwrap();
+        let times: Vec<u64> = log.records().iter().map(|record| record.at_ms).collect();
+        log.assert_commands(&[
+            Command::Duty(Leg::RightBack, angle_to_duty(90, 1024)),
+            Command::Delay(5),
+            Command::Duty(Leg::LeftBack, angle_to_duty(90, 1024)),
+            Command::Delay(5),
+            Command::Duty(Leg::RightFront, angle_to_duty(90, 1024)),
+            Command::Delay(5),
+            Command::Duty(Leg::LeftFront, angle_to_duty(90, 1024)),
+        ]);
+        assert_eq!(times, [0, 0, 5, 5, 10, 10, 15]);
+
+        log.clear();
+        controller.set_all_servos_angle(0).unwrap();
+        assert_eq!(
+            log.commands()
+                .iter()
+                .filter(|c| **c == Command::Delay(5))
+                .count(),
+            3
+        );
+
+        // Sub-batch pauses use the longer of the two delays
+        log.clear();
+        controller.set_max_concurrent_moves(2);
+        controller.set_left_servos(90, 90).unwrap();
+        controller.set_servo_angles(180, 180, 180, 180).unwrap();
+        log.assert_commands(&[
+            Command::Duty(Leg::LeftBack, angle_to_duty(90, 1024)),
+            Command::Delay(5),
+            Command::Duty(Leg::LeftFront, angle_to_duty(90, 1024)),
+            Command::Duty(Leg::RightBack, angle_to_duty(180, 1024)),
+            Command::Delay(5),
+            Command::Duty(Leg::LeftBack, angle_to_duty(180, 1024)),
+            Command::Delay(WAVE_DELAY_MS),
+            Command::Duty(Leg::RightFront, angle_to_duty(180, 1024)),
+            Command::Delay(5),
+            Command::Duty(Leg::LeftFront, angle_to_duty(180, 1024)),
+        ]);
+
+        // Single-leg writes are never delayed
+        log.clear();
+        controller.set_leg_angle(Leg::RightBack, 90).unwrap();
+        log.assert_commands(&[Command::Duty(Leg::RightBack, angle_to_duty(90, 1024))]);
+    }
+
+    /// Test that the default moves every leg at once and 0 is treated as 1
+    #[test]
+    fn test_max_concurrent_moves_default_and_zero() {
+        let (mut controller, log) = mock_controller();
+        assert_eq!(controller.max_concurrent_moves(), 4);
+
+        controller.set_all_servos_angle(0).unwrap();
+        assert!(!log.commands().contains(&Command::Delay(WAVE_DELAY_MS)));
+
+        log.clear();
+        controller.set_max_concurrent_moves(0);
+        controller.set_right_servos(0, 180).unwrap();
+        log.assert_commands(&[
+            Command::Duty(Leg::RightBack, angle_to_duty(0, 1024)),
+            Command::Delay(WAVE_DELAY_MS),
+            Command::Duty(Leg::RightFront, angle_to_duty(180, 1024)),
+        ]);
+    }
+
+    /// Test that controller log records carry the configured target
+    #[cfg(feature = "logging")]
+    #[test]
+    fn test_log_target() {
+        capture_logs();
+        let (controller, _log) = mock_controller();
+        let mut controller = controller.with_log_target("cobot.test.target");
+        assert_eq!(controller.log_target(), "cobot.test.target");
+
+        controller.set_all_servos_angle(45).unwrap();
+        controller.set_leg_angle(Leg::LeftBack, 0).unwrap();
+
+        let records = captured_logs("cobot.test.target");
+        assert!(records.len() >= 6, "expected logs, got {records:?}");
+        assert!(records.iter().any(|r| r.contains("All servos set to 45")));
+        assert!(records.iter().any(|r| r.contains("left_back_leg set to 0")));
+    }
+
+    /// Test that duty conversion uses the stored actual frequency
+    #[test]
+    fn test_actual_frequency_duty() {
+        assert_eq!(period_us(FREQUENCY_HZ), PERIOD_US);
+        assert_eq!(
+            angle_to_duty_with_period(90, 1024, PERIOD_US),
+            angle_to_duty(90, 1024)
+        );
+
+        let (controller, log) = mock_controller();
+        assert_eq!(controller.actual_frequency_hz(), FREQUENCY_HZ);
+
+        // 40 Hz → 25 ms period: 90° (1500 µs) is 1500 * 1024 / 25000 = 61
+        let mut controller = controller.with_actual_frequency_hz(40);
+        controller.set_leg_angle(Leg::RightBack, 90).unwrap();
+        controller.set_all_servos_angle(90).unwrap();
+
+        log.assert_duties(&[
+            (Leg::RightBack, 61),
+            (Leg::RightBack, 61),
+            (Leg::LeftBack, 61),
+            (Leg::RightFront, 61),
+            (Leg::LeftFront, 61),
+        ]);
+    }
+
+    /// Test that bound alternates the front and back pairs
+    #[test]
+    fn test_bound_alternates_pairs() {
+        let (mut controller, log) = mock_controller();
+
+        controller.bound(Ms(50), 1).unwrap();
+
+        let (lift, plant) = (angle_to_duty(45, 1024), angle_to_duty(90, 1024));
+        log.assert_commands(&[
+            Command::Duty(Leg::RightFront, lift),
+            Command::Duty(Leg::LeftFront, lift),
+            Command::Delay(50),
+            Command::Duty(Leg::RightFront, plant),
+            Command::Duty(Leg::LeftFront, plant),
+            Command::Delay(50),
+            Command::Duty(Leg::RightBack, lift),
+            Command::Duty(Leg::LeftBack, lift),
+            Command::Delay(50),
+            Command::Duty(Leg::RightBack, plant),
+            Command::Duty(Leg::LeftBack, plant),
+            Command::Delay(50),
+        ]);
+    }
+
+    /// Test that a timed walk stops after the frame that crosses the duration
+    #[test]
+    fn test_walk_forward_for_duration() {
+        let (mut controller, log) = mock_controller();
+
+        // Frames start at 0-1200 ms; the one starting at 1200 ends at 1300
+        controller.walk_forward_for(Ms(100), Ms(1250)).unwrap();
+        assert_eq!(log.now_ms(), 1300);
+        // 13 frames: two full 5-frame walks, then three more
+        assert_eq!(log.duties_for(Leg::RightBack).len(), 13);
+        assert_eq!(controller.current_angles(), [90, 45, 90, 45]);
+
+        // Shorter than one frame: exactly one frame plays
+        log.clear();
+        let start = log.now_ms();
+        controller
+            .walk_forward_for(Ms(100), Duration::from_millis(30))
+            .unwrap();
+        assert_eq!(log.now_ms() - start, 100);
+        assert_eq!(log.duties_for(Leg::RightBack).len(), 1);
+
+        // Zero duration: nothing
+        log.clear();
+        controller.walk_forward_for(Ms(100), Ms(0)).unwrap();
+        assert!(log.commands().is_empty());
+    }
+
+    /// Test that the gait cutoff stops a walk whose predicate never turns
+    /// false
+    #[test]
+    fn test_max_gait_duration_cutoff() {
+        let (controller, log) = mock_controller();
+        let clock = MockClock::new();
+        let mut controller = controller.with_clock(clock.clone());
+        assert_eq!(
+            controller.max_gait_duration(),
+            Some(DEFAULT_MAX_GAIT_DURATION)
+        );
+
+        // Each frame takes 100 ms on the controller's clock
+        controller.set_max_gait_duration(Some(Duration::from_millis(1000)));
+        let stopped = controller
+            .walk_forward_while(Ms(100), || {
+                clock.advance(100);
+                true
+            })
+            .unwrap();
+        assert!(stopped);
+        assert_eq!(log.duties_for(Leg::RightBack).len(), 10);
+
+        // A predicate that ends the walk first is not a cutoff
+        log.clear();
+        let mut frames = 0;
+        let stopped = controller
+            .walk_forward_while(Ms(100), || {
+                frames += 1;
+                frames <= 3
+            })
+            .unwrap();
+        assert!(!stopped);
+        assert_eq!(log.duties_for(Leg::RightBack).len(), 3);
+
+        // The default cutoff applies too
+        controller.set_max_gait_duration(Some(DEFAULT_MAX_GAIT_DURATION));
+        log.clear();
+        let frame_ms = 200;
+        let stopped = controller
+            .walk_forward_while(Ms(frame_ms), || {
+                clock.advance(u64::from(frame_ms));
+                true
+            })
+            .unwrap();
+        assert!(stopped);
+        let expected = DEFAULT_MAX_GAIT_DURATION.as_millis() as usize / frame_ms as usize;
+        assert_eq!(log.duties_for(Leg::RightBack).len(), expected);
+    }
+
+    /// Test that the warm-up walks swing less than the walks after them
+    #[test]
+    fn test_walk_forward_warmup() {
+        let (mut controller, log) = mock_controller();
+
+        controller.walk_forward_warmup(Ms(100), 3, 5).unwrap();
+
+        // Largest swing from center of each 5-frame walk
+        let center = angle_to_duty(90, 1024);
+        let duties = log.duties_for(Leg::RightBack);
+        let swings: Vec<u32> = duties
+            .chunks(5)
+            .map(|walk| walk.iter().map(|d| d.abs_diff(center)).max().unwrap())
+            .collect();
+        assert_eq!(swings.len(), 5);
+        assert!(swings[0] > 0);
+        assert!(swings[0] < swings[1] && swings[1] < swings[2] && swings[2] < swings[3]);
+        assert_eq!(swings[3], swings[4]);
+        let full_swing = angle_to_duty(90 + 45, 1024)
+            .abs_diff(center)
+            .max(angle_to_duty(90 - 45, 1024).abs_diff(center));
+        assert_eq!(swings[4], full_swing);
+        assert_eq!(log.now_ms(), 5 * 5 * 100);
+    }
+
+    /// Test that the level walk alternates mirrored frames for each cycle
+    #[test]
+    fn test_walk_level_cycles() {
+        let (mut controller, log) = mock_controller();
+
+        controller.walk_level(Ms(40), 2).unwrap();
+
+        let (back, forward) = (angle_to_duty(45, 1024), angle_to_duty(135, 1024));
+        assert_eq!(
+            log.duties_for(Leg::RightFront),
+            vec![forward, back, forward, back]
+        );
+        assert_eq!(
+            log.duties_for(Leg::LeftFront),
+            vec![back, forward, back, forward]
+        );
+        assert_eq!(log.now_ms(), 4 * 40);
+        assert_eq!(controller.current_angles(), [45, 135, 45, 135]);
+    }
+
+    /// Test that a right arc swings the right legs less than the left
+    #[test]
+    fn test_walk_arc() {
+        let (mut controller, log) = mock_controller();
+
+        controller.walk_arc(Ms(50), 40, 2).unwrap();
+
+        // 45° steps shortened by 40% on the right: 27°
+        let duties = |angles: [u32; 3]| angles.map(|angle| angle_to_duty(angle, 1024));
+        let [right_up, right_fwd, center] = duties([63, 117, 90]);
+        let [left_up, left_fwd, _] = duties([45, 135, 90]);
+        assert_eq!(
+            log.duties_for(Leg::RightFront),
+            [right_up, right_fwd, center, center, center].repeat(2)
+        );
+        assert_eq!(
+            log.duties_for(Leg::LeftBack),
+            [center, center, left_up, left_fwd, center].repeat(2)
+        );
+        assert_eq!(log.now_ms(), 2 * 5 * 50);
+    }
+
+    /// Test that a creep steps each leg in turn and ends planted
+    #[test]
+    fn test_creep_cycles() {
+        let (mut controller, log) = mock_controller();
+
+        controller.creep(Ms(30), 2).unwrap();
+
+        let (up, fwd, plant) = (
+            angle_to_duty(45, 1024),
+            angle_to_duty(135, 1024),
+            angle_to_duty(90, 1024),
+        );
+        let mut right_front = vec![up, fwd];
+        right_front.extend([plant; 10]);
+        assert_eq!(log.duties_for(Leg::RightFront)[..12], right_front[..]);
+        assert_eq!(log.duties_for(Leg::LeftBack)[3..5], [up, fwd]);
+        assert_eq!(log.duties_for(Leg::RightBack)[9..11], [up, fwd]);
+        assert_eq!(log.now_ms(), 2 * 12 * 30);
+        assert_eq!(controller.current_angles(), [90; 4]);
+    }
+
+    /// Test that marching lifts every leg once per cycle, in order, and never
+    /// reaches forward
+    #[test]
+    fn test_march_in_place() {
+        let (mut controller, log) = mock_controller();
+
+        controller.march_in_place(Ms(40), 3).unwrap();
+
+        let (up, plant) = (angle_to_duty(45, 1024), angle_to_duty(90, 1024));
+        for leg in LEGS {
+            let duties = log.duties_for(leg);
+            assert_eq!(duties.len(), 3 * 8);
+            assert!(duties.iter().all(|&duty| duty == up || duty == plant));
+            for cycle in duties.chunks(8) {
+                let lifts: Vec<usize> = (0..8).filter(|&frame| cycle[frame] == up).collect();
+                let slot = gaits::MARCH_ORDER.iter().position(|&l| l == leg).unwrap();
+                assert_eq!(lifts, vec![2 * slot], "{} lifts", leg.name());
+            }
+        }
+        assert_eq!(log.now_ms(), 3 * 8 * 40);
+        assert_eq!(controller.current_angles(), [90; 4]);
+    }
+
+    /// Test that durations convert to whole milliseconds and time the gait
+    /// methods like the equivalent `Ms`
+    #[test]
+    fn test_ms_from_duration() {
+        assert_eq!(Ms::from(Duration::from_millis(250)), Ms(250));
+        assert_eq!(Ms::from(Duration::from_secs(2)), Ms(2000));
+        assert_eq!(Ms::from(Duration::from_micros(1999)), Ms(1));
+        assert_eq!(Ms::from(Duration::from_secs(u64::MAX)), Ms(u32::MAX));
+        assert_eq!(Duration::from(Ms(40)), Duration::from_millis(40));
+
+        let (mut typed, typed_log) = mock_controller();
+        let (mut raw, raw_log) = mock_controller();
+        typed.creep(Duration::from_millis(30), 1).unwrap();
+        raw.creep(Ms(30), 1).unwrap();
+        assert_eq!(typed_log.commands(), raw_log.commands());
+        assert_eq!(typed_log.now_ms(), 12 * 30);
+
+        // A duration in microseconds is not taken for milliseconds
+        let (mut controller, log) = mock_controller();
+        controller.pronk(Duration::from_micros(50_000), 1).unwrap();
+        assert_eq!(log.now_ms(), PRONK_FRAMES.len() as u64 * 50);
+
+        // The gait constructors and the builder take durations too
+        assert_eq!(
+            gaits::walk(45, Duration::from_millis(300)),
+            gaits::walk(45, Ms(300))
+        );
+        assert_eq!(
+            MovementBuilder::new().delay(Duration::from_secs(1)).steps(),
+            &[MovementStep::Delay(1000)]
+        );
+    }
+
+    /// Test that pronk moves all legs together
+    #[test]
+    fn test_pronk_moves_all_together() {
+        let (mut controller, log) = mock_controller();
+
+        controller.pronk(Ms(50), 2).unwrap();
+
+        let commands = log.commands();
+        assert_eq!(commands.len(), 4 * 5);
+        for (frame, chunk) in commands.chunks(5).enumerate() {
+            let duty = angle_to_duty(PRONK_FRAMES[frame % 2], 1024);
+            let expected: Vec<Command> = LEGS
+                .iter()
+                .map(|&leg| Command::Duty(leg, duty))
+                .chain([Command::Delay(50)])
+                .collect();
+            assert_eq!(chunk, expected.as_slice());
+        }
+    }
+
+    /// Test that a transient failure is retried until the write succeeds
+    #[test]
+    fn test_retry_policy_recovers() {
+        let (mut controller, log) = mock_controller();
+        controller.set_retry_policy(3, 5);
+        log.fail_writes(Leg::LeftBack, 2);
+
+        controller.set_leg_angle(Leg::LeftBack, 0).unwrap();
+
+        log.assert_commands(&[
+            Command::Delay(5),
+            Command::Delay(5),
+            Command::Duty(Leg::LeftBack, angle_to_duty(0, 1024)),
+        ]);
+        assert_eq!(controller.current_angle(Leg::LeftBack), 0);
+    }
+
+    /// Test that the error is returned once retries are exhausted
+    #[test]
+    fn test_retry_policy_exhausted() {
+        let (mut controller, log) = mock_controller();
+        controller.set_retry_policy(2, 5);
+        log.fail_writes(Leg::LeftBack, 3);
+
+        let err = controller.set_leg_angle(Leg::LeftBack, 0).unwrap_err();
+
+        assert!(err.to_string().contains("left_back_leg"));
+        log.assert_commands(&[Command::Delay(5), Command::Delay(5)]);
+        assert_eq!(controller.current_angle(Leg::LeftBack), 90);
+    }
+
+    /// Test that without a retry policy a failure is returned immediately
+    #[test]
+    fn test_no_retry_by_default() {
+        let (mut controller, log) = mock_controller();
+        log.fail_writes(Leg::LeftBack, 1);
+
+        assert!(controller.set_leg_angle(Leg::LeftBack, 0).is_err());
+        assert!(log.commands().is_empty());
+    }
+
+    /// Test that a defined pose can be recalled and redefined
+    #[test]
+    fn test_define_and_goto_pose() {
+        let (mut controller, log) = mock_controller();
+
+        controller.define_pose("sit", [0, 0, 90, 90]).unwrap();
+        controller.define_pose("stand", [90; 4]).unwrap();
+        assert!(log.commands().is_empty());
+
+        controller.goto_pose("sit").unwrap();
+        assert_eq!(controller.current_angles(), [0, 0, 90, 90]);
+
+        controller.define_pose("sit", [10, 10, 90, 90]).unwrap();
+        controller.goto_pose("sit").unwrap();
+        assert_eq!(controller.current_angles(), [10, 10, 90, 90]);
+        assert_eq!(controller.pose("stand"), Some([90; 4]));
+    }
+
+    /// Test that recalling an undefined pose fails without moving
+    #[test]
+    fn test_goto_undefined_pose() {
+        let (mut controller, log) = mock_controller();
+
+        let err = controller.goto_pose("dance").unwrap_err();
+
+        assert_eq!(
+            err.downcast_ref::<ServoError>(),
+            Some(&ServoError::UnknownPose("dance".to_string()))
+        );
+        assert!(log.commands().is_empty());
+    }
+
+    /// Test blending at both ends, the midpoint and out of range
+    #[test]
+    fn test_blend_pose() {
+        let (a, b) = ([0, 90, 180, 45], [180, 90, 0, 50]);
+
+        assert_eq!(blend_pose(a, b, 0.0), a);
+        assert_eq!(blend_pose(a, b, 1.0), b);
+        // 47.5 rounds up
+        assert_eq!(blend_pose(a, b, 0.5), [90, 90, 90, 48]);
+        assert_eq!(blend_pose(a, b, 0.25), [45, 90, 135, 46]);
+        assert_eq!(blend_pose(a, b, -1.0), a);
+        assert_eq!(blend_pose(a, b, 7.0), b);
+        assert_eq!(blend_pose(a, b, f32::NAN), a);
+    }
+
+    /// Test that a blend of named poses is commanded
+    #[test]
+    fn test_goto_blended_pose() {
+        let (mut controller, log) = mock_controller();
+        controller.define_pose("sit", [0, 0, 90, 90]).unwrap();
+        controller.define_pose("stand", [90; 4]).unwrap();
+
+        controller.goto_blended_pose("sit", "stand", 0.5).unwrap();
+        assert_eq!(controller.current_angles(), [45, 45, 90, 90]);
+
+        log.clear();
+        let err = controller
+            .goto_blended_pose("sit", "dance", 0.5)
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ServoError>(),
+            Some(&ServoError::UnknownPose("dance".to_string()))
+        );
+        assert!(log.commands().is_empty());
+    }
+
+    /// Test that the number of stored poses is capped
+    #[test]
+    fn test_pose_limit() {
+        let (mut controller, _log) = mock_controller();
+        for i in 0..MAX_POSES {
+            controller
+                .define_pose(&format!("p{i}"), [i as u32; 4])
+                .unwrap();
+        }
+
+        let err = controller.define_pose("one_more", [0; 4]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ServoError>(),
+            Some(&ServoError::PoseLimitReached { max: MAX_POSES })
+        );
+        // Redefining an existing pose is still allowed
+        controller.define_pose("p0", [1; 4]).unwrap();
+    }
+
+    /// Test that sleep detaches every leg and wake re-attaches and centers them
+    #[test]
+    fn test_sleep_and_wake() {
+        use std::sync::{Arc, Mutex};
+
+        struct MockPower(Arc<Mutex<Vec<&'static str>>>);
+        impl PowerControl for MockPower {
+            fn reduce_clock(&mut self) -> Result<()> {
+                self.0.lock().unwrap().push("reduce");
+                Ok(())
+            }
+            fn restore_clock(&mut self) -> Result<()> {
+                self.0.lock().unwrap().push("restore");
+                Ok(())
+            }
+        }
+
+        let (mut controller, log) = mock_controller();
+        let clock = Arc::new(Mutex::new(Vec::new()));
+        controller.set_power_control(Box::new(MockPower(clock.clone())));
+
+        controller.enter_sleep().unwrap();
+        assert!(controller.is_sleeping());
+        assert!(LEGS.iter().all(|&leg| controller.is_detached(leg)));
+        log.assert_commands(&LEGS.map(Command::Disable));
+
+        log.clear();
+        controller.wake().unwrap();
+        assert!(!controller.is_sleeping());
+        let center = angle_to_duty(90, 1024);
+        log.assert_commands(
+            &LEGS
+                .iter()
+                .flat_map(|&leg| [Command::Enable(leg), Command::Duty(leg, center)])
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(*clock.lock().unwrap(), ["reduce", "restore"]);
+    }
+
+    /// Test radian conversion, clamping and round trips
+    #[test]
+    fn test_radian_conversion() {
+        use std::f32::consts::{FRAC_PI_2, PI};
+
+        assert_eq!(radians_to_degrees(FRAC_PI_2), 90);
+        assert_eq!(radians_to_degrees(PI), 180);
+        assert_eq!(radians_to_degrees(-1.0), 0);
+        assert_eq!(radians_to_degrees(10.0), 180);
+        assert_eq!(radians_to_degrees(f32::NAN), 0);
+
+        for degrees in 0..=180 {
+            assert_eq!(radians_to_degrees(degrees_to_radians(degrees)), degrees);
+        }
+        assert!((degrees_to_radians(90) - FRAC_PI_2).abs() < 1e-6);
+    }
+
+    /// Test that the radian setter drives the same angles as the degree one
+    #[test]
+    fn test_set_servo_angles_rad() {
+        use std::f32::consts::{FRAC_PI_2, PI};
+        let (mut controller, _log) = mock_controller();
+
+        controller
+            .set_servo_angles_rad(0.0, FRAC_PI_2, PI, -PI)
+            .unwrap();
+
+        assert_eq!(controller.current_angles(), [0, 90, 180, 0]);
+    }
+
+    /// Test that rapid submits are coalesced into one write per control tick
+    #[test]
+    fn test_tick_control_coalesces() {
+        let (mut controller, log) = mock_controller();
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+
+        // Nothing submitted: nothing written, and the next submit isn't delayed
+        assert!(!controller.tick_control(at(0)).unwrap());
+
+        for angle in [10, 15, 20] {
+            controller.submit_target(Leg::RightBack, angle);
+        }
+        controller.submit_target(Leg::LeftFront, 30);
+        assert!(log.duties().is_empty(), "submitting writes nothing");
+        assert!(controller.tick_control(at(0)).unwrap());
+        log.assert_duties(&[
+            (Leg::RightBack, angle_to_duty(20, 1024)),
+            (Leg::LeftFront, angle_to_duty(30, 1024)),
+        ]);
+        assert_eq!(controller.current_angles(), [20, 90, 90, 30]);
+
+        // The default rate allows one write per 20 ms
+        log.clear();
+        controller.submit_target(Leg::RightBack, 40);
+        assert!(!controller.tick_control(at(10)).unwrap());
+        controller.submit_target(Leg::RightBack, 50);
+        assert!(log.duties().is_empty());
+        assert!(controller.tick_control(at(20)).unwrap());
+        log.assert_duties(&[(Leg::RightBack, angle_to_duty(50, 1024))]);
+        assert!(
+            !controller.tick_control(at(40)).unwrap(),
+            "targets are consumed"
+        );
+
+        log.clear();
+        controller.set_control_rate_hz(10);
+        controller.submit_target(Leg::LeftBack, 60);
+        assert!(!controller.tick_control(at(100)).unwrap());
+        assert!(controller.tick_control(at(120)).unwrap());
+        log.assert_duties(&[(Leg::LeftBack, angle_to_duty(60, 1024))]);
+    }
+
+    /// Test that commands above the rate limit are dropped
+    #[test]
+    fn test_command_rate_limit_drop() {
+        let (mut controller, log) = mock_controller();
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let writes = [(Leg::RightBack, 10)];
+
+        controller.set_max_command_rate(10);
+        assert_eq!(controller.max_command_rate(), 10);
+        assert!(controller.admit_command(at(0), &writes));
+        assert!(!controller.admit_command(at(50), &writes));
+        assert!(!controller.admit_command(at(99), &writes));
+        assert!(controller.admit_command(at(100), &writes));
+        assert!(!controller.admit_command(at(150), &writes));
+        assert!(controller.admit_command(at(250), &writes));
+        assert_eq!(controller.targets, [None; 4], "dropped, not stored");
+
+        // A runaway loop gets one command through
+        controller.set_max_command_rate(1);
+        for angle in [20, 30, 40] {
+            controller
+                .set_servo_angles(angle, angle, angle, angle)
+                .unwrap();
+            controller.set_leg_angle(Leg::LeftFront, angle + 1).unwrap();
+            controller.set_front_servos(angle + 2, angle + 2).unwrap();
+        }
+        log.assert_duties(&LEGS.map(|leg| (leg, angle_to_duty(20, 1024))));
+        assert_eq!(controller.current_angles(), [20; 4]);
+
+        // No limit writes everything
+        log.clear();
+        controller.set_max_command_rate(0);
+        controller.set_leg_angle(Leg::LeftFront, 50).unwrap();
+        controller.set_leg_angle(Leg::LeftFront, 60).unwrap();
+        assert_eq!(log.duties().len(), 2);
+    }
+
+    /// Test that idle legs are centered after the timeout, and that new
+    /// commands restart the wait
+    #[test]
+    fn test_auto_center_after() {
+        let (controller, log) = mock_controller();
+        let clock = MockClock::new();
+        let mut controller = controller.with_clock(clock.clone());
+        let center = LEGS.map(|leg| (leg, angle_to_duty(90, 1024)));
+
+        // Off by default, and nothing to center before the first write
+        controller.set_servo_angles(10, 20, 30, 40).unwrap();
+        clock.advance(10_000);
+        assert!(!controller.tick_control(clock.now()).unwrap());
+
+        controller.auto_center_after(Some(Duration::from_millis(500)));
+        controller.set_servo_angles(10, 20, 30, 40).unwrap();
+        clock.advance(499);
+        assert!(!controller.tick_control(clock.now()).unwrap());
+        clock.advance(1);
+        log.clear();
+        assert!(controller.tick_control(clock.now()).unwrap());
+        log.assert_duties(&center);
+        assert_eq!(controller.current_angles(), [90; 4]);
+
+        // Centering happens once, not on every later tick
+        clock.advance(1000);
+        assert!(!controller.tick_control(clock.now()).unwrap());
+
+        // A new command before the timeout cancels it and restarts the wait
+        controller.set_leg_angle(Leg::LeftFront, 45).unwrap();
+        clock.advance(400);
+        controller.submit_target(Leg::RightBack, 60);
+        assert!(controller.tick_control(clock.now()).unwrap());
+        clock.advance(400);
+        log.clear();
+        assert!(!controller.tick_control(clock.now()).unwrap());
+        clock.advance(100);
+        assert!(controller.tick_control(clock.now()).unwrap());
+        log.assert_duties(&center);
+
+        // Detaching cancels it until the next write
+        controller.set_leg_angle(Leg::LeftFront, 45).unwrap();
+        controller.detach_all().unwrap();
+        clock.advance(1000);
+        assert!(!controller.tick_control(clock.now()).unwrap());
+        assert!(controller.is_detached(Leg::LeftFront));
+    }
+
+    /// Test the rate limiter's interval on a clock advanced by hand
+    #[test]
+    fn test_command_rate_limit_mock_clock() {
+        let (controller, log) = mock_controller();
+        let clock = MockClock::new();
+        let mut controller = controller.with_clock(clock.clone());
+        assert_eq!(controller.now(), clock.at(0));
+        controller.set_max_command_rate(10);
+
+        controller.set_leg_angle(Leg::RightBack, 10).unwrap();
+        clock.advance(99);
+        controller.set_leg_angle(Leg::RightBack, 20).unwrap();
+        assert_eq!(log.duties().len(), 1, "still within 100 ms");
+
+        clock.advance(1);
+        controller.set_leg_angle(Leg::RightBack, 30).unwrap();
+        controller.set_leg_angle(Leg::RightBack, 40).unwrap();
+        log.assert_duties(&[
+            (Leg::RightBack, angle_to_duty(10, 1024)),
+            (Leg::RightBack, angle_to_duty(30, 1024)),
+        ]);
+
+        // Stopped time never lets another command through
+        for angle in 50..60 {
+            controller.set_leg_angle(Leg::RightBack, angle).unwrap();
+        }
+        assert_eq!(log.duties().len(), 2);
+        assert_eq!(controller.current_angle(Leg::RightBack), 30);
+    }
+
+    /// Test that coalesced commands leave their latest angles to tick_control
+    #[test]
+    fn test_command_rate_limit_coalesce() {
+        let (mut controller, log) = mock_controller();
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        controller.set_rate_limit_policy(RateLimitPolicy::Coalesce);
+        controller.set_max_command_rate(10);
+
+        assert!(controller.admit_command(at(0), &[(Leg::RightBack, 10)]));
+        assert!(!controller.admit_command(at(10), &[(Leg::RightBack, 20), (Leg::LeftBack, 30)]));
+        assert!(!controller.admit_command(at(20), &[(Leg::RightBack, 40)]));
+        assert_eq!(controller.targets, [Some(40), Some(30), None, None]);
+        // A command let through supersedes the pending targets of its legs
+        assert!(controller.admit_command(at(100), &[(Leg::RightBack, 50)]));
+        assert_eq!(controller.targets, [None, Some(30), None, None]);
+
+        controller.set_max_command_rate(1);
+        controller.set_servo_angles(10, 20, 30, 40).unwrap();
+        controller.set_servo_angles(50, 60, 70, 80).unwrap();
+        controller.set_leg_angle(Leg::RightBack, 100).unwrap();
+        log.assert_duties(&[
+            (Leg::RightBack, angle_to_duty(10, 1024)),
+            (Leg::LeftBack, angle_to_duty(20, 1024)),
+            (Leg::RightFront, angle_to_duty(30, 1024)),
+            (Leg::LeftFront, angle_to_duty(40, 1024)),
+        ]);
+
+        log.clear();
+        assert!(controller.tick_control(Instant::now()).unwrap());
+        log.assert_duties(&[
+            (Leg::RightBack, angle_to_duty(100, 1024)),
+            (Leg::LeftBack, angle_to_duty(60, 1024)),
+            (Leg::RightFront, angle_to_duty(70, 1024)),
+            (Leg::LeftFront, angle_to_duty(80, 1024)),
+        ]);
+    }
+
+    /// Test that kill zeroes every channel and refuses moves until cleared
+    #[test]
+    fn test_kill() {
+        let (mut controller, log) = mock_controller();
+        controller.set_on_error(OnError::CenterAll);
+        controller.set_servo_angles(10, 20, 30, 40).unwrap();
+        controller.set_leg_enabled(Leg::LeftFront, false);
+        log.clear();
+
+        controller.kill().unwrap();
+
+        assert!(controller.is_halted());
+        log.assert_duties(&LEGS.map(|leg| (leg, 0)));
+        assert_eq!(
+            LEGS.map(|leg| controller.read_duty(leg)),
+            [0; 4],
+            "every channel reads back no pulse"
+        );
+        assert_eq!(controller.current_angles(), [10, 20, 30, 40]);
+
+        log.clear();
+        let err = controller.set_leg_angle(Leg::RightBack, 90).unwrap_err();
+        assert_eq!(err.downcast_ref::<ServoError>(), Some(&ServoError::Halted));
+        controller.set_all_servos_angle(90).unwrap_err();
+        assert!(
+            log.duties().is_empty(),
+            "no movement or recovery while halted"
+        );
+
+        controller.clear_halt();
+        assert!(!controller.is_halted());
+        controller.set_leg_angle(Leg::RightBack, 90).unwrap();
+        log.assert_duties(&[(Leg::RightBack, angle_to_duty(90, 1024))]);
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Test that an unknown state refuses moves until resync re-commands the
+    /// tracked angles and centers the legs never written
+    #[test]
+    fn test_state_unknown_until_resync() {
+        let (mut controller, log) = mock_controller();
+        controller.set_deadband(5);
+        controller.set_leg_angle(Leg::RightBack, 30).unwrap();
+        controller.set_leg_angle(Leg::LeftFront, 150).unwrap();
+        controller.mark_state_unknown();
+        assert!(controller.is_state_unknown());
+        log.clear();
 
-    /// Test basic angle_to_duty calculation for ESP32 10-bit LEDC
+        let err = controller.set_leg_angle(Leg::RightBack, 90).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ServoError>(),
+            Some(&ServoError::StateUnknown)
+        );
+        controller.set_servo_angles(1, 2, 3, 4).unwrap_err();
+        controller.move_leg_smooth(Leg::LeftBack, 0).unwrap_err();
+        assert!(log.duties().is_empty(), "no movement or recovery");
+        assert_eq!(controller.current_angles(), [30, 90, 90, 150]);
+
+        // A failed resync leaves the state unknown
+        log.fail_writes(Leg::LeftBack, 1);
+        assert!(controller.resync().is_err());
+        assert!(controller.is_state_unknown());
+
+        log.clear();
+        controller.resync().unwrap();
+        assert!(!controller.is_state_unknown());
+        log.assert_duties(&[
+            (Leg::RightBack, angle_to_duty(30, 1024)),
+            (Leg::LeftBack, angle_to_duty(90, 1024)),
+            (Leg::RightFront, angle_to_duty(90, 1024)),
+            (Leg::LeftFront, angle_to_duty(150, 1024)),
+        ]);
+
+        controller.set_leg_angle(Leg::RightBack, 90).unwrap();
+        assert_eq!(controller.current_angles()[0], 90);
+    }
+
+    /// Test that a failed atomic pose puts the written legs back
     #[test]
-    fn test_angle_to_duty_basic() {
-        let max_duty = 1024; // ESP32 10-bit default
+    fn test_set_servo_angles_atomic_rolls_back() {
+        let (mut controller, log) = mock_controller();
+        controller.set_on_error(OnError::CenterAll);
+        controller.set_servo_angles(10, 20, 30, 40).unwrap();
+        controller.detach_leg(Leg::LeftBack).unwrap();
+        log.clear();
 
-        // Test key angles
-        assert_eq!(angle_to_duty(0, max_duty), 25); // 0°
-        assert_eq!(angle_to_duty(90, max_duty), 76); // 90° (center)
-        assert_eq!(angle_to_duty(180, max_duty), 128); // 180°
+        log.fail_writes(Leg::LeftFront, 1);
+        assert!(
+            controller
+                .set_servo_angles_atomic(100, 110, 120, 130)
+                .is_err()
+        );
+
+        let duty = |angle| angle_to_duty(angle, 1024);
+        log.assert_duties(&[
+            (Leg::RightBack, duty(100)),
+            (Leg::LeftBack, duty(110)),
+            (Leg::RightFront, duty(120)),
+            (Leg::RightBack, duty(10)),
+            (Leg::LeftBack, duty(20)),
+            (Leg::RightFront, duty(30)),
+        ]);
+        assert_eq!(controller.current_angles(), [10, 20, 30, 40]);
+        assert!(controller.is_detached(Leg::LeftBack));
+        assert!(log.commands().ends_with(&[
+            Command::Duty(Leg::LeftBack, duty(20)),
+            Command::Disable(Leg::LeftBack),
+            Command::Duty(Leg::RightFront, duty(30)),
+        ]));
+
+        // Nothing to roll back when the first leg fails; success applies all
+        log.clear();
+        log.fail_writes(Leg::RightBack, 1);
+        assert!(controller.set_servo_angles_atomic(1, 2, 3, 4).is_err());
+        assert!(log.duties().is_empty());
+        controller.set_servo_angles_atomic(1, 2, 3, 4).unwrap();
+        assert_eq!(controller.current_angles(), [1, 2, 3, 4]);
     }
 
-    /// Test that duty values are within valid range
+    /// Test that NaN and infinite radians are rejected without moving
     #[test]
-    fn test_duty_bounds() {
-        let max_duty = 1024;
+    fn test_set_servo_angles_rad_non_finite() {
+        let (mut controller, log) = mock_controller();
 
-        // Test all angles produce valid duty values
-        for angle in 0..=180 {
-            let duty = angle_to_duty(angle, max_duty);
-            assert!(
-                duty <= max_duty,
-                "Duty {} exceeds max_duty for angle {}",
-                duty,
-                angle
+        let err = controller
+            .set_servo_angles_rad(0.0, f32::NAN, 1.0, 1.0)
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ServoError>(),
+            Some(&ServoError::InvalidAngle { leg: Leg::LeftBack, degrees }) if degrees.is_nan()
+        ));
+
+        let err = controller
+            .set_servo_angles_rad(0.0, 1.0, 1.0, f32::INFINITY)
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ServoError>(),
+            Some(&ServoError::InvalidAngle {
+                leg: Leg::LeftFront,
+                degrees: f32::INFINITY
+            })
+        );
+        assert!(log.commands().is_empty());
+        assert_eq!(controller.current_angles(), [90; 4]);
+    }
+
+    /// Test that parking folds the legs slowly to the park pose and detaches
+    #[test]
+    fn test_park_flat() {
+        let (mut controller, log) = mock_controller();
+        assert_eq!(controller.park_pose(), PARK_FLAT_POSE);
+
+        controller.park_flat(Ms(100)).unwrap();
+
+        // Five 20 ms frames from center, ending at the fold angles
+        let right_back = log.duties_for(Leg::RightBack);
+        assert_eq!(right_back.len(), 5);
+        assert!(right_back.windows(2).all(|pair| pair[1] < pair[0]));
+        let mut tail = LEGS
+            .map(|leg| Command::Duty(leg, angle_to_duty(PARK_FLAT_POSE[leg.index()], 1024)))
+            .to_vec();
+        tail.push(Command::Delay(20));
+        tail.extend(LEGS.map(Command::Disable));
+        assert!(log.commands().ends_with(&tail));
+        assert_eq!(log.now_ms(), 100);
+        assert_eq!(controller.current_angles(), PARK_FLAT_POSE);
+
+        // A custom pose, too short to blend: straight there
+        log.clear();
+        controller.set_park_pose([10, 20, 170, 200]);
+        assert_eq!(controller.park_pose(), [10, 20, 170, 180]);
+        controller.park_flat(Ms(0)).unwrap();
+        assert_eq!(controller.current_angles(), [10, 20, 170, 180]);
+        assert_eq!(log.duties().len(), 4);
+        assert!(LEGS.iter().all(|&leg| controller.is_detached(leg)));
+    }
+
+    /// Test that the demo reel plays its segments in order, each for the
+    /// same time
+    #[test]
+    fn test_play_demo_reel() {
+        let (mut controller, log) = mock_controller();
+
+        controller.play_demo_reel(&DEMO_REEL, Ms(1000)).unwrap();
+
+        assert_eq!(log.now_ms(), 7 * 1000);
+        // Each segment's first frame is written the moment it starts
+        let first_frames = [
+            [90, 90, 90, 90],
+            [90, 90, 45, 90],
+            [45, 90, 45, 90],
+            [45, 90, 90, 45],
+            SIT_POSE,
+            STAND_POSE,
+            [90, 90, 90, 90],
+        ];
+        for (segment, angles) in first_frames.iter().enumerate() {
+            let start_ms = segment as u64 * 1000;
+            let written: Vec<Command> = log
+                .records()
+                .iter()
+                .filter(|record| record.at_ms == start_ms)
+                .map(|record| record.command)
+                .filter(|command| matches!(command, Command::Duty(..)))
+                .take(4)
+                .collect();
+            let expected: Vec<Command> = LEGS
+                .iter()
+                .map(|&leg| Command::Duty(leg, angle_to_duty(angles[leg.index()], 1024)))
+                .collect();
+            assert_eq!(
+                written, expected,
+                "segment {segment} ({:?})",
+                DEMO_REEL[segment]
             );
         }
+
+        // Walk's 5 frames don't fit in 1000 ms at 250 ms each: the 5th is
+        // never reached, and the next segment starts on time
+        let walk_writes = log
+            .records()
+            .iter()
+            .filter(|record| (2000..3000).contains(&record.at_ms))
+            .filter(|record| {
+                record.command == Command::Duty(Leg::RightBack, angle_to_duty(90, 1024))
+            })
+            .count();
+        assert_eq!(walk_writes, 2);
+
+        // A reordered reel plays in its own order
+        log.clear();
+        let reel = [DemoSegment::Sit, DemoSegment::Stand];
+        controller.play_demo_reel(&reel, Ms(300)).unwrap();
+        log.assert_commands(&[
+            Command::Duty(Leg::RightBack, angle_to_duty(0, 1024)),
+            Command::Duty(Leg::LeftBack, angle_to_duty(0, 1024)),
+            Command::Duty(Leg::RightFront, angle_to_duty(90, 1024)),
+            Command::Duty(Leg::LeftFront, angle_to_duty(90, 1024)),
+            Command::Delay(300),
+            Command::Duty(Leg::RightBack, angle_to_duty(90, 1024)),
+            Command::Duty(Leg::LeftBack, angle_to_duty(90, 1024)),
+            Command::Duty(Leg::RightFront, angle_to_duty(90, 1024)),
+            Command::Duty(Leg::LeftFront, angle_to_duty(90, 1024)),
+            Command::Delay(300),
+        ]);
     }
 
-    /// Test angle clamping behavior
+    /// Test that recover plays the documented push sequence only when needed
     #[test]
-    fn test_angle_clamping() {
-        let max_duty = 1024;
+    fn test_recover() {
+        let (mut controller, log) = mock_controller();
 
-        // Angles over 180 should be clamped
-        assert_eq!(angle_to_duty(200, max_duty), angle_to_duty(180, max_duty));
-        assert_eq!(angle_to_duty(999, max_duty), angle_to_duty(180, max_duty));
+        assert!(!controller.recover(false).unwrap());
+        assert!(log.commands().is_empty());
+
+        assert!(controller.recover(true).unwrap());
+        let pushes = [
+            [90, 90, 90, 90],
+            [180, 0, 180, 0],
+            [180, 180, 180, 180],
+            [0, 180, 0, 180],
+            [90, 90, 90, 90],
+        ];
+        assert_eq!(RECOVERY_FRAMES, pushes);
+        for leg in LEGS {
+            let expected: Vec<u32> = pushes
+                .iter()
+                .map(|angles| angle_to_duty(angles[leg.index()], 1024))
+                .collect();
+            assert_eq!(log.duties_for(leg), expected, "{}", leg.name());
+        }
+        assert_eq!(log.now_ms(), 5 * u64::from(RECOVERY_DWELL_MS));
+        assert_eq!(controller.current_angles(), [90; 4]);
     }
 
-    /// Test roundtrip conversion accuracy
+    /// Test that each leg lags the previous one by the phase step
     #[test]
-    fn test_roundtrip_conversion() {
-        let max_duty = 65536; // Higher precision for better roundtrip
+    fn test_swim_angles_phase_offsets() {
+        // A quarter-cycle offset: each leg is where the previous one was a
+        // quarter period earlier
+        assert_eq!(swim_angles(Ms(0), Ms(1000), 40, 90), [90, 50, 90, 130]);
+        assert_eq!(swim_angles(Ms(250), Ms(1000), 40, 90), [130, 90, 50, 90]);
+        assert_eq!(swim_angles(Ms(1250), Ms(1000), 40, 90), [130, 90, 50, 90]);
+        assert_eq!(swim_angles(Ms(125), Ms(1000), 40, 0), [118; 4]);
 
-        for angle in (0..=180).step_by(10) {
-            let duty = angle_to_duty(angle, max_duty);
-            let recovered_angle = duty_to_angle(duty, max_duty);
-            let diff = (recovered_angle as i32 - angle as i32).abs();
+        // Amplitude is clamped so angles stay in range
+        assert_eq!(swim_angles(Ms(250), Ms(1000), 500, 180), [180, 0, 180, 0]);
+        assert_eq!(swim_angles(Ms(250), Ms(0), 40, 90), [90, 50, 90, 130]);
+    }
 
-            assert!(
-                diff <= 2,
-                "Roundtrip failed: {} → {} → {} (diff: {})",
-                angle,
-                duty,
-                recovered_angle,
-                diff
+    /// Test that swim samples the wave each PWM period and then centers
+    #[test]
+    fn test_swim() {
+        let (mut controller, log) = mock_controller();
+
+        controller.swim(Ms(100), 40, 90, 2).unwrap();
+
+        let samples: Vec<[u32; 4]> = (0..5)
+            .map(|i| swim_angles(Ms(i * 20), Ms(100), 40, 90))
+            .collect();
+        for leg in LEGS {
+            let expected: Vec<u32> = samples
+                .iter()
+                .chain(&samples)
+                .map(|angles| angle_to_duty(angles[leg.index()], 1024))
+                .chain([angle_to_duty(90, 1024)])
+                .collect();
+            assert_eq!(log.duties_for(leg), expected);
+        }
+        assert_eq!(log.now_ms(), 200);
+        assert_eq!(controller.current_angles(), [90; 4]);
+
+        log.clear();
+        controller.swim(Ms(100), 40, 90, 0).unwrap();
+        assert!(log.commands().is_empty());
+    }
+
+    /// Test that a fixed seed yields a known, in-range pose sequence
+    #[test]
+    fn test_wander_poses_deterministic() {
+        let poses = wander_poses(42, 3);
+
+        assert_eq!(
+            poses,
+            vec![[98, 64, 109, 63], [102, 81, 95, 113], [61, 109, 115, 70]]
+        );
+        assert_eq!(wander_poses(42, 3), poses);
+        assert_ne!(wander_poses(43, 3), poses);
+        assert!(
+            wander_poses(0, 100)
+                .iter()
+                .flatten()
+                .all(|angle| WANDER_RANGE.contains(angle))
+        );
+    }
+
+    /// Test that wander drives the generated poses with a delay after each
+    #[test]
+    fn test_wander_moves() {
+        let (mut controller, log) = mock_controller();
+
+        controller.wander(7, 5, Ms(40)).unwrap();
+
+        assert_eq!(
+            log.commands()
+                .iter()
+                .filter(|c| **c == Command::Delay(40))
+                .count(),
+            5
+        );
+        assert_eq!(controller.current_angles(), wander_poses(7, 5)[4]);
+    }
+
+    /// Test that a zero max duty is reported instead of writing zero duties
+    #[test]
+    fn test_zero_max_duty_is_an_error() {
+        let (mut controller, log) = mock_controller_with_max_duty(0);
+
+        let err = controller.check_timer_config().unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ServoError>(),
+            Some(&ServoError::InvalidTimerConfig {
+                leg: Leg::RightBack
+            })
+        );
+
+        for result in [
+            controller.set_leg_angle(Leg::LeftFront, 45),
+            controller.set_all_servos_angle(45),
+            controller.set_right_servos(45, 45),
+        ] {
+            let err = result.unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<ServoError>(),
+                Some(ServoError::InvalidTimerConfig { .. })
+            ));
+        }
+        assert!(log.duties().is_empty());
+    }
+
+    /// Test that a correction curve passes through its points and interpolates
+    #[test]
+    fn test_correction_curve() {
+        let curve = CorrectionCurve::new(&[(45, 30), (135, 150)]).unwrap();
+
+        assert_eq!(curve.apply(45), 30);
+        assert_eq!(curve.apply(135), 150);
+        assert_eq!(curve.apply(90), 90);
+        assert_eq!(curve.apply(60), 50);
+        assert_eq!(curve.apply(0), 0);
+        assert_eq!(curve.apply(180), 180);
+        assert_eq!(curve.apply(500), 180);
+
+        let identity = CorrectionCurve::default();
+        assert!((0..=180).all(|angle| identity.apply(angle) == angle));
+
+        let explicit_ends = CorrectionCurve::new(&[(0, 10), (180, 170)]).unwrap();
+        assert_eq!(explicit_ends.apply(90), 90);
+        assert_eq!(explicit_ends.apply(0), 10);
+
+        assert!(CorrectionCurve::new(&[(90, 90), (45, 45)]).is_err());
+        assert!(CorrectionCurve::new(&[(90, 200)]).is_err());
+    }
+
+    /// Test that the duty inverse round-trips through the calibration model
+    #[test]
+    fn test_angle_from_duty_round_trip() {
+        let (mut controller, _log) = mock_controller_with_max_duty(65_536);
+        controller.set_calibration(
+            Leg::RightBack,
+            LegCalibration {
+                min_pulse_us: 600,
+                max_pulse_us: 2400,
+                trim_deg: 5,
+                ..Default::default()
+            },
+        );
+        controller.set_calibration(
+            Leg::LeftBack,
+            LegCalibration {
+                trim_deg: -3,
+                inverted: true,
+                ..Default::default()
+            },
+        );
+        controller.set_correction_curve(
+            Leg::RightFront,
+            CorrectionCurve::new(&[(45, 30), (135, 150)]).unwrap(),
+        );
+
+        for leg in LEGS {
+            for angle in 0..=180 {
+                let duty = controller.duty_for(leg, angle).unwrap();
+                let recovered = controller.angle_from_duty(leg, duty);
+                // Trim pushes these past an end stop: they all read back as
+                // the last angle that reaches it
+                let expected = match leg {
+                    Leg::RightBack => angle.min(175),
+                    Leg::LeftBack => angle.min(177),
+                    _ => angle,
+                };
+                assert!(
+                    recovered.abs_diff(expected) <= 1,
+                    "{}: {angle}° → duty {duty} → {recovered}°",
+                    leg.name()
+                );
+            }
+        }
+
+        // Duties outside the pulse range clamp to the extremes
+        assert_eq!(controller.angle_from_duty(Leg::LeftFront, 0), 0);
+        assert_eq!(controller.angle_from_duty(Leg::LeftFront, 65_536), 180);
+    }
+
+    /// Test that a leg's curve is applied before the duty calculation
+    #[test]
+    fn test_correction_curve_applied_to_writes() {
+        let (mut controller, log) = mock_controller();
+        let curve = CorrectionCurve::new(&[(90, 100)]).unwrap();
+        controller.set_correction_curve(Leg::LeftBack, curve);
+
+        controller.set_leg_angle(Leg::LeftBack, 90).unwrap();
+        controller.set_all_servos_angle(90).unwrap();
+
+        let (center, corrected) = (angle_to_duty(90, 1024), angle_to_duty(100, 1024));
+        log.assert_duties(&[
+            (Leg::LeftBack, corrected),
+            (Leg::RightBack, center),
+            (Leg::LeftBack, corrected),
+            (Leg::RightFront, center),
+            (Leg::LeftFront, center),
+        ]);
+        assert_eq!(controller.current_angle(Leg::LeftBack), 90);
+    }
+
+    /// Distance source returning a fixed series of readings
+    struct StubObstacle(std::vec::IntoIter<Option<u32>>);
+
+    impl ObstacleSource for StubObstacle {
+        fn distance_cm(&mut self) -> Option<u32> {
+            self.0.next().flatten()
+        }
+    }
+
+    /// Test that the walk stops and centers at the first close reading
+    #[test]
+    fn test_walk_stops_before_obstacle() {
+        let (mut controller, log) = mock_controller();
+        let mut source = StubObstacle(vec![Some(80), None, Some(25), Some(5)].into_iter());
+
+        let stopped = controller
+            .walk_forward_with_obstacle(Ms(10), 30, &mut source)
+            .unwrap();
+
+        assert!(stopped);
+        // Two frames were walked before the stop, then the legs were centered
+        let delays = log
+            .commands()
+            .iter()
+            .filter(|c| **c == Command::Delay(10))
+            .count();
+        assert_eq!(delays, 2);
+        assert_eq!(controller.current_angles(), [90; 4]);
+        assert_eq!(
+            log.duties_for(Leg::RightBack),
+            vec![
+                angle_to_duty(45, 1024),
+                angle_to_duty(135, 1024),
+                angle_to_duty(90, 1024)
+            ]
+        );
+    }
+
+    /// Test that a clear path completes the whole walk
+    #[test]
+    fn test_walk_without_obstacle() {
+        let (mut controller, log) = mock_controller();
+        let mut source = StubObstacle(vec![Some(100); 5].into_iter());
+
+        let stopped = controller
+            .walk_forward_with_obstacle(Ms(10), 30, &mut source)
+            .unwrap();
+
+        assert!(!stopped);
+        let delays = log
+            .commands()
+            .iter()
+            .filter(|c| **c == Command::Delay(10))
+            .count();
+        assert_eq!(delays, 5);
+    }
+
+    /// Test that trim, inversion, pulse range and center shape the duty
+    #[test]
+    fn test_leg_calibration() {
+        let (mut controller, log) = mock_controller();
+        controller.set_calibration(
+            Leg::RightBack,
+            LegCalibration {
+                trim_deg: 10,
+                ..LegCalibration::default()
+            },
+        );
+        controller.set_calibration(
+            Leg::LeftBack,
+            LegCalibration {
+                inverted: true,
+                ..LegCalibration::default()
+            },
+        );
+        controller.set_calibration(
+            Leg::RightFront,
+            LegCalibration {
+                min_pulse_us: 1000,
+                max_pulse_us: 2000,
+                ..LegCalibration::default()
+            },
+        );
+        controller.set_calibration(
+            Leg::LeftFront,
+            LegCalibration {
+                center_deg: 80,
+                ..LegCalibration::default()
+            },
+        );
+
+        controller.set_servo_angles(0, 0, 180, 90).unwrap();
+        controller.center_all_servos().unwrap();
+
+        log.assert_duties(&[
+            (Leg::RightBack, angle_to_duty(10, 1024)),
+            (Leg::LeftBack, angle_to_duty(180, 1024)),
+            (
+                Leg::RightFront,
+                expected_duty(180, 1000, 2000, PERIOD_US, 1024),
+            ),
+            (Leg::LeftFront, angle_to_duty(90, 1024)),
+            (Leg::RightBack, angle_to_duty(100, 1024)),
+            (Leg::LeftBack, angle_to_duty(90, 1024)),
+            (
+                Leg::RightFront,
+                expected_duty(90, 1000, 2000, PERIOD_US, 1024),
+            ),
+            (Leg::LeftFront, angle_to_duty(80, 1024)),
+        ]);
+        // Tracked angles stay logical
+        assert_eq!(controller.current_angles(), [90, 90, 90, 80]);
+    }
+
+    /// Test that snapshot → mutate → restore returns every setting
+    #[test]
+    fn test_config_snapshot_restore() {
+        let (mut controller, _log) = mock_controller();
+        let snapshot = controller.config();
+        assert_eq!(snapshot, ControllerConfig::default());
+
+        controller.set_calibration(
+            Leg::LeftFront,
+            LegCalibration {
+                trim_deg: -7,
+                inverted: true,
+                center_deg: 100,
+                min_pulse_us: 600,
+                max_pulse_us: 2400,
+            },
+        );
+        controller.set_correction_curve(Leg::RightBack, CorrectionCurve::new(&[(90, 95)]).unwrap());
+        controller.set_motion_limits(MotionLimits {
+            max_speed_dps: 30.0,
+            max_accel_dps2: 60.0,
+        });
+        controller.set_leg_enabled(Leg::LeftBack, false);
+        controller.set_on_error(OnError::DetachAll);
+        controller.set_max_concurrent_moves(1);
+        controller.set_retry_policy(3, 10);
+        controller.set_park_pose([10, 20, 160, 170]);
+        controller.set_max_command_rate(25);
+        controller.set_rate_limit_policy(RateLimitPolicy::Coalesce);
+        controller.set_control_rate_hz(10);
+        controller.auto_center_after(Some(Duration::from_secs(5)));
+        controller.set_current_model(CurrentModel {
+            hold_ma: 25,
+            ..Default::default()
+        });
+        controller.add_forbidden_zone(Leg::RightFront, 60, 80);
+        controller.set_quantize(true);
+        controller.set_max_gait_duration(None);
+        controller.set_gait_lead_in_ms(200);
+        controller.set_write_strategy(WriteStrategy::Batched);
+        let tuned = controller.config();
+        assert_ne!(tuned, snapshot);
+
+        controller.apply_config(&snapshot);
+        assert_eq!(controller.config(), snapshot);
+        assert_eq!(
+            controller.calibration(Leg::LeftFront),
+            LegCalibration::default()
+        );
+        assert!(controller.is_leg_enabled(Leg::LeftBack));
+        assert_eq!(controller.on_error(), OnError::Abort);
+        assert_eq!(controller.max_concurrent_moves(), 4);
+        assert_eq!(controller.park_pose(), PARK_FLAT_POSE);
+        assert_eq!(controller.max_command_rate(), 0);
+        assert!(controller.forbidden_zones(Leg::RightFront).is_empty());
+        assert!(!controller.quantize());
+        assert_eq!(
+            controller.max_gait_duration(),
+            Some(DEFAULT_MAX_GAIT_DURATION)
+        );
+        assert_eq!(controller.gait_lead_in_ms(), 0);
+        assert_eq!(controller.write_strategy(), WriteStrategy::Immediate);
+
+        controller.apply_config(&tuned);
+        assert_eq!(controller.config(), tuned);
+    }
+
+    /// Test that limping parks the dead leg and steps the other three
+    #[test]
+    fn test_limp_gait() {
+        let (mut controller, log) = mock_controller();
+
+        controller.limp_gait(Leg::LeftBack, Ms(20)).unwrap();
+
+        let center = angle_to_duty(90, 1024);
+        assert_eq!(log.duties_for(Leg::LeftBack), vec![center]);
+        assert!(controller.is_detached(Leg::LeftBack));
+        for leg in [Leg::RightBack, Leg::RightFront, Leg::LeftFront] {
+            assert_eq!(
+                log.duties_for(leg),
+                vec![angle_to_duty(45, 1024), angle_to_duty(135, 1024), center],
+                "{} did not step",
+                leg.name()
             );
         }
+
+        // Detaching comes before any other leg moves
+        let commands = log.commands();
+        assert_eq!(commands[1], Command::Disable(Leg::LeftBack));
     }
 
-    /// Test ServoOperation struct
+    /// Test each clamp mode with an over-range angle
     #[test]
-    fn test_servo_operation() {
-        let op = ServoOperation {
-            angle: 90,
-            max_duty: 1024,
-            servo_name: "test_servo".to_string(),
+    fn test_clamp_modes() {
+        let (mut controller, log) = mock_controller();
+        assert_eq!(controller.clamp_mode(), ClampMode::Saturate);
+
+        controller.set_leg_angle(Leg::LeftFront, 200).unwrap();
+        log.assert_duties(&[(Leg::LeftFront, angle_to_duty(180, 1024))]);
+
+        log.clear();
+        controller.set_clamp_mode(ClampMode::Error);
+        let err = controller.set_leg_angle(Leg::LeftFront, 200).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ServoError>(),
+            Some(&ServoError::InvalidAngle {
+                leg: Leg::LeftFront,
+                degrees: 200.0
+            })
+        );
+        assert!(controller.set_all_servos_angle(181).is_err());
+        controller.set_leg_angle(Leg::LeftFront, 180).unwrap();
+        log.assert_duties(&[(Leg::LeftFront, angle_to_duty(180, 1024))]);
+
+        // 198° extrapolates to 2700 µs: 2700 * 1024 / 20000 = 138
+        log.clear();
+        controller.set_clamp_mode(ClampMode::None);
+        controller.set_leg_angle(Leg::LeftFront, 198).unwrap();
+        controller.set_all_servos_angle(198).unwrap();
+        assert_eq!(log.duties_for(Leg::LeftFront), vec![138, 138]);
+    }
+
+    /// Test that commands are clamped to a leg's soft limits
+    #[test]
+    fn test_soft_limits() {
+        let (mut controller, log) = mock_controller();
+        controller.set_soft_limits(Leg::RightFront, 150, 40);
+        assert_eq!(controller.soft_limits(Leg::RightFront), (40, 150));
+
+        controller.set_leg_angle(Leg::RightFront, 10).unwrap();
+        controller.set_leg_angle(Leg::RightFront, 170).unwrap();
+        controller.set_leg_angle(Leg::RightFront, 100).unwrap();
+        assert_eq!(
+            log.duties_for(Leg::RightFront),
+            [40, 150, 100].map(|a| angle_to_duty(a, 1024))
+        );
+        assert_eq!(controller.current_angle(Leg::RightFront), 100);
+
+        // Batch setters and other legs
+        log.clear();
+        controller.set_all_servos_angle(0).unwrap();
+        assert_eq!(controller.current_angles(), [0, 0, 40, 0]);
+        assert_eq!(
+            log.duties_for(Leg::RightFront),
+            vec![angle_to_duty(40, 1024)]
+        );
+        controller.set_servo_angles(180, 180, 180, 180).unwrap();
+        assert_eq!(controller.current_angles(), [180, 180, 150, 180]);
+        assert_eq!(controller.config().soft_limits[2], (40, 150));
+    }
+
+    /// Test that moves into or across a forbidden zone are rejected without
+    /// writing or recovering, and moves clear of it are unaffected
+    #[test]
+    fn test_forbidden_zone() {
+        let (mut controller, log) = mock_controller();
+        controller.set_on_error(OnError::CenterAll);
+        controller.add_forbidden_zone(Leg::RightFront, 80, 60);
+        assert_eq!(controller.forbidden_zones(Leg::RightFront), &[(60, 80)]);
+
+        controller.set_leg_angle(Leg::RightFront, 120).unwrap();
+        controller.set_leg_angle(Leg::RightFront, 100).unwrap();
+        controller.set_all_servos_angle(140).unwrap();
+        assert_eq!(controller.current_angles(), [140; 4]);
+
+        log.clear();
+        let err = controller.set_leg_angle(Leg::RightFront, 70).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ServoError>(),
+            Some(&ServoError::ForbiddenZone {
+                leg: Leg::RightFront,
+                from: 140,
+                to: 70,
+                zone: (60, 80),
+            })
+        );
+        assert_eq!(
+            err.to_string(),
+            "right_front_leg cannot move from 140° to 70° through forbidden zone 60-80°"
+        );
+        // Crossing without stopping in the zone, in a batch or smoothly
+        assert!(controller.set_leg_angle(Leg::RightFront, 30).is_err());
+        assert!(controller.set_servo_angles(30, 30, 30, 30).is_err());
+        assert!(controller.move_leg_smooth(Leg::RightFront, 0).is_err());
+        assert!(log.duties().is_empty(), "nothing written or recovered");
+        assert_eq!(controller.current_angles(), [140; 4]);
+
+        // Other legs, and this one up to the edge of the zone, still move
+        controller.set_servo_angles(30, 30, 81, 30).unwrap();
+        assert_eq!(controller.current_angles(), [30, 30, 81, 30]);
+
+        // A leg inside a zone may leave it
+        controller.clear_forbidden_zones(Leg::RightFront);
+        controller.set_leg_angle(Leg::RightFront, 70).unwrap();
+        controller.add_forbidden_zone(Leg::RightFront, 60, 80);
+        controller.set_leg_angle(Leg::RightFront, 50).unwrap();
+        assert_eq!(controller.current_angle(Leg::RightFront), 50);
+    }
+
+    /// Test that batched writes compute every duty before committing any,
+    /// while immediate writes commit leg by leg
+    #[test]
+    fn test_write_strategy() {
+        let (mut controller, log) = mock_controller();
+        let duty = |angle| angle_to_duty(angle, MOCK_MAX_DUTY);
+        assert_eq!(controller.write_strategy(), WriteStrategy::Immediate);
+        controller.set_clamp_mode(ClampMode::Error);
+
+        // Immediate: the right leg is written before the left is rejected
+        assert!(controller.set_front_servos(45, 200).is_err());
+        assert_eq!(
+            log.commands(),
+            vec![Command::Duty(Leg::RightFront, duty(45))]
+        );
+
+        // Batched: the rejected left leg stops the batch before any commit
+        controller.set_write_strategy(WriteStrategy::Batched);
+        log.clear();
+        assert!(controller.set_front_servos(30, 200).is_err());
+        assert!(log.commands().is_empty());
+        assert_eq!(controller.current_angle(Leg::RightFront), 45);
+
+        // Commits follow each other directly, paced only as configured
+        controller.set_back_servos(30, 150).unwrap();
+        controller.set_stagger_ms(15);
+        controller.set_front_servos(30, 150).unwrap();
+        assert_eq!(
+            log.commands(),
+            vec![
+                Command::Duty(Leg::RightBack, duty(30)),
+                Command::Duty(Leg::LeftBack, duty(150)),
+                Command::Duty(Leg::RightFront, duty(30)),
+                Command::Delay(15),
+                Command::Duty(Leg::LeftFront, duty(150)),
+            ]
+        );
+        assert_eq!(controller.current_angles(), [30, 150, 30, 150]);
+    }
+
+    /// Test that angles sharing a duty are tracked and written as one when
+    /// quantizing
+    #[test]
+    fn test_quantize() {
+        let duty = |angle| angle_to_duty(angle, MOCK_MAX_DUTY);
+        let a = (40..60).find(|&a| duty(a) == duty(a + 1)).unwrap();
+
+        let (mut controller, log) = mock_controller();
+        controller.set_leg_angle(Leg::RightBack, a).unwrap();
+        controller.set_leg_angle(Leg::RightBack, a + 1).unwrap();
+        assert_eq!(log.duties_for(Leg::RightBack), vec![duty(a); 2]);
+        assert_eq!(controller.current_angle(Leg::RightBack), a + 1);
+
+        let (mut controller, log) = mock_controller();
+        controller.set_quantize(true);
+        assert!(controller.quantize());
+        controller.set_leg_angle(Leg::RightBack, a).unwrap();
+        let snapped = controller.current_angle(Leg::RightBack);
+        assert_eq!(duty(snapped), duty(a));
+        controller.set_leg_angle(Leg::RightBack, a + 1).unwrap();
+        controller.set_all_servos_angle(a + 1).unwrap();
+        controller.move_leg_smooth(Leg::RightBack, a).unwrap();
+        assert_eq!(log.duties_for(Leg::RightBack), vec![duty(a)]);
+        assert_eq!(controller.current_angles(), [snapped; 4]);
+        assert_eq!(log.duties().len(), 4);
+
+        // Either angle snaps to the same one from anywhere
+        let (mut controller, _log) = mock_controller();
+        controller.set_quantize(true);
+        controller.set_servo_angles(a + 1, a, 0, 180).unwrap();
+        let [right_back, left_back, ..] = controller.current_angles();
+        assert_eq!((right_back, left_back), (snapped, snapped));
+    }
+
+    /// Test that commands within the deadband are skipped
+    #[test]
+    fn test_deadband() {
+        let (mut controller, log) = mock_controller();
+        controller.set_deadband(2);
+
+        // The first write always goes through, even at the assumed 90°
+        controller.set_leg_angle(Leg::RightBack, 90).unwrap();
+        controller.set_leg_angle(Leg::RightBack, 92).unwrap();
+        controller.set_leg_angle(Leg::RightBack, 88).unwrap();
+        assert_eq!(
+            log.duties_for(Leg::RightBack),
+            vec![angle_to_duty(90, 1024)]
+        );
+        assert_eq!(controller.current_angle(Leg::RightBack), 90);
+
+        // Beyond the deadband: applied
+        controller.set_leg_angle(Leg::RightBack, 93).unwrap();
+        assert_eq!(controller.current_angle(Leg::RightBack), 93);
+
+        // Batch setters skip only the legs within the deadband
+        controller.set_all_servos_angle(100).unwrap();
+        log.clear();
+        controller.set_servo_angles(101, 110, 99, 100).unwrap();
+        assert_eq!(
+            log.duties(),
+            vec![(Leg::LeftBack, angle_to_duty(110, 1024))]
+        );
+        controller.set_front_servos(102, 103).unwrap();
+        assert_eq!(log.duties().len(), 2);
+        assert_eq!(controller.current_angles(), [100, 110, 100, 103]);
+    }
+
+    /// Test that a failed channel leaves the other legs usable
+    #[test]
+    fn test_setup_with_failing_channel() {
+        let log = CommandLog::default();
+        let (mut controller, failed) = setup_with_factory(MockDelay::new(&log), |leg| {
+            if leg == Leg::LeftBack {
+                anyhow::bail!("GPIO22 is in use");
+            }
+            Ok(MockPwm::new(leg, 1024, &log))
+        });
+
+        assert_eq!(failed, vec![Leg::LeftBack]);
+        assert!(!controller.is_leg_enabled(Leg::LeftBack));
+        controller.check_timer_config().unwrap();
+
+        controller.set_all_servos_angle(45).unwrap();
+        controller.set_left_servos(10, 20).unwrap();
+        controller.detach_all().unwrap();
+        let duty = angle_to_duty(45, 1024);
+        log.assert_commands(&[
+            Command::Duty(Leg::RightBack, duty),
+            Command::Duty(Leg::RightFront, duty),
+            Command::Duty(Leg::LeftFront, duty),
+            Command::Duty(Leg::LeftFront, angle_to_duty(20, 1024)),
+            Command::Disable(Leg::RightBack),
+            Command::Disable(Leg::RightFront),
+            Command::Disable(Leg::LeftFront),
+        ]);
+
+        // Forcing the missing leg on fails cleanly
+        controller.set_leg_enabled(Leg::LeftBack, true);
+        assert!(controller.set_leg_angle(Leg::LeftBack, 90).is_err());
+    }
+
+    /// Test that a two-servo controller drives its legs and skips the others
+    #[test]
+    fn test_setup_with_two_legs() {
+        let log = CommandLog::default();
+        let mut controller = setup_with_legs(
+            [Leg::RightFront, Leg::LeftFront].map(|leg| (leg, MockPwm::new(leg, 1024, &log))),
+            MockDelay::new(&log),
+        )
+        .unwrap();
+
+        assert!(controller.is_leg_enabled(Leg::RightFront));
+        assert!(controller.is_leg_enabled(Leg::LeftFront));
+        assert!(!controller.is_leg_enabled(Leg::RightBack));
+        assert!(!controller.is_leg_enabled(Leg::LeftBack));
+        controller.check_timer_config().unwrap();
+
+        controller.set_all_servos_angle(45).unwrap();
+        controller.set_servo_angles(10, 20, 30, 40).unwrap();
+        controller.set_front_servos(100, 110).unwrap();
+        controller.set_back_servos(120, 130).unwrap();
+        controller.set_leg_angle(Leg::RightBack, 60).unwrap();
+        controller.center_all_servos().unwrap();
+        log.assert_duties(&[
+            (Leg::RightFront, angle_to_duty(45, 1024)),
+            (Leg::LeftFront, angle_to_duty(45, 1024)),
+            (Leg::RightFront, angle_to_duty(30, 1024)),
+            (Leg::LeftFront, angle_to_duty(40, 1024)),
+            (Leg::RightFront, angle_to_duty(100, 1024)),
+            (Leg::LeftFront, angle_to_duty(110, 1024)),
+            (Leg::RightFront, angle_to_duty(90, 1024)),
+            (Leg::LeftFront, angle_to_duty(90, 1024)),
+        ]);
+
+        // A leg given two channels is rejected
+        let err = setup_with_legs(
+            [Leg::LeftBack, Leg::LeftBack].map(|leg| (leg, MockPwm::new(leg, 1024, &log))),
+            MockDelay::new(&log),
+        )
+        .err()
+        .unwrap();
+        assert!(matches!(
+            err.downcast_ref::<ServoError>(),
+            Some(ServoError::InvalidWiring)
+        ));
+    }
+
+    /// Test that read-back duties match what was written
+    #[test]
+    fn test_verify_duties_match() {
+        let (mut controller, _log) = mock_controller();
+        // Nothing written yet: nothing to check
+        assert_eq!(controller.verify_duties(), Ok(()));
+
+        controller.set_servo_angles(0, 45, 90, 180).unwrap();
+        assert_eq!(
+            controller.read_duty(Leg::RightFront),
+            angle_to_duty(90, 1024)
+        );
+        assert_eq!(controller.verify_duties(), Ok(()));
+    }
+
+    /// Test that writes that didn't take are listed with expected and actual
+    #[test]
+    fn test_verify_duties_mismatch() {
+        let (mut controller, log) = mock_controller();
+        controller.set_all_servos_angle(90).unwrap();
+
+        log.ignore_writes(Leg::LeftBack, 1);
+        log.ignore_writes(Leg::LeftFront, 1);
+        controller.set_all_servos_angle(180).unwrap();
+
+        let (center, full) = (angle_to_duty(90, 1024), angle_to_duty(180, 1024));
+        assert_eq!(
+            controller.verify_duties(),
+            Err(vec![
+                (Leg::LeftBack, full, center),
+                (Leg::LeftFront, full, center)
+            ])
+        );
+
+        // A detached leg is not checked
+        controller.detach_leg(Leg::LeftBack).unwrap();
+        controller.set_leg_angle(Leg::LeftFront, 180).unwrap();
+        assert_eq!(controller.verify_duties(), Ok(()));
+    }
+
+    /// Test that setters wait the settle time after writing, and only then
+    #[test]
+    fn test_settle_time() {
+        let (mut controller, log) = mock_controller();
+        controller.set_leg_angle(Leg::RightBack, 45).unwrap();
+        assert_eq!(log.now_ms(), 0);
+
+        controller.set_settle_time_ms(150);
+        assert_eq!(controller.config().settle_time_ms, 150);
+        controller.set_leg_angle(Leg::RightBack, 60).unwrap();
+        assert_eq!(log.now_ms(), 150);
+        assert_eq!(log.records().last().unwrap().command, Command::Delay(150));
+
+        controller.set_servo_angles(10, 20, 30, 40).unwrap();
+        controller.set_front_servos(80, 100).unwrap();
+        assert_eq!(log.now_ms(), 450);
+
+        // A smooth move settles once, after its last step
+        log.clear();
+        controller.move_leg_smooth(Leg::RightBack, 70).unwrap();
+        let delays: Vec<Command> = log
+            .commands()
+            .into_iter()
+            .filter(|c| matches!(c, Command::Delay(_)))
+            .collect();
+        assert_eq!(delays.last(), Some(&Command::Delay(150)));
+        assert_eq!(
+            delays.iter().filter(|&&c| c == Command::Delay(150)).count(),
+            1
+        );
+
+        // A failed write returns without settling
+        let before = log.now_ms();
+        log.fail_writes(Leg::LeftBack, 1);
+        assert!(controller.set_leg_angle(Leg::LeftBack, 90).is_err());
+        assert_eq!(log.now_ms(), before);
+    }
+
+    /// Test that per-call timing slows one move and leaves the stored
+    /// settings as they were
+    #[test]
+    fn test_per_call_timing() {
+        let (mut controller, log) = mock_controller();
+        controller.set_settle_time_ms(20);
+        let limits = controller.motion_limits();
+        let slow = Timing {
+            motion_limits: Some(MotionLimits {
+                max_speed_dps: 30.0,
+                max_accel_dps2: 60.0,
+            }),
+            settle_time_ms: Some(500),
+            lead_in_ms: None,
         };
 
-        assert_eq!(op.angle, 90);
-        assert_eq!(op.max_duty, 1024);
-        assert_eq!(op.servo_name, "test_servo");
+        controller.move_leg_smooth(Leg::RightBack, 0).unwrap();
+        let normal_ms = log.now_ms();
+        controller
+            .move_leg_smooth_with(Leg::RightBack, 90, slow)
+            .unwrap();
+        let slow_ms = log.now_ms() - normal_ms;
+        let profile = TrapezoidalProfile::new(0, 90, 30.0, 60.0);
+        assert!(slow_ms > normal_ms);
+        assert!(slow_ms >= u64::from(profile.duration_ms()) + 500);
+        assert_eq!(log.commands().last(), Some(&Command::Delay(500)));
+
+        assert_eq!(controller.motion_limits(), limits);
+        assert_eq!(controller.settle_time_ms(), 20);
+
+        // Gaits settle after every frame with the override, then restore
+        let gait = crate::gaits::walk(45, Ms(100));
+        let start = log.now_ms();
+        controller
+            .play_sequence_with(
+                &gait,
+                Timing {
+                    settle_time_ms: Some(0),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(log.now_ms() - start, 5 * 100);
+        assert_eq!(controller.settle_time_ms(), 20);
+
+        // An empty override changes nothing
+        let start = log.now_ms();
+        controller
+            .play_sequence_with(&gait, Timing::default())
+            .unwrap();
+        assert_eq!(log.now_ms() - start, 5 * (100 + 20));
+
+        // Settings are restored when the call fails
+        log.fail_writes(Leg::LeftBack, 1);
+        assert!(controller.play_sequence_with(&gait, slow).is_err());
+        assert_eq!(controller.motion_limits(), limits);
+        assert_eq!(controller.settle_time_ms(), 20);
     }
 
-    /// Test pulse width calculation
+    /// Test the builder records steps without executing them
     #[test]
-    fn test_pulse_width_calculation() {
-        assert_eq!(angle_to_pulse_width(0), MIN_PULSE_US);
-        assert_eq!(angle_to_pulse_width(90), 1500);
-        assert_eq!(angle_to_pulse_width(180), MAX_PULSE_US);
+    fn test_movement_builder_steps() {
+        let builder = MovementBuilder::new().angles(1, 2, 3, 4).delay(Ms(5));
+        assert_eq!(
+            builder.steps(),
+            &[MovementStep::Angles([1, 2, 3, 4]), MovementStep::Delay(5)]
+        );
     }
 }
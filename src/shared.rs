@@ -0,0 +1,168 @@
+//! # Shared Controller
+//!
+//! A cloneable handle for driving one controller from several tasks, e.g. a
+//! background task playing a gait while another handles console commands.
+//!
+//! Every call locks the controller for its full duration, so calls from
+//! different tasks never interleave their writes. That includes blocking
+//! movements: a `walk_forward` holds the lock through all of its delays.
+//!
+//! ## Priority inversion
+//!
+//! On ESP-IDF `std::sync::Mutex` is a FreeRTOS mutex, which raises the holder
+//! to the priority of the highest waiting task. That bounds the inversion but
+//! does not shorten it: a high-priority task still waits for the whole call in
+//! progress. Keep locked sections short:
+//!
+//! - Drive long movements frame by frame (e.g. `GaitRunner::poll` in a loop),
+//!   locking per frame rather than around a whole blocking gait.
+//! - Don't wait on other locks or queues inside `with`.
+//! - Give tasks that share the controller similar priorities where possible.
+
+use crate::{Delay, Gait, Leg, PwmBackend, ServoController};
+use anyhow::Result;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Thread-safe shared handle to a `ServoController`
+///
+/// Clones refer to the same controller. If a task panics while holding the
+/// lock, later calls still proceed with the controller as the panicking call
+/// left it.
+pub struct SharedController<P: PwmBackend, D: Delay> {
+    inner: Arc<Mutex<ServoController<P, D>>>,
+}
+
+impl<P: PwmBackend, D: Delay> Clone for SharedController<P, D> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<P: PwmBackend, D: Delay> SharedController<P, D> {
+    /// Share `controller` between tasks
+    pub fn new(controller: ServoController<P, D>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(controller)),
+        }
+    }
+
+    /// Lock the controller until the guard is dropped
+    pub fn lock(&self) -> MutexGuard<'_, ServoController<P, D>> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Run `f` with the controller locked
+    pub fn with<R>(&self, f: impl FnOnce(&mut ServoController<P, D>) -> R) -> R {
+        f(&mut self.lock())
+    }
+
+    /// Set a single leg to the given angle
+    pub fn set_leg_angle(&self, leg: Leg, angle: u32) -> Result<()> {
+        self.lock().set_leg_angle(leg, angle)
+    }
+
+    /// Set all servos to the same angle
+    pub fn set_all_servos_angle(&self, angle: u32) -> Result<()> {
+        self.lock().set_all_servos_angle(angle)
+    }
+
+    /// Set all four legs, in `set_servo_angles` order
+    pub fn set_servo_angles(&self, angles: [u32; 4]) -> Result<()> {
+        let [right_back, left_back, right_front, left_front] = angles;
+        self.lock()
+            .set_servo_angles(right_back, left_back, right_front, left_front)
+    }
+
+    /// Center all servos
+    pub fn center_all_servos(&self) -> Result<()> {
+        self.lock().center_all_servos()
+    }
+
+    /// Move all legs to a stored pose
+    pub fn goto_pose(&self, name: &str) -> Result<()> {
+        self.lock().goto_pose(name)
+    }
+
+    /// Play every frame of a gait once (holds the lock throughout)
+    pub fn play_sequence(&self, gait: &Gait) -> Result<()> {
+        self.lock().play_sequence(gait)
+    }
+
+    /// Stop driving every leg
+    pub fn detach_all(&self) -> Result<()> {
+        self.lock().detach_all()
+    }
+
+    /// Last commanded angle of every leg
+    pub fn current_angles(&self) -> [u32; 4] {
+        self.lock().current_angles()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::angle_to_duty;
+    use crate::test_support::*;
+    use std::thread;
+
+    /// Test that batch writes from two threads never interleave
+    #[test]
+    fn test_concurrent_calls_serialize() {
+        let (controller, log) = mock_controller();
+        let shared = SharedController::new(controller);
+
+        let handles: Vec<_> = [0, 180]
+            .into_iter()
+            .map(|angle| {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    for _ in 0..50 {
+                        shared.set_all_servos_angle(angle).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let duties = log.duties();
+        assert_eq!(duties.len(), 2 * 50 * 4);
+        for batch in duties.chunks(4) {
+            assert!(
+                batch.iter().all(|&(_, duty)| duty == batch[0].1),
+                "interleaved batch: {batch:?}"
+            );
+        }
+        let final_angle = shared.current_angles()[0];
+        assert!(final_angle == 0 || final_angle == 180);
+        assert_eq!(shared.current_angles(), [final_angle; 4]);
+    }
+
+    /// Test that a closure runs under one lock and a panic does not wedge it
+    #[test]
+    fn test_with_and_poisoned_lock() {
+        let (controller, log) = mock_controller();
+        let shared = SharedController::new(controller);
+
+        let other = shared.clone();
+        let result = thread::spawn(move || {
+            other.with(|controller| {
+                controller.set_leg_angle(Leg::RightBack, 10).unwrap();
+                panic!("task failed mid-move");
+            })
+        })
+        .join();
+        assert!(result.is_err());
+
+        shared.set_servo_angles([20, 30, 40, 50]).unwrap();
+        assert_eq!(shared.current_angles(), [20, 30, 40, 50]);
+        assert_eq!(
+            log.duties_for(Leg::RightBack),
+            vec![angle_to_duty(10, 1024), angle_to_duty(20, 1024)]
+        );
+    }
+}
@@ -0,0 +1,238 @@
+//! # Support
+//!
+//! A rough stability check for a pose: which legs are planted and whether
+//! they hold the body up.
+//!
+//! This is a heuristic, not a physics model. A leg counts as planted when its
+//! angle is near `PLANT_ANGLE`, where the gaits put a leg down. The body is
+//! taken to be stable when at least two pairs of neighboring legs (legs
+//! sharing a side of the body) are planted, which means three or four legs
+//! are down. Two planted legs never count as stable: diagonal legs leave the
+//! body balanced on a line, neighboring legs leave the other side unsupported.
+//!
+//! ## Payload compensation
+//!
+//! `ServoController::set_payload_offset` describes a load carried off-center.
+//! Every planted leg is then biased by `payload_bias`: the legs on the
+//! payload's side are raised, the legs on the far side lowered, so the body
+//! sits level again. This is a fixed correction for a known load, not active
+//! balancing; swinging legs are left alone.
+
+use crate::{Delay, LEGS, Leg, PwmBackend, ServoController};
+
+/// Angle at which a leg is down on the ground
+pub const PLANT_ANGLE: u32 = 90;
+
+/// Largest distance from `PLANT_ANGLE` at which a leg still counts as planted
+pub const PLANT_TOLERANCE_DEG: u32 = 20;
+
+/// Legs sharing a side of the body: back, front, right and left
+const NEIGHBORS: [(Leg, Leg); 4] = [
+    (Leg::RightBack, Leg::LeftBack),
+    (Leg::RightFront, Leg::LeftFront),
+    (Leg::RightBack, Leg::RightFront),
+    (Leg::LeftBack, Leg::LeftFront),
+];
+
+/// Per-leg stance bias for a payload offset, in `set_servo_angles` order
+///
+/// `fore_aft` is positive toward the front, `left_right` positive toward the
+/// left, both in degrees of correction. Each leg is biased by the components
+/// pointing to its side of the body and away from the far side.
+pub fn payload_bias(fore_aft: i32, left_right: i32) -> [i32; 4] {
+    LEGS.map(|leg| {
+        let front = matches!(leg, Leg::RightFront | Leg::LeftFront);
+        let left = matches!(leg, Leg::LeftBack | Leg::LeftFront);
+        let fore_aft = if front { fore_aft } else { -fore_aft };
+        let left_right = if left { left_right } else { -left_right };
+        fore_aft.saturating_add(left_right)
+    })
+}
+
+/// Whether a leg at `angle` is planted
+fn is_planted_angle(angle: u32) -> bool {
+    angle.abs_diff(PLANT_ANGLE) <= PLANT_TOLERANCE_DEG
+}
+
+/// Result of `ServoController::support_state`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SupportInfo {
+    /// Whether each leg is planted, indexed by `Leg::index`
+    pub planted: [bool; 4],
+    /// Pairs of neighboring legs that are both planted (0-4)
+    pub planted_pairs: usize,
+    /// Whether the planted legs are expected to hold the body up
+    pub stable: bool,
+}
+
+impl SupportInfo {
+    /// Classify the legs of a pose, given in `set_servo_angles` order
+    pub fn from_angles(angles: [u32; 4]) -> Self {
+        let planted = angles.map(is_planted_angle);
+        let planted_pairs = NEIGHBORS
+            .iter()
+            .filter(|(a, b)| planted[a.index()] && planted[b.index()])
+            .count();
+        Self {
+            planted,
+            planted_pairs,
+            stable: planted_pairs >= 2,
+        }
+    }
+
+    /// Whether `leg` is planted
+    pub fn is_planted(&self, leg: Leg) -> bool {
+        self.planted[leg.index()]
+    }
+
+    /// Legs that are planted, in `set_servo_angles` order
+    pub fn planted_legs(&self) -> Vec<Leg> {
+        LEGS.into_iter()
+            .filter(|&leg| self.is_planted(leg))
+            .collect()
+    }
+}
+
+impl<P: PwmBackend, D: Delay> ServoController<P, D> {
+    /// Which legs a pose would plant and whether the robot would be stable
+    ///
+    /// `angles` are limited to each leg's soft limits first, as they would be
+    /// when commanded. Nothing is moved.
+    pub fn support_state(&self, angles: [u32; 4]) -> SupportInfo {
+        SupportInfo::from_angles(LEGS.map(|leg| self.limit_angle(leg, angles[leg.index()])))
+    }
+
+    /// Compensate the stance for a payload carried off-center
+    ///
+    /// From the next write, every command that plants a leg is shifted by the
+    /// leg's `payload_bias` and clamped to 0-180° and its soft limits. The
+    /// tracked angles stay as commanded. `(0, 0)`, the default, turns the
+    /// compensation off.
+    pub fn set_payload_offset(&mut self, fore_aft: i32, left_right: i32) {
+        self.payload_offset = (fore_aft, left_right);
+    }
+
+    /// Payload offset set with `set_payload_offset` as `(fore_aft, left_right)`
+    pub fn payload_offset(&self) -> (i32, i32) {
+        self.payload_offset
+    }
+
+    /// Angle actually driven for a command to `leg`: biased for the payload if
+    /// it plants the leg, unchanged otherwise
+    pub(crate) fn stance_angle(&self, leg: Leg, angle: u32) -> u32 {
+        if !is_planted_angle(angle) {
+            return angle;
+        }
+        let (fore_aft, left_right) = self.payload_offset;
+        let bias = payload_bias(fore_aft, left_right)[leg.index()];
+        angle.saturating_add_signed(bias).min(180)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::angle_to_duty;
+    use crate::test_support::*;
+
+    /// Test that four planted legs are stable
+    #[test]
+    fn test_all_planted() {
+        let (controller, _log) = mock_controller();
+
+        let support = controller.support_state([90, 100, 80, 110]);
+
+        assert_eq!(support.planted, [true; 4]);
+        assert_eq!(support.planted_pairs, 4);
+        assert!(support.stable);
+    }
+
+    /// Test that three planted legs form a stable tripod
+    #[test]
+    fn test_three_planted() {
+        let (controller, _log) = mock_controller();
+
+        let support = controller.support_state([90, 90, 45, 90]);
+
+        assert!(!support.is_planted(Leg::RightFront));
+        assert_eq!(
+            support.planted_legs(),
+            vec![Leg::RightBack, Leg::LeftBack, Leg::LeftFront]
+        );
+        assert_eq!(support.planted_pairs, 2);
+        assert!(support.stable);
+    }
+
+    /// Test that two planted legs are unstable, diagonal or not
+    #[test]
+    fn test_two_planted() {
+        let (controller, _log) = mock_controller();
+
+        let diagonal = controller.support_state([90, 45, 45, 90]);
+        assert_eq!(
+            diagonal.planted_legs(),
+            vec![Leg::RightBack, Leg::LeftFront]
+        );
+        assert_eq!(diagonal.planted_pairs, 0);
+        assert!(!diagonal.stable);
+
+        let back = controller.support_state([90, 90, 135, 135]);
+        assert_eq!(back.planted_pairs, 1);
+        assert!(!back.stable);
+    }
+
+    /// Test that angles are judged where the soft limits would put them
+    #[test]
+    fn test_soft_limits_apply() {
+        let (mut controller, _log) = mock_controller();
+        controller.set_soft_limits(Leg::RightFront, 75, 105);
+
+        assert!(
+            controller
+                .support_state([90, 90, 0, 90])
+                .is_planted(Leg::RightFront)
+        );
+        assert!(!SupportInfo::from_angles([90, 90, 0, 90]).is_planted(Leg::RightFront));
+    }
+
+    /// Test that a payload on the left raises the left legs and lowers the
+    /// right ones
+    #[test]
+    fn test_payload_bias() {
+        assert_eq!(payload_bias(0, 0), [0; 4]);
+        assert_eq!(payload_bias(0, 10), [-10, 10, -10, 10]);
+        assert_eq!(payload_bias(4, 0), [-4, -4, 4, 4]);
+        assert_eq!(payload_bias(4, 10), [-14, 6, -6, 14]);
+    }
+
+    /// Test that the bias shifts planted legs only, clamped, and leaves the
+    /// tracked angles as commanded
+    #[test]
+    fn test_payload_offset_applies_to_stance() {
+        let (mut controller, log) = mock_controller();
+        controller.set_payload_offset(0, 10);
+        assert_eq!(controller.payload_offset(), (0, 10));
+        assert_eq!(controller.config().payload_offset, (0, 10));
+
+        controller.set_servo_angles(90, 90, 90, 45).unwrap();
+        log.assert_duties(&[
+            (Leg::RightBack, angle_to_duty(80, MOCK_MAX_DUTY)),
+            (Leg::LeftBack, angle_to_duty(100, MOCK_MAX_DUTY)),
+            (Leg::RightFront, angle_to_duty(80, MOCK_MAX_DUTY)),
+            (Leg::LeftFront, angle_to_duty(45, MOCK_MAX_DUTY)),
+        ]);
+        assert_eq!(controller.current_angles(), [90, 90, 90, 45]);
+
+        // Biased stance angles are clamped to the soft limits
+        log.clear();
+        controller.set_payload_offset(0, 30);
+        controller.set_soft_limits(Leg::LeftBack, 0, 100);
+        controller.set_leg_angle(Leg::LeftBack, 95).unwrap();
+        log.assert_duties(&[(Leg::LeftBack, angle_to_duty(100, MOCK_MAX_DUTY))]);
+
+        log.clear();
+        controller.set_payload_offset(0, 0);
+        controller.set_leg_angle(Leg::RightBack, 91).unwrap();
+        log.assert_duties(&[(Leg::RightBack, angle_to_duty(91, MOCK_MAX_DUTY))]);
+    }
+}
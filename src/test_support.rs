@@ -0,0 +1,416 @@
+//! # Test Support
+//!
+//! Mock hardware for running controller logic off-device.
+//!
+//! `MockPwm` and `MockDelay` share a `CommandLog` that records every duty write
+//! and delay in execution order. Delays advance a simulated clock instead of
+//! blocking, so each record carries the simulated time at which it happened.
+//! `MockClock` stands in for the controller's `Clock`: `mock_controller`
+//! installs the log's clock, which every delay moves forward, and a test can
+//! also advance it directly or install a clock of its own.
+//!
+//! ```rust,ignore
+//! let (mut controller, log) = mock_controller();
+//! controller.center_all_servos()?;
+//! log.assert_duties(&[(Leg::RightBack, 76), /* ... */]);
+//! ```
+
+use crate::{Clock, Delay, Leg, PwmBackend, ServoController};
+use anyhow::Result;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Default max duty reported by `MockPwm` (ESP32 10-bit LEDC)
+pub const MOCK_MAX_DUTY: u32 = 1024;
+
+/// A hardware command captured by the mocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// `set_duty` on a leg's channel
+    Duty(Leg, u32),
+    /// `delay_ms` on the controller's delay provider
+    Delay(u32),
+    /// `disable` on a leg's channel
+    Disable(Leg),
+    /// `enable` on a leg's channel
+    Enable(Leg),
+}
+
+/// A command together with the simulated time it was issued
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Record {
+    pub at_ms: u64,
+    pub command: Command,
+}
+
+#[derive(Debug, Default)]
+struct LogState {
+    now_ms: u64,
+    /// Clock advanced by every delay, alongside `now_ms`
+    clock: MockClock,
+    records: Vec<Record>,
+    /// Remaining injected `set_duty` failures per leg
+    pending_failures: [u32; 4],
+    /// Remaining `set_duty` calls per leg that succeed without taking effect
+    pending_ignored: [u32; 4],
+    /// Duty held by each leg's channel, as read back by `get_duty`
+    registers: [u32; 4],
+}
+
+/// Shared, ordered log of every command sent to the mock hardware
+///
+/// Thread-safe, so mock controllers can be shared between threads.
+#[derive(Debug, Clone, Default)]
+pub struct CommandLog(Arc<Mutex<LogState>>);
+
+impl CommandLog {
+    fn state(&self) -> MutexGuard<'_, LogState> {
+        self.0.lock().unwrap()
+    }
+
+    fn push(&self, command: Command) {
+        let mut state = self.state();
+        let at_ms = state.now_ms;
+        state.records.push(Record { at_ms, command });
+    }
+
+    /// Make the next `count` duty writes to `leg` fail (they are not recorded)
+    pub fn fail_writes(&self, leg: Leg, count: u32) {
+        self.state().pending_failures[leg.index()] = count;
+    }
+
+    /// Make the next `count` duty writes to `leg` succeed without changing the
+    /// channel's duty (they are still recorded)
+    pub fn ignore_writes(&self, leg: Leg, count: u32) {
+        self.state().pending_ignored[leg.index()] = count;
+    }
+
+    /// Store a written duty in `leg`'s channel unless the write is ignored
+    fn store_duty(&self, leg: Leg, duty: u32) {
+        let mut state = self.state();
+        if state.pending_ignored[leg.index()] > 0 {
+            state.pending_ignored[leg.index()] -= 1;
+        } else {
+            state.registers[leg.index()] = duty;
+        }
+    }
+
+    /// Consume one injected failure for `leg`, if any
+    fn take_failure(&self, leg: Leg) -> bool {
+        let mut state = self.state();
+        let pending = &mut state.pending_failures[leg.index()];
+        if *pending > 0 {
+            *pending -= 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Current simulated time (sum of all delays so far)
+    pub fn now_ms(&self) -> u64 {
+        self.state().now_ms
+    }
+
+    /// Clock advanced by every `MockDelay` writing into this log
+    pub fn clock(&self) -> MockClock {
+        self.state().clock.clone()
+    }
+
+    /// All records with timestamps
+    pub fn records(&self) -> Vec<Record> {
+        self.state().records.clone()
+    }
+
+    /// All commands without timestamps
+    pub fn commands(&self) -> Vec<Command> {
+        self.state().records.iter().map(|r| r.command).collect()
+    }
+
+    /// Duty writes only, in order
+    pub fn duties(&self) -> Vec<(Leg, u32)> {
+        self.commands()
+            .into_iter()
+            .filter_map(|c| match c {
+                Command::Duty(leg, duty) => Some((leg, duty)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Duty writes to one leg, in order
+    pub fn duties_for(&self, leg: Leg) -> Vec<u32> {
+        self.duties()
+            .into_iter()
+            .filter(|&(l, _)| l == leg)
+            .map(|(_, duty)| duty)
+            .collect()
+    }
+
+    /// Forget all records (the simulated clock keeps running)
+    pub fn clear(&self) {
+        self.state().records.clear();
+    }
+
+    /// Assert the exact sequence of commands
+    #[track_caller]
+    pub fn assert_commands(&self, expected: &[Command]) {
+        assert_eq!(self.commands(), expected, "unexpected command sequence");
+    }
+
+    /// Assert the exact sequence of duty writes, ignoring delays
+    #[track_caller]
+    pub fn assert_duties(&self, expected: &[(Leg, u32)]) {
+        assert_eq!(self.duties(), expected, "unexpected duty sequence");
+    }
+}
+
+/// PWM channel that records every duty write
+#[derive(Debug)]
+pub struct MockPwm {
+    leg: Leg,
+    max_duty: u32,
+    log: CommandLog,
+}
+
+impl MockPwm {
+    /// Create a mock channel for `leg` writing into `log`
+    pub fn new(leg: Leg, max_duty: u32, log: &CommandLog) -> Self {
+        Self {
+            leg,
+            max_duty,
+            log: log.clone(),
+        }
+    }
+}
+
+impl PwmBackend for MockPwm {
+    fn get_max_duty(&self) -> u32 {
+        self.max_duty
+    }
+
+    fn set_duty(&mut self, duty: u32) -> Result<()> {
+        if self.log.take_failure(self.leg) {
+            anyhow::bail!("injected write failure on {}", self.leg.name());
+        }
+        self.log.push(Command::Duty(self.leg, duty));
+        self.log.store_duty(self.leg, duty);
+        Ok(())
+    }
+
+    fn get_duty(&self) -> u32 {
+        self.log.state().registers[self.leg.index()]
+    }
+
+    fn disable(&mut self) -> Result<()> {
+        self.log.push(Command::Disable(self.leg));
+        Ok(())
+    }
+
+    fn enable(&mut self) -> Result<()> {
+        self.log.push(Command::Enable(self.leg));
+        Ok(())
+    }
+}
+
+/// Delay that advances the simulated clock and its `MockClock` instead of
+/// blocking
+#[derive(Debug)]
+pub struct MockDelay {
+    log: CommandLog,
+}
+
+impl MockDelay {
+    /// Create a delay provider advancing `log`'s clock
+    pub fn new(log: &CommandLog) -> Self {
+        Self { log: log.clone() }
+    }
+}
+
+impl Delay for MockDelay {
+    fn delay_ms(&mut self, ms: u32) {
+        self.log.push(Command::Delay(ms));
+        let mut state = self.log.state();
+        state.now_ms += u64::from(ms);
+        state.clock.advance(u64::from(ms));
+    }
+}
+
+/// Clock that stands still until `advance`d
+///
+/// Clones share the same time, so a test can keep one and hand another to
+/// the controller with `with_clock`.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    start: Instant,
+    elapsed: Arc<Mutex<Duration>>,
+}
+
+impl MockClock {
+    /// A clock at its start time
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            elapsed: Arc::default(),
+        }
+    }
+
+    /// Move the clock forward by `ms`
+    pub fn advance(&self, ms: u64) {
+        *self.elapsed.lock().unwrap() += Duration::from_millis(ms);
+    }
+
+    /// Time `ms` after the clock's start
+    pub fn at(&self, ms: u64) -> Instant {
+        self.start + Duration::from_millis(ms)
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.start + *self.elapsed.lock().unwrap()
+    }
+}
+
+/// Controller wired to mock hardware
+pub type MockController = ServoController<MockPwm, MockDelay>;
+
+/// Controller with four `MockPwm` channels at `MOCK_MAX_DUTY`
+pub fn mock_controller() -> (MockController, CommandLog) {
+    mock_controller_with_max_duty(MOCK_MAX_DUTY)
+}
+
+/// Controller with four `MockPwm` channels reporting `max_duty`, timed by
+/// the log's clock
+pub fn mock_controller_with_max_duty(max_duty: u32) -> (MockController, CommandLog) {
+    let log = CommandLog::default();
+    let controller = ServoController::new(
+        MockPwm::new(Leg::RightBack, max_duty, &log),
+        MockPwm::new(Leg::LeftBack, max_duty, &log),
+        MockPwm::new(Leg::RightFront, max_duty, &log),
+        MockPwm::new(Leg::LeftFront, max_duty, &log),
+        MockDelay::new(&log),
+    )
+    .with_clock(log.clock());
+    (controller, log)
+}
+
+/// Logger capturing every record as `(target, message)`
+#[cfg(feature = "logging")]
+struct CapturingLogger(std::sync::Mutex<Vec<(String, String)>>);
+
+#[cfg(feature = "logging")]
+impl log::Log for CapturingLogger {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        self.0
+            .lock()
+            .unwrap()
+            .push((record.target().to_string(), record.args().to_string()));
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(feature = "logging")]
+static LOGGER: CapturingLogger = CapturingLogger(std::sync::Mutex::new(Vec::new()));
+
+/// Install the capturing logger (once per test binary) at the most verbose level
+#[cfg(feature = "logging")]
+pub fn capture_logs() {
+    static INSTALL: std::sync::Once = std::sync::Once::new();
+    INSTALL.call_once(|| {
+        log::set_logger(&LOGGER).expect("another logger is installed");
+        log::set_max_level(log::LevelFilter::Trace);
+    });
+}
+
+/// Messages captured so far for `target`
+///
+/// The logger is global and tests run in parallel, so use a target unique to
+/// the test. Call `capture_logs` before the code under test runs.
+#[cfg(feature = "logging")]
+pub fn captured_logs(target: &str) -> Vec<String> {
+    LOGGER
+        .0
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(t, _)| t == target)
+        .map(|(_, message)| message.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::angle_to_duty;
+
+    /// Test that centering produces one center-duty write per leg
+    #[test]
+    fn test_center_all_servos_writes() {
+        let (mut controller, log) = mock_controller();
+
+        controller.center_all_servos().unwrap();
+
+        let center = angle_to_duty(90, MOCK_MAX_DUTY);
+        log.assert_duties(&[
+            (Leg::RightBack, center),
+            (Leg::LeftBack, center),
+            (Leg::RightFront, center),
+            (Leg::LeftFront, center),
+        ]);
+    }
+
+    /// Test that delays advance the simulated clock and stamp later writes
+    #[test]
+    fn test_records_are_timestamped() {
+        let (mut controller, log) = mock_controller();
+
+        controller.set_leg_angle(Leg::LeftFront, 0).unwrap();
+        controller.delay_ms(250);
+        controller.set_leg_angle(Leg::LeftFront, 180).unwrap();
+
+        assert_eq!(log.now_ms(), 250);
+        assert_eq!(
+            controller.now(),
+            log.clock().at(250),
+            "controller clock follows"
+        );
+        assert_eq!(
+            log.records(),
+            vec![
+                Record {
+                    at_ms: 0,
+                    command: Command::Duty(Leg::LeftFront, angle_to_duty(0, 1024)),
+                },
+                Record {
+                    at_ms: 0,
+                    command: Command::Delay(250),
+                },
+                Record {
+                    at_ms: 250,
+                    command: Command::Duty(Leg::LeftFront, angle_to_duty(180, 1024)),
+                },
+            ]
+        );
+        assert_eq!(
+            log.duties_for(Leg::LeftFront),
+            vec![angle_to_duty(0, 1024), angle_to_duty(180, 1024)]
+        );
+
+        // Clearing drops records but keeps the simulated clock
+        log.clear();
+        assert!(log.records().is_empty());
+        assert_eq!(log.now_ms(), 250);
+    }
+}